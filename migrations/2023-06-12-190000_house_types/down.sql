@@ -0,0 +1,22 @@
+CREATE TABLE houses_old (
+  id          INTEGER     NOT NULL,
+  kind        TEXT        NOT NULL,
+  street      TEXT        NOT NULL,
+  number      INTEGER     NOT NULL,
+  floor       INTEGER     NOT NULL,
+  postcode    INTEGER     NOT NULL,
+  rooms       INTEGER     NOT NULL,
+  baths       INTEGER     NOT NULL,
+  area        REAL        NOT NULL,
+
+  CONSTRAINT houses_id_pk PRIMARY KEY ("id")
+);
+
+INSERT INTO houses_old (id, kind, street, number, floor, postcode, rooms, baths, area)
+SELECT h.id, t.name, h.street, h.number, h.floor, h.postcode, h.rooms, h.baths, h.area
+FROM houses h JOIN house_types t ON t.id = h.kind_id;
+
+DROP TABLE houses;
+ALTER TABLE houses_old RENAME TO houses;
+
+DROP TABLE house_types;
@@ -0,0 +1,38 @@
+CREATE TABLE house_types (
+  id          INTEGER     NOT NULL,
+  name        TEXT        NOT NULL,
+  retired     BOOLEAN     NOT NULL DEFAULT 0, -- not offered for new houses
+
+  CONSTRAINT house_types_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT house_types_name_uk UNIQUE ("name")
+);
+
+INSERT INTO house_types (name)
+VALUES ('Casa'), ('Loft'), ('Chalet'), ('Dúplex'), ('Apartamento');
+
+-- Kinds written outside the application are kept as new types
+INSERT OR IGNORE INTO house_types (name)
+SELECT DISTINCT kind FROM houses;
+
+-- SQLite cannot add a foreign key to an existing table, so it is rebuilt
+CREATE TABLE houses_new (
+  id          INTEGER     NOT NULL,
+  kind_id     INTEGER     NOT NULL,
+  street      TEXT        NOT NULL,
+  number      INTEGER     NOT NULL,
+  floor       INTEGER     NOT NULL,
+  postcode    INTEGER     NOT NULL,
+  rooms       INTEGER     NOT NULL,
+  baths       INTEGER     NOT NULL,
+  area        REAL        NOT NULL,
+
+  CONSTRAINT houses_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT houses_kind_id_fk FOREIGN KEY ("kind_id") REFERENCES house_types ("id")
+);
+
+INSERT INTO houses_new (id, kind_id, street, number, floor, postcode, rooms, baths, area)
+SELECT h.id, t.id, h.street, h.number, h.floor, h.postcode, h.rooms, h.baths, h.area
+FROM houses h JOIN house_types t ON t.name = h.kind;
+
+DROP TABLE houses;
+ALTER TABLE houses_new RENAME TO houses;
@@ -0,0 +1,2 @@
+DROP TABLE house_owners;
+DROP TABLE owners;
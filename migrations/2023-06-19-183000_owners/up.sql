@@ -0,0 +1,23 @@
+CREATE TABLE owners (
+  id          INTEGER     NOT NULL,
+  name        TEXT        NOT NULL,
+  phone       TEXT        NOT NULL DEFAULT '',
+  email       TEXT        NOT NULL DEFAULT '',
+  tax_id      TEXT        NOT NULL DEFAULT '',
+  notes       TEXT        NOT NULL DEFAULT '',
+
+  CONSTRAINT owners_id_pk PRIMARY KEY ("id")
+);
+
+CREATE TABLE house_owners (
+  house_id    INTEGER     NOT NULL,
+  owner_id    INTEGER     NOT NULL,
+  share       REAL        NOT NULL, -- percentage of the house that belongs to the owner
+
+  CONSTRAINT house_owners_pk PRIMARY KEY ("house_id", "owner_id"),
+  CONSTRAINT house_owners_house_id_fk FOREIGN KEY ("house_id") REFERENCES houses ("id") ON DELETE CASCADE,
+  CONSTRAINT house_owners_owner_id_fk FOREIGN KEY ("owner_id") REFERENCES owners ("id"),
+  CONSTRAINT house_owners_share_ck CHECK ("share" > 0 AND "share" <= 100)
+);
+
+CREATE INDEX house_owners_owner_id_ix ON house_owners ("owner_id");
@@ -0,0 +1,5 @@
+DROP TABLE price_history;
+ALTER TABLE houses DROP COLUMN expenses;
+ALTER TABLE houses DROP COLUMN currency;
+ALTER TABLE houses DROP COLUMN price;
+ALTER TABLE houses DROP COLUMN operation;
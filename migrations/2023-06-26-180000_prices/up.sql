@@ -0,0 +1,21 @@
+-- The houses that already exist are for sale, without a price yet
+ALTER TABLE houses ADD COLUMN operation TEXT NOT NULL DEFAULT 'sale'
+  CONSTRAINT houses_operation_ck CHECK ("operation" IN ('sale', 'rent', 'both'));
+ALTER TABLE houses ADD COLUMN price REAL NOT NULL DEFAULT 0 -- monthly when it is rented
+  CONSTRAINT houses_price_ck CHECK ("price" >= 0);
+ALTER TABLE houses ADD COLUMN currency TEXT NOT NULL DEFAULT 'ARS'; -- ISO 4217 code
+ALTER TABLE houses ADD COLUMN expenses REAL NOT NULL DEFAULT 0 -- monthly
+  CONSTRAINT houses_expenses_ck CHECK ("expenses" >= 0);
+
+CREATE TABLE price_history (
+  id          INTEGER     NOT NULL,
+  house_id    INTEGER     NOT NULL,
+  price       REAL        NOT NULL,
+  currency    TEXT        NOT NULL,
+  changed_at  TIMESTAMP   NOT NULL,
+
+  CONSTRAINT price_history_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT price_history_house_id_fk FOREIGN KEY ("house_id") REFERENCES houses ("id") ON DELETE CASCADE
+);
+
+CREATE INDEX price_history_house_id_ix ON price_history ("house_id");
@@ -0,0 +1,38 @@
+CREATE TABLE tenants (
+  id          INTEGER     NOT NULL,
+  name        TEXT        NOT NULL,
+  phone       TEXT        NOT NULL DEFAULT '',
+  email       TEXT        NOT NULL DEFAULT '',
+  tax_id      TEXT        NOT NULL DEFAULT '',
+  notes       TEXT        NOT NULL DEFAULT '',
+
+  CONSTRAINT tenants_id_pk PRIMARY KEY ("id")
+);
+
+-- The leases are not deleted with the house, they are the record of its rentals
+CREATE TABLE leases (
+  id            INTEGER     NOT NULL,
+  house_id      INTEGER     NOT NULL,
+  tenant_id     INTEGER     NOT NULL,
+  start_date    DATE        NOT NULL,
+  end_date      DATE        NOT NULL, -- last day of the lease
+  rent          REAL        NOT NULL, -- monthly
+  currency      TEXT        NOT NULL,
+  deposit       REAL        NOT NULL DEFAULT 0,
+  index_months  INTEGER     NOT NULL DEFAULT 0, -- the rent is updated every index_months, 0 is a fixed rent
+  index_rate    REAL        NOT NULL DEFAULT 0, -- percentage of each update
+  status        TEXT        NOT NULL DEFAULT 'active',
+
+  CONSTRAINT leases_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT leases_house_id_fk FOREIGN KEY ("house_id") REFERENCES houses ("id"),
+  CONSTRAINT leases_tenant_id_fk FOREIGN KEY ("tenant_id") REFERENCES tenants ("id"),
+  CONSTRAINT leases_dates_ck CHECK ("end_date" > "start_date"),
+  CONSTRAINT leases_amounts_ck CHECK ("rent" > 0 AND "deposit" >= 0 AND "index_months" >= 0 AND "index_rate" >= 0),
+  CONSTRAINT leases_status_ck CHECK ("status" IN ('active', 'ended', 'cancelled'))
+);
+
+CREATE INDEX leases_house_id_ix ON leases ("house_id");
+CREATE INDEX leases_tenant_id_ix ON leases ("tenant_id");
+
+-- A house has at most one active lease
+CREATE UNIQUE INDEX leases_active_ux ON leases ("house_id") WHERE "status" = 'active';
@@ -0,0 +1,16 @@
+-- The charges are not stored, they follow from the terms of each lease
+CREATE TABLE payments (
+  id          INTEGER     NOT NULL,
+  lease_id    INTEGER     NOT NULL,
+  paid_on     DATE        NOT NULL,
+  amount      REAL        NOT NULL, -- in the currency of the lease
+  method      TEXT        NOT NULL,
+  notes       TEXT        NOT NULL DEFAULT '',
+
+  CONSTRAINT payments_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT payments_lease_id_fk FOREIGN KEY ("lease_id") REFERENCES leases ("id"),
+  CONSTRAINT payments_amount_ck CHECK ("amount" > 0),
+  CONSTRAINT payments_method_ck CHECK ("method" IN ('cash', 'transfer', 'check', 'card', 'other'))
+);
+
+CREATE INDEX payments_lease_id_ix ON payments ("lease_id");
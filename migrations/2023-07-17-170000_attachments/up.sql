@@ -0,0 +1,20 @@
+-- The files are kept outside the database, in a directory next to it, named by the hash
+CREATE TABLE attachments (
+  id          INTEGER     NOT NULL,
+  house_id    INTEGER     NOT NULL,
+  hash        TEXT        NOT NULL, -- SHA-256 of the content, in hexadecimal
+  name        TEXT        NOT NULL, -- of the file that was added
+  size        BIGINT      NOT NULL,
+  cover       BOOLEAN     NOT NULL DEFAULT 0,
+  added_at    TIMESTAMP   NOT NULL,
+
+  CONSTRAINT attachments_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT attachments_house_id_fk FOREIGN KEY ("house_id") REFERENCES houses ("id") ON DELETE CASCADE,
+  CONSTRAINT attachments_hash_uk UNIQUE ("house_id", "hash"),
+  CONSTRAINT attachments_size_ck CHECK ("size" >= 0)
+);
+
+CREATE INDEX attachments_hash_ix ON attachments ("hash");
+
+-- At most one cover for each house
+CREATE UNIQUE INDEX attachments_cover_ux ON attachments ("house_id") WHERE "cover";
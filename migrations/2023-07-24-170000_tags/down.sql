@@ -0,0 +1,2 @@
+DROP TABLE house_tags;
+DROP TABLE tags;
@@ -0,0 +1,22 @@
+CREATE TABLE tags (
+  id          INTEGER     NOT NULL,
+  name        TEXT        NOT NULL,
+
+  CONSTRAINT tags_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT tags_name_uk UNIQUE ("name")
+);
+
+INSERT INTO tags (name)
+VALUES ('Ascensor'), ('Aire acondicionado'), ('Balcón'), ('Calefacción central'), ('Cochera'),
+       ('Jardín'), ('Pileta'), ('Terraza'), ('Amoblado'), ('Acepta mascotas');
+
+CREATE TABLE house_tags (
+  house_id    INTEGER     NOT NULL,
+  tag_id      INTEGER     NOT NULL,
+
+  CONSTRAINT house_tags_pk PRIMARY KEY ("house_id", "tag_id"),
+  CONSTRAINT house_tags_house_id_fk FOREIGN KEY ("house_id") REFERENCES houses ("id") ON DELETE CASCADE,
+  CONSTRAINT house_tags_tag_id_fk FOREIGN KEY ("tag_id") REFERENCES tags ("id")
+);
+
+CREATE INDEX house_tags_tag_id_ix ON house_tags ("tag_id");
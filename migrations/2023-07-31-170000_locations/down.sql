@@ -0,0 +1,6 @@
+DROP INDEX houses_latitude_ix;
+ALTER TABLE houses DROP COLUMN longitude;
+ALTER TABLE houses DROP COLUMN latitude;
+ALTER TABLE houses DROP COLUMN country;
+ALTER TABLE houses DROP COLUMN province;
+ALTER TABLE houses DROP COLUMN city;
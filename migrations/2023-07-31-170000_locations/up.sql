@@ -0,0 +1,10 @@
+-- The coordinates are entered by hand or imported, in decimal degrees
+ALTER TABLE houses ADD COLUMN city TEXT NOT NULL DEFAULT '';
+ALTER TABLE houses ADD COLUMN province TEXT NOT NULL DEFAULT '';
+ALTER TABLE houses ADD COLUMN country TEXT NOT NULL DEFAULT '';
+ALTER TABLE houses ADD COLUMN latitude REAL NULL
+  CONSTRAINT houses_latitude_ck CHECK ("latitude" BETWEEN -90 AND 90);
+ALTER TABLE houses ADD COLUMN longitude REAL NULL
+  CONSTRAINT houses_longitude_ck CHECK ("longitude" BETWEEN -180 AND 180);
+
+CREATE INDEX houses_latitude_ix ON houses ("latitude");
@@ -0,0 +1,25 @@
+-- Without a foreign key, the entries of a house outlive it
+CREATE TABLE audit_log (
+  id          INTEGER     NOT NULL,
+  house_id    INTEGER     NOT NULL,
+  action      TEXT        NOT NULL,
+  changes     TEXT        NOT NULL, -- JSON list of {field, before, after}
+  user_name   TEXT        NOT NULL,
+  changed_at  TIMESTAMP   NOT NULL,
+
+  CONSTRAINT audit_log_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT audit_log_action_ck CHECK ("action" IN ('create', 'update', 'delete'))
+);
+
+CREATE INDEX audit_log_house_id_ix ON audit_log ("house_id");
+
+-- Append only, the entries are never changed nor removed
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
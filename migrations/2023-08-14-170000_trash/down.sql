@@ -0,0 +1,33 @@
+-- The houses in the trash are purged, and so are the entries of the actions that did not exist
+DELETE FROM houses WHERE "deleted_at" IS NOT NULL;
+
+CREATE TABLE audit_log_old (
+  id          INTEGER     NOT NULL,
+  house_id    INTEGER     NOT NULL,
+  action      TEXT        NOT NULL,
+  changes     TEXT        NOT NULL, -- JSON list of {field, before, after}
+  user_name   TEXT        NOT NULL,
+  changed_at  TIMESTAMP   NOT NULL,
+
+  CONSTRAINT audit_log_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT audit_log_action_ck CHECK ("action" IN ('create', 'update', 'delete'))
+);
+
+INSERT INTO audit_log_old SELECT * FROM audit_log WHERE "action" IN ('create', 'update', 'delete');
+DROP TABLE audit_log;
+ALTER TABLE audit_log_old RENAME TO audit_log;
+
+CREATE INDEX audit_log_house_id_ix ON audit_log ("house_id");
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+DROP INDEX houses_deleted_at_ix;
+ALTER TABLE houses DROP COLUMN deleted_at;
@@ -0,0 +1,35 @@
+-- A deleted house stays in the trash until it is restored or purged, NULL is a house in use
+ALTER TABLE houses ADD COLUMN deleted_at TIMESTAMP NULL;
+
+CREATE INDEX houses_deleted_at_ix ON houses ("deleted_at");
+
+-- The audit log also records the houses restored from the trash and the purged ones.
+-- SQLite cannot change a CHECK constraint, so the table is rebuilt with its entries;
+-- DROP TABLE does not fire the triggers that keep it append-only.
+CREATE TABLE audit_log_new (
+  id          INTEGER     NOT NULL,
+  house_id    INTEGER     NOT NULL,
+  action      TEXT        NOT NULL,
+  changes     TEXT        NOT NULL, -- JSON list of {field, before, after}
+  user_name   TEXT        NOT NULL,
+  changed_at  TIMESTAMP   NOT NULL,
+
+  CONSTRAINT audit_log_id_pk PRIMARY KEY ("id"),
+  CONSTRAINT audit_log_action_ck CHECK ("action" IN ('create', 'update', 'delete', 'restore', 'purge'))
+);
+
+INSERT INTO audit_log_new SELECT * FROM audit_log;
+DROP TABLE audit_log;
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX audit_log_house_id_ix ON audit_log ("house_id");
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
@@ -0,0 +1,3 @@
+-- Number of times the house was saved, an update is only written over the version it read.
+-- Several people can open the same file, so a change made since then is not overwritten.
+ALTER TABLE houses ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -1,31 +1,50 @@
 mod dialogs;
-mod utils;
+mod history;
 mod wrapper;
 
-use crate::base::{Filter, House, DAO, HOUSE_TYPES};
-
-use dialogs::{FilterDialog, MDButton, MessageDialog};
-use utils::test_positive_and_zero_number;
-use wrapper::Widget;
+use crate::base::{
+    self, Attachment, AuditEntry, Error, Filter, House, HouseType, Lease, LeaseStatus, Owner,
+    Ownership, Page, Tag, Tenant, DAO,
+};
+use crate::config::Config;
+use crate::csv::{self, Report};
+use crate::data::Service;
+use crate::gazetteer::{Gazetteer, Resolution};
+use crate::json;
+use crate::utils::test_positive_and_zero_number;
+
+use dialogs::{
+    choose_file, AccountDialog, BulkDialog, ContactsDialog, ExpiringDialog, FilterDialog,
+    KindsDialog, MDButton, MessageDialog, OverdueDialog, StreetChoice, StreetsDialog, TableDialog,
+    TagsDialog, TrashDialog,
+};
+use history::{History, Operation};
+use wrapper::{menu_label, Widget};
 
 use std::{
     cell::RefCell,
     collections::{BTreeMap, HashMap},
-    fmt::Display,
+    env, error,
+    fmt::{Debug, Display},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
     rc::Rc,
 };
 
+use chrono::NaiveDate;
 use fltk::{
     app::{self, channel, App, Receiver, Scheme, Sender},
-    browser::HoldBrowser,
+    browser::{CheckBrowser, HoldBrowser, MultiBrowser},
     button::Button,
-    dialog,
-    enums::{CallbackTrigger, Color, Event, Font, FrameType, Key},
+    dialog, draw,
+    enums::{Align, CallbackTrigger, Color, Event, Font, FrameType, Key, Shortcut},
     frame::Frame,
-    group::Flex,
-    image::SvgImage,
+    group::{Flex, Pack, PackType, Scroll, ScrollType, Tabs},
+    image::{SharedImage, SvgImage},
     input::{FloatInput, Input, IntInput},
-    menu::Choice,
+    menu::{Choice, MenuBar, MenuFlag},
     prelude::*,
     window::DoubleWindow,
 };
@@ -35,6 +54,9 @@ pub const NEW_HOUSE: &str = "«nuevo»";
 pub const MARGIN_SIZE: i32 = 16;
 pub const BUTTON_WIDTH: i32 = 128;
 pub const BUTTON_HEIGHT: i32 = 32;
+pub const PAGE_SIZE: i64 = 100;
+pub const TABLE_ROWS: i64 = 2000; // the table has no pages, a larger filter is cut
+pub const SEARCH_DELAY: f64 = 0.4; // seconds
 
 pub const FOREGROUND_COLOR: Color = Color::from_rgb(190, 190, 190);
 pub const SELECTION_COLOR: Color = Color::from_rgb(13, 13, 13);
@@ -52,39 +74,199 @@ pub enum Action {
     Delete,
     Change,
     Close,
+    PrevPage,
+    NextPage,
+    Import,
+    Export,
+    Undo,
+    Redo,
+    Kinds,
+    Tags,
+    OpenDatabase,
+    NewDatabase,
+    Recent(usize),
+    PickOwner,
+    AddOwner,
+    RemoveOwner,
+    Owners,
+    PickLease,
+    AddLease,
+    SaveLease,
+    DeleteLease,
+    Payments,
+    Tenants,
+    Expiring,
+    Overdue,
+    PickAttachment,
+    AddAttachment,
+    RemoveAttachment,
+    CoverAttachment,
+    OpenAttachment,
+    OpenGazetteer,
+    CloseGazetteer,
+    Trash,
+    Bulk,
+    Table,
+    Search,
+}
+
+const FILE_TYPES: &str = "CSV\t*.csv\nJSON\t*.json";
+const DATABASE_TYPES: &str = "SQLite\t*.{sqlite,sqlite3,db}";
+const GAZETTEER_TYPES: &str = "Callejero\t*.{csv,sqlite,sqlite3,db}";
+const ATTACHMENT_TYPES: &str = "Imágenes y documentos\t*.{png,jpg,jpeg,gif,bmp,svg,pdf}\nTodos\t*";
+
+pub fn alert(message: &str) {
+    dialog::alert_default(message);
+}
+
+// The format is chosen by the extension, CSV unless it is .json
+
+fn export_file(
+    dao: &mut dyn DAO,
+    filter: &Filter,
+    path: &Path,
+) -> Result<usize, Box<dyn error::Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let count = if json::is_json(path) {
+        json::export(dao, filter, &mut writer)?
+    } else {
+        csv::export(dao, filter, &mut writer)?
+    };
+    writer.flush()?;
+    Ok(count)
+}
+
+fn import_file(dao: &mut dyn DAO, path: &Path) -> Result<Report, Box<dyn error::Error>> {
+    let mut file = File::open(path)?;
+    if json::is_json(path) {
+        Ok(json::import(dao, &mut file)?)
+    } else {
+        Ok(csv::import(dao, &mut file)?)
+    }
+}
+
+const DATA_KEYS: [&str; 17] = [
+    "kind",
+    "street",
+    "number",
+    "floor",
+    "postcode",
+    "rooms",
+    "baths",
+    "area",
+    "operation",
+    "price",
+    "currency",
+    "expenses",
+    "city",
+    "province",
+    "country",
+    "latitude",
+    "longitude",
+];
+
+// Fields of the lease selected in the Alquiler tab, they are not part of the house
+const LEASE_KEYS: [&str; 9] = [
+    "tenant",
+    "start",
+    "end",
+    "rent",
+    "lease_currency",
+    "deposit",
+    "status",
+    "index_months",
+    "index_rate",
+];
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Side of the square where the thumbnails of the attachments are drawn
+const THUMBNAIL_SIZE: i32 = 96;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unsaved {
+    Clean,
+    Saved,
+    Discarded,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+enum Conflict {
+    Reload,
+    Overwrite(Box<House>), // as it is now in the database
+    Cancelled,
 }
 
 // Rc<RefCell<···>> https://doc.rust-lang.org/book/ch15-05-interior-mutability.html
 
-pub struct Gui<'a> {
+pub struct Gui {
     app: App,
     win: DoubleWindow,
-    dao: &'a mut dyn DAO,
+    menu: Option<MenuBar>,
+    search: Option<Input>, // out of the inputs, clearing the form does not clear it
+    dao: Box<dyn DAO>,
+    database: Option<PathBuf>, // None when the DAO is not a file (--demo)
+    config: Config,
     sender: Sender<Action>,
     receiver: Receiver<Action>,
     inputs: HashMap<String, Widget>,
     idxhid: HashMap<String, i32>,
     houses: BTreeMap<i32, Rc<RefCell<House>>>,
     buttons: HashMap<Action, Button>,
+    labels: HashMap<String, Frame>,
     hid_select: i32,
     current_filter: Filter,
+    offset: i64,
+    total: i64,
+    history: History,
+    kinds: Vec<HouseType>,
+    kind_choices: Vec<String>, // names in the kind Choice, by index
+    tags: Vec<Tag>,            // items of the tags checklist, by index
+    owners: Vec<Owner>,
+    house_owners: Vec<Ownership>, // owners of the house in the form, saved with it
+    tenants: Vec<Tenant>,
+    leases: Vec<Lease>, // of the selected house, saved on their own
+    expiring_days: i64,
+    attachments: Vec<Attachment>, // of the selected house, saved on their own
+    strip: Option<Pack>,          // thumbnails of the attachments
+    gazetteer: Option<Gazetteer>, // streets to check the addresses when saving
 }
 
-impl<'a> Gui<'a> {
-    pub fn new(dao: &'a mut dyn DAO) -> Self {
+impl Gui {
+    pub fn new(dao: Box<dyn DAO>, database: Option<PathBuf>, config: Config) -> Self {
         let (sender, receiver) = channel::<Action>();
         Gui {
             dao,
+            database,
+            config,
             sender,
             receiver,
             app: App::default(),
             win: DoubleWindow::default(),
+            menu: None,
+            search: None,
             inputs: HashMap::new(),
             idxhid: HashMap::new(),
             houses: BTreeMap::new(),
             buttons: HashMap::new(),
+            labels: HashMap::new(),
             hid_select: -1,
             current_filter: Filter::default(),
+            offset: 0,
+            total: 0,
+            history: History::default(),
+            kinds: Vec::new(),
+            kind_choices: Vec::new(),
+            tags: Vec::new(),
+            owners: Vec::new(),
+            house_owners: Vec::new(),
+            tenants: Vec::new(),
+            leases: Vec::new(),
+            expiring_days: 30,
+            attachments: Vec::new(),
+            strip: None,
+            gazetteer: None,
         }
     }
 
@@ -101,11 +283,9 @@ impl<'a> Gui<'a> {
         let (r, g, b) = SELECTION_COLOR.to_rgb();
         app::set_selection_color(r, g, b);
 
-        self.win.set_label("Wohnen - Schcriher");
-
         let (sx, sy, sw, sh) = app::screen_work_area(self.win.screen_num());
         let w = 900;
-        let h = 500;
+        let h = 600;
 
         if w > sw || h > sh {
             let x = sx + (sw - 420) / 2;
@@ -129,7 +309,13 @@ impl<'a> Gui<'a> {
 
         self.win.begin();
 
-        let mut main = Flex::default_fill().row();
+        let mut outer = Flex::default_fill().column();
+
+        let menu = MenuBar::default();
+        outer.set_size(&menu, BUTTON_HEIGHT);
+        self.menu = Some(menu);
+
+        let mut main = Flex::default().row();
         main.set_margin(MARGIN_SIZE);
 
         // --- LEFT ---------------------------------------------
@@ -142,14 +328,58 @@ impl<'a> Gui<'a> {
             self.create_button("Nuevo", Action::New);
             self.create_button("Filtrar", Action::Filter);
             self.create_button("Quitar Filtro", Action::Unfilter);
+            self.create_button("Tipos", Action::Kinds);
             row.end();
             left.set_size(&row, BUTTON_HEIGHT);
         }
 
         {
-            let mut select = HoldBrowser::default();
+            let mut search = Input::default();
+            search.set_tooltip("Buscar por calle, tipo o código postal, dentro del filtro");
+            search.set_trigger(CallbackTrigger::Changed);
+            // It searches once the typing pauses, not for each key with its question about unsaved changes
+            let sender = self.sender;
+            let mut pending = None;
+            search.set_callback(move |_| {
+                if let Some(handle) = pending.take() {
+                    app::remove_timeout3(handle);
+                }
+                pending = Some(app::add_timeout3(SEARCH_DELAY, move |_| {
+                    sender.send(Action::Search)
+                }));
+            });
+            left.set_size(&search, BUTTON_HEIGHT);
+            self.search = Some(search);
+        }
+
+        {
+            // The form shows the last clicked house, Ctrl and Shift select several for Action::Bulk
+            let mut select = MultiBrowser::default();
+            // address, price and distance to the filter point
+            select.set_column_widths(&[250, 150, 80]);
             select.emit(self.sender, Action::Select);
-            self.inputs.insert("select".to_owned(), Widget::Browser(select));
+            self.inputs
+                .insert("select".to_owned(), Widget::Multi(select));
+        }
+
+        {
+            let row = Flex::default().row();
+            self.create_button("◀ Anterior", Action::PrevPage);
+            let pager = Frame::default();
+            self.labels.insert("pager".to_owned(), pager);
+            self.create_button("Siguiente ▶", Action::NextPage);
+            row.end();
+            left.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        {
+            let row = Flex::default().row();
+            self.create_button("Deshacer", Action::Undo);
+            self.create_button("Rehacer", Action::Redo);
+            self.create_button("Importar", Action::Import);
+            self.create_button("Exportar", Action::Export);
+            row.end();
+            left.set_size(&row, BUTTON_HEIGHT);
         }
 
         left.end();
@@ -167,15 +397,236 @@ impl<'a> Gui<'a> {
         let sep = Frame::default();
         right.set_size(&sep, 8);
 
-        self.create_input("id", "Número de registro");
-        self.create_input("kind", "Tipo de vivienda");
-        self.create_input("street", "Calle");
-        self.create_input("number", "Número");
-        self.create_input("floor", "Piso");
-        self.create_input("postcode", "Código postal");
-        self.create_input("rooms", "Número de habitaciones");
-        self.create_input("baths", "Número de baños");
-        self.create_input("area", "Superficie total (m²)");
+        let mut tabs = Tabs::default();
+        {
+            let mut data = Flex::default().column().with_label("Datos");
+            data.set_margin(MARGIN_SIZE / 2);
+
+            self.create_input("id", "Número de registro");
+            self.create_input("kind", "Tipo de vivienda");
+            self.create_input("street", "Calle");
+            self.create_input("number", "Número");
+            self.create_input("floor", "Piso");
+            self.create_input("postcode", "Código postal");
+            self.create_input("rooms", "Número de habitaciones");
+            self.create_input("baths", "Número de baños");
+            self.create_input("area", "Superficie total (m²)");
+
+            data.end();
+        }
+        {
+            let mut location = Flex::default().column().with_label("Ubicación");
+            location.set_margin(MARGIN_SIZE / 2);
+
+            for (key, text) in [
+                ("city", "Ciudad"),
+                ("province", "Provincia"),
+                ("country", "País"),
+                ("latitude", "Latitud"),
+                ("longitude", "Longitud"),
+            ] {
+                let row = self.create_input(key, text);
+                location.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            let mut note = Frame::default().with_label(
+                "Las coordenadas van en grados decimales, negativas al sur y al oeste;\n\
+                 se cargan a mano o se importan, y se usan para filtrar por distancia",
+            );
+            note.set_label_size(12);
+            note.set_align(Align::Top | Align::Inside);
+
+            location.end();
+        }
+        {
+            let mut prices = Flex::default().column().with_label("Precio");
+            prices.set_margin(MARGIN_SIZE / 2);
+
+            for (key, text) in [
+                ("operation", "Operación"),
+                ("price", "Precio pedido"),
+                ("currency", "Moneda"),
+                ("expenses", "Expensas mensuales"),
+            ] {
+                let row = self.create_input(key, text);
+                prices.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            let label = Frame::default().with_label("Historial de precios");
+            prices.set_size(&label, BUTTON_HEIGHT);
+
+            let mut browser = HoldBrowser::default();
+            browser.set_column_widths(&[160]); // date and price
+            self.inputs
+                .insert("prices".to_owned(), Widget::Browser(browser));
+
+            prices.end();
+        }
+        {
+            let mut features = Flex::default().column().with_label("Características");
+            features.set_margin(MARGIN_SIZE / 2);
+
+            let mut checks = CheckBrowser::default();
+            checks.set_tooltip("Marque las características que tiene la vivienda");
+            checks.set_trigger(CallbackTrigger::Changed);
+            checks.emit(self.sender, Action::Change);
+            self.inputs
+                .insert("tags".to_owned(), Widget::Checks(checks));
+
+            {
+                let row = Flex::default().row();
+                Frame::default();
+                self.create_button("Características…", Action::Tags);
+                row.end();
+                features.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            features.end();
+        }
+        {
+            let mut owners = Flex::default().column().with_label("Propietarios");
+            owners.set_margin(MARGIN_SIZE / 2);
+
+            let mut browser = HoldBrowser::default();
+            browser.emit(self.sender, Action::PickOwner);
+            self.inputs
+                .insert("owners".to_owned(), Widget::Browser(browser));
+
+            self.create_input("phone", "Teléfono");
+            self.create_input("email", "Correo electrónico");
+            self.create_input("tax_id", "Identificación fiscal");
+            self.create_input("notes", "Notas");
+
+            {
+                let mut row = Flex::default().row();
+                let mut owner = Choice::default();
+                owner.set_tooltip("Propietario a agregar a la vivienda");
+                self.inputs
+                    .insert("owner".to_owned(), Widget::Choice(owner));
+                let mut share = FloatInput::default();
+                share.set_tooltip("Porcentaje de la vivienda, si se omite se asume el resto");
+                row.set_size(&share, 80);
+                self.inputs
+                    .insert("share".to_owned(), Widget::FInput(share));
+                let percent = Frame::default().with_label("%");
+                row.set_size(&percent, 16);
+                self.create_button("Agregar", Action::AddOwner);
+                row.end();
+                owners.set_size(&row, BUTTON_HEIGHT);
+            }
+            {
+                let row = Flex::default().row();
+                self.create_button("Quitar", Action::RemoveOwner);
+                self.create_button("Propietarios…", Action::Owners);
+                row.end();
+                owners.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            owners.end();
+        }
+        {
+            let mut leases = Flex::default().column().with_label("Alquiler");
+            leases.set_margin(MARGIN_SIZE / 2);
+
+            let mut browser = HoldBrowser::default();
+            browser.set_column_widths(&[100, 200]); // status, dates and tenant
+            browser.emit(self.sender, Action::PickLease);
+            self.inputs
+                .insert("leases".to_owned(), Widget::Browser(browser));
+
+            {
+                let row = Flex::default().row();
+                self.create_input("tenant", "Inquilino");
+                self.create_button("Inquilinos…", Action::Tenants);
+                row.end();
+                leases.set_size(&row, BUTTON_HEIGHT);
+            }
+            for pair in [
+                [("start", "Desde"), ("end", "Hasta")],
+                [("rent", "Alquiler"), ("lease_currency", "Moneda")],
+                [("deposit", "Depósito"), ("status", "Estado")],
+                [
+                    ("index_months", "Ajuste (meses)"),
+                    ("index_rate", "Ajuste (%)"),
+                ],
+            ] {
+                let row = Flex::default().row();
+                for (key, text) in pair {
+                    self.create_input(key, text);
+                }
+                row.end();
+                leases.set_size(&row, BUTTON_HEIGHT);
+            }
+            {
+                let row = Flex::default().row();
+                self.create_button("Agregar", Action::AddLease);
+                self.create_button("Guardar", Action::SaveLease);
+                self.create_button("Borrar", Action::DeleteLease);
+                self.create_button("Pagos…", Action::Payments);
+                row.end();
+                leases.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            leases.end();
+        }
+        {
+            let mut files = Flex::default().column().with_label("Adjuntos");
+            files.set_margin(MARGIN_SIZE / 2);
+
+            let mut scroll = Scroll::default();
+            scroll.set_type(ScrollType::Horizontal);
+            let mut strip = Pack::default_fill();
+            strip.set_type(PackType::Horizontal);
+            strip.set_spacing(MARGIN_SIZE / 2);
+            strip.end();
+            scroll.end();
+            // Room for the thumbnail, its name and the scrollbar
+            files.set_size(&scroll, THUMBNAIL_SIZE + 2 * BUTTON_HEIGHT);
+            self.strip = Some(strip);
+
+            let mut browser = HoldBrowser::default();
+            browser.set_column_widths(&[240, 100]); // name, size and cover
+            browser.emit(self.sender, Action::PickAttachment);
+            self.inputs
+                .insert("attachments".to_owned(), Widget::Browser(browser));
+
+            {
+                let row = Flex::default().row();
+                self.create_button("Agregar…", Action::AddAttachment);
+                self.create_button("Quitar", Action::RemoveAttachment);
+                self.create_button("Portada", Action::CoverAttachment);
+                self.create_button("Abrir", Action::OpenAttachment);
+                row.end();
+                files.set_size(&row, BUTTON_HEIGHT);
+            }
+
+            files.end();
+        }
+        {
+            let mut history = Flex::default().column().with_label("Historial");
+            history.set_margin(MARGIN_SIZE / 2);
+
+            let label =
+                Frame::default().with_label("Cambios de la vivienda, el más reciente primero");
+            history.set_size(&label, BUTTON_HEIGHT);
+
+            // Each change has a line with the date, the operation and the user, then one line for each field
+            let mut browser = HoldBrowser::default();
+            browser.set_column_widths(&[140, 110]); // date, operation and user
+            self.inputs
+                .insert("audit".to_owned(), Widget::Browser(browser));
+
+            history.end();
+        }
+        tabs.end();
+        // The pages are kept under the tab labels when the Flex sizes the Tabs
+        tabs.resize_callback(|t, x, y, w, h| {
+            for index in 0..t.children() {
+                if let Some(mut page) = t.child(index) {
+                    page.resize(x, y + BUTTON_HEIGHT, w, h - BUTTON_HEIGHT);
+                }
+            }
+        });
 
         let sep = Frame::default();
         right.set_size(&sep, 16);
@@ -195,6 +646,8 @@ impl<'a> Gui<'a> {
 
         main.end();
 
+        outer.end();
+
         self.win.end();
 
         let icon = include_bytes!("../assets/icon.svg");
@@ -209,6 +662,19 @@ impl<'a> Gui<'a> {
                         sender.send(Action::Close);
                         true
                     }
+                    // Inside a text input these keys undo the typing instead
+                    key if app::is_event_ctrl() && key == Key::from_char('z') => {
+                        sender.send(if app::is_event_shift() {
+                            Action::Redo
+                        } else {
+                            Action::Undo
+                        });
+                        true
+                    }
+                    key if app::is_event_ctrl() && key == Key::from_char('y') => {
+                        sender.send(Action::Redo);
+                        true
+                    }
                     _ => false,
                 },
                 _ => false,
@@ -225,6 +691,224 @@ impl<'a> Gui<'a> {
         });
     }
 
+    fn set_title(&mut self) {
+        match &self.database {
+            Some(path) => {
+                let name = path
+                    .file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy();
+                self.win.set_label(&format!("Wohnen - Schcriher - {name}"));
+            }
+            None => self.win.set_label("Wohnen - Schcriher"),
+        }
+    }
+
+    fn fill_menu(&mut self) {
+        let Some(menu) = self.menu.as_mut() else {
+            return;
+        };
+        let sender = self.sender;
+        menu.clear();
+        menu.add_emit(
+            "Base de datos/Abrir…\t",
+            Shortcut::Ctrl | 'o',
+            MenuFlag::Normal,
+            sender,
+            Action::OpenDatabase,
+        );
+        menu.add_emit(
+            "Base de datos/Nueva…\t",
+            Shortcut::Ctrl | 'n',
+            MenuFlag::MenuDivider,
+            sender,
+            Action::NewDatabase,
+        );
+        if self.config.recent.is_empty() {
+            menu.add(
+                "Base de datos/Recientes/(ninguna)",
+                Shortcut::None,
+                MenuFlag::Inactive,
+                |_| {},
+            );
+        }
+        for (index, path) in self.config.recent.iter().enumerate() {
+            // The database in use is shown but cannot be chosen again
+            let flag = if self.database.as_ref() == Some(path) {
+                MenuFlag::Inactive
+            } else {
+                MenuFlag::Normal
+            };
+            let label = format!(
+                "Base de datos/Recientes/{}",
+                menu_label(&path.display().to_string())
+            );
+            menu.add_emit(&label, Shortcut::None, flag, sender, Action::Recent(index));
+        }
+        menu.add_emit(
+            "Base de datos/Papelera…",
+            Shortcut::None,
+            MenuFlag::MenuDivider,
+            sender,
+            Action::Trash,
+        );
+        menu.add_emit(
+            "Base de datos/Salir",
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender,
+            Action::Close,
+        );
+        menu.add_emit(
+            "Viviendas/Editar selección…\t",
+            Shortcut::Ctrl | 'e',
+            MenuFlag::Normal,
+            sender,
+            Action::Bulk,
+        );
+        menu.add_emit(
+            "Viviendas/Tabla…\t",
+            Shortcut::Ctrl | 't',
+            MenuFlag::Normal,
+            sender,
+            Action::Table,
+        );
+        menu.add_emit(
+            "Alquileres/Vencimientos…",
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender,
+            Action::Expiring,
+        );
+        menu.add_emit(
+            "Alquileres/Morosos…",
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender,
+            Action::Overdue,
+        );
+        menu.add_emit(
+            "Alquileres/Inquilinos…",
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender,
+            Action::Tenants,
+        );
+        menu.add_emit(
+            "Callejero/Abrir…",
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender,
+            Action::OpenGazetteer,
+        );
+        let flag = if self.config.gazetteer.is_some() {
+            MenuFlag::Normal
+        } else {
+            MenuFlag::Inactive
+        };
+        menu.add_emit(
+            "Callejero/Quitar",
+            Shortcut::None,
+            flag,
+            sender,
+            Action::CloseGazetteer,
+        );
+    }
+
+    fn load_gazetteer(&mut self) {
+        self.gazetteer = None;
+        if let Some(path) = self.config.gazetteer.clone() {
+            match Gazetteer::load(&path) {
+                Ok(gazetteer) => self.gazetteer = Some(gazetteer),
+                Err(error) => self.show_error(
+                    &format!("No se pudo abrir el callejero {}", path.display()),
+                    &error,
+                ),
+            }
+        }
+        self.fill_menu();
+    }
+
+    fn set_gazetteer(&mut self, path: Option<PathBuf>) {
+        self.config.gazetteer = path;
+        if let Err(error) = self.config.save() {
+            self.show_error("No se pudo guardar la configuración", &error);
+        }
+        self.load_gazetteer();
+        if let Some(gazetteer) = &self.gazetteer {
+            let message = format!("El callejero tiene {} calles", gazetteer.count());
+            self.open_message_dialog("Callejero", &message, vec![MDButton::new("Aceptar", 0)]);
+        }
+    }
+
+    // False when the saving is cancelled
+    fn check_street(&mut self, house: &mut House) -> bool {
+        let Some(gazetteer) = &self.gazetteer else {
+            return true;
+        };
+        let candidates = match gazetteer.resolve(&house.street, house.postcode) {
+            Resolution::Unknown => return true,
+            Resolution::Exact(street) => {
+                street.apply(house);
+                return true;
+            }
+            Resolution::Ambiguous(candidates) => candidates,
+        };
+
+        self.win.deactivate();
+        let width = 760;
+        let height = 420;
+        let (x, y) = self.get_pos(width, height);
+        let mut dialog = StreetsDialog::new(x, y, width, height, &house.street, &candidates);
+        let choice = dialog.run();
+        self.win.activate();
+
+        match choice {
+            StreetChoice::Use(index) => {
+                candidates[index].street.apply(house);
+                true
+            }
+            StreetChoice::Keep => true,
+            StreetChoice::Cancel => false,
+        }
+    }
+
+    // The current one is kept if it fails
+    fn open_database(&mut self, path: &Path, create: bool) {
+        match Service::open(path, create, &self.config.user()) {
+            Ok(service) => {
+                self.dao = Box::new(service);
+                self.database = Some(self.config.add_recent(path));
+                if let Err(error) = self.config.save() {
+                    self.show_error("No se pudo guardar la configuración", &error);
+                }
+                if let Some(before) = self.config.purge_before() {
+                    if let Err(error) = self.dao.purge_deleted(before) {
+                        self.show_error("No se pudo vaciar la papelera", &error);
+                    }
+                }
+                // The operations of the history belong to the previous database
+                self.history = History::default();
+                self.current_filter = Filter::default();
+                self.set_search("");
+                self.hid_select = -1;
+                self.offset = 0;
+                self.set_title();
+                self.fill_menu();
+                self.load_kinds();
+                self.load_tags();
+                self.load_owners();
+                self.load_tenants();
+            }
+            Err(error) => {
+                self.show_error(&format!("No se pudo abrir «{}»", path.display()), &error)
+            }
+        }
+        self.set_buttons_new_save_delete(true, false, false);
+        self.fill_select(); // update delete button
+        self.reset_inputs_color();
+    }
+
     fn get_pos(&self, width: i32, height: i32) -> (i32, i32) {
         let w = self.win.w();
         let h = self.win.h();
@@ -256,10 +940,15 @@ impl<'a> Gui<'a> {
             }
             _ => false,
         });
+        match action {
+            Action::Undo => button.set_tooltip("Ctrl+Z"),
+            Action::Redo => button.set_tooltip("Ctrl+Y"),
+            _ => {}
+        }
         self.buttons.insert(action, button);
     }
 
-    fn create_input(&mut self, key: &str, text: &str) {
+    fn create_input(&mut self, key: &str, text: &str) -> Flex {
         let row = Flex::default().row();
         Frame::default().with_label(text);
         let mut widget = match key {
@@ -271,6 +960,12 @@ impl<'a> Gui<'a> {
                 input.deactivate();
                 Widget::TInput(input)
             }
+            "phone" | "email" | "tax_id" | "notes" => {
+                let mut input = Input::default();
+                input.set_tooltip("Los datos del propietario se modifican con «Propietarios…»");
+                input.set_readonly(true);
+                Widget::TInput(input)
+            }
             "kind" => {
                 let mut kind = Choice::default();
                 kind.emit(self.sender, Action::Change);
@@ -290,6 +985,85 @@ impl<'a> Gui<'a> {
                 input.emit(self.sender, Action::Change);
                 Widget::FInput(input)
             }
+            "operation" => {
+                let mut operation = Choice::default();
+                for value in base::Operation::ALL {
+                    operation.add_choice(&value.to_string());
+                }
+                operation.emit(self.sender, Action::Change);
+                Widget::Choice(operation)
+            }
+            "price" | "expenses" => {
+                let mut input = FloatInput::default();
+                input.set_tooltip(if key == "price" {
+                    "Ingrese el importe, mensual si es un alquiler; 0 si no tiene precio"
+                } else {
+                    "Ingrese el importe mensual, 0 si no tiene expensas"
+                });
+                input.set_trigger(CallbackTrigger::Changed);
+                input.emit(self.sender, Action::Change);
+                Widget::FInput(input)
+            }
+            "tenant" | "status" => {
+                let mut choice = Choice::default();
+                if key == "status" {
+                    for value in LeaseStatus::ALL {
+                        choice.add_choice(&value.to_string());
+                    }
+                }
+                Widget::Choice(choice)
+            }
+            "start" | "end" => {
+                let mut input = Input::default();
+                input.set_tooltip("Fecha en formato AAAA-MM-DD, el último día en «Hasta»");
+                Widget::TInput(input)
+            }
+            "rent" | "deposit" | "index_rate" => {
+                let mut input = FloatInput::default();
+                input.set_tooltip(match key {
+                    "rent" => "Importe mensual al comenzar el contrato",
+                    "deposit" => "Importe del depósito en garantía",
+                    _ => "Porcentaje en que aumenta el alquiler en cada ajuste",
+                });
+                Widget::FInput(input)
+            }
+            "index_months" => {
+                let mut input = IntInput::default();
+                input.set_tooltip("Meses entre cada ajuste del alquiler, 0 si es fijo");
+                Widget::IInput(input)
+            }
+            "lease_currency" => {
+                let mut input = Input::default();
+                input.set_tooltip("Código ISO 4217 de la moneda, por ejemplo ARS o USD");
+                input.set_maximum_size(3);
+                Widget::TInput(input)
+            }
+            "city" | "province" | "country" => {
+                let mut input = Input::default();
+                input.set_tooltip("Puede quedar vacío");
+                input.set_trigger(CallbackTrigger::Changed);
+                input.emit(self.sender, Action::Change);
+                Widget::TInput(input)
+            }
+            "latitude" | "longitude" => {
+                let mut input = FloatInput::default();
+                input.set_tooltip(if key == "latitude" {
+                    "Grados decimales entre -90 y 90, negativa al sur; vacía junto con la longitud si no se conoce"
+                } else {
+                    "Grados decimales entre -180 y 180, negativa al oeste; vacía junto con la latitud si no se conoce"
+                });
+                input.set_trigger(CallbackTrigger::Changed);
+                input.emit(self.sender, Action::Change);
+                Widget::FInput(input)
+            }
+            "currency" => {
+                let mut input = Input::default();
+                input.set_tooltip("Código ISO 4217 de la moneda, por ejemplo ARS o USD");
+                input.set_maximum_size(3);
+                input.set_trigger(CallbackTrigger::Changed);
+                input.emit(self.sender, Action::Change);
+                Widget::TInput(input)
+            }
             _ => {
                 let mut input = IntInput::default();
                 input.set_tooltip("Ingrese solamente números enteros");
@@ -301,6 +1075,7 @@ impl<'a> Gui<'a> {
         widget.set_color(NORMAL_COLOR);
         row.end();
         self.inputs.insert(key.to_owned(), widget);
+        row
     }
 
     fn get_widget(&self, key: &str) -> &Widget {
@@ -335,20 +1110,68 @@ impl<'a> Gui<'a> {
         let select = self.inputs.get_mut("select").unwrap();
         select.clear();
 
-        let mut max = 0;
-        // TODO Pagination should be implemented
-        if let Ok(houses) = self.dao.get_houses() {
-            max = houses.len();
-            for house in houses {
-                if self.current_filter.valid(&house) {
+        let page = Page {
+            offset: self.offset,
+            limit: Some(PAGE_SIZE),
+        };
+        match self.dao.get_houses(&self.current_filter, page) {
+            Ok(page) => {
+                self.total = page.total;
+                for house in page.houses {
                     self.houses.insert(house.id, Rc::new(RefCell::new(house)));
                 }
             }
+            Err(error) => {
+                self.total = 0;
+                self.show_error("No se pudieron leer las viviendas", &error);
+            }
+        }
+
+        if self.offset >= self.total && self.total > 0 {
+            // The page went past the end (deletion, new filter or a new house), go to the last one
+            self.offset = (self.total - 1) / PAGE_SIZE * PAGE_SIZE;
+            return self.fill_select();
         }
 
+        let select = self.inputs.get_mut("select").unwrap();
         for (index, house) in self.houses.values().enumerate() {
             let house = house.borrow();
-            select.add(&format!("{} al {}", house.street, house.number));
+            let distance = match self
+                .current_filter
+                .near
+                .as_ref()
+                .and_then(|near| near.distance(&house))
+            {
+                Some(distance) => format!("{distance:.1} km"),
+                None => String::new(),
+            };
+            // The browser formats whole columns, so the text found by the search is enclosed in «»
+            let search = &self.current_filter.search;
+            let mark = |text: &str| match search.position(text) {
+                Some(range) if !range.is_empty() => {
+                    format!(
+                        "{}«{}»{}",
+                        &text[..range.start],
+                        &text[range.clone()],
+                        &text[range.end..]
+                    )
+                }
+                _ => text.to_owned(),
+            };
+            let mut address = format!("{} al {}", house.street, house.number);
+            if !search.text.is_empty() {
+                match self.current_filter.search_match(&house) {
+                    Some("street") => {
+                        address = format!("{} al {}", mark(&house.street), house.number)
+                    }
+                    Some("kind") => address = format!("{address} · {}", mark(&house.kind)),
+                    Some(_) => {
+                        address = format!("{address} · CP {}", mark(&house.postcode.to_string()))
+                    }
+                    None => {}
+                }
+            }
+            select.add(&format!("{address}\t{}\t{distance}", house.price_text()));
             let idx = (index + 1).to_string();
             self.idxhid.insert(idx.clone(), house.id);
 
@@ -357,104 +1180,845 @@ impl<'a> Gui<'a> {
             }
         }
 
+        let pager = if self.total == 0 {
+            "Sin viviendas".to_owned()
+        } else {
+            let last = self.offset + self.houses.len() as i64;
+            format!("{} a {} de {}", self.offset + 1, last, self.total)
+        };
+        self.labels.get_mut("pager").unwrap().set_label(&pager);
+
         let selected = select.get() != "0";
         self.set_button_status(Action::Delete, selected);
-        self.set_button_status(Action::Unfilter, self.houses.len() < max);
+        self.set_button_status(Action::Unfilter, self.current_filter != Filter::default());
+        self.set_button_status(Action::PrevPage, self.offset > 0);
+        self.set_button_status(Action::NextPage, self.offset + PAGE_SIZE < self.total);
+        self.set_button_status(Action::Undo, self.history.can_undo());
+        self.set_button_status(Action::Redo, self.history.can_redo());
         self.show_house();
     }
 
-    fn fill_kind(&mut self) {
-        let kind = self.get_widget_mut("kind");
-        for value in HOUSE_TYPES {
-            kind.add(value);
+    fn load_kinds(&mut self) {
+        match self.dao.get_house_types() {
+            Ok(kinds) => self.kinds = kinds,
+            Err(error) => self.show_error("No se pudieron leer los tipos de vivienda", &error),
         }
     }
 
-    fn set_new_house(&mut self) {
-        self.set_value("id", NEW_HOUSE);
-        self.add_value("select", NEW_HOUSE);
-        self.set_value("select", i32::MAX);
+    // The retired kinds are left out, but `current`
+    fn fill_kind(&mut self, current: &str) {
+        let kinds: Vec<HouseType> = self
+            .kinds
+            .iter()
+            .filter(|kind| !kind.retired || kind.name == current)
+            .cloned()
+            .collect();
+        let choice = self.get_widget_mut("kind");
+        choice.clear();
+        for kind in &kinds {
+            if kind.retired {
+                choice.add(&format!("{} (retirado)", kind.name));
+            } else {
+                choice.add(&kind.name);
+            }
+        }
+        self.kind_choices = kinds.into_iter().map(|kind| kind.name).collect();
     }
 
-    fn clear_house(&mut self) {
-        for widget in self.inputs.values_mut() {
-            widget.set("");
+    fn load_tags(&mut self) {
+        match self.dao.get_tags() {
+            Ok(tags) => self.tags = tags,
+            Err(error) => self.show_error("No se pudieron leer las características", &error),
+        }
+        let names: Vec<String> = self.tags.iter().map(|tag| tag.name.clone()).collect();
+        let checks = self.get_widget_mut("tags");
+        checks.clear();
+        for name in &names {
+            checks.add(name);
         }
     }
 
-    fn show_house(&mut self) {
-        self.hid_select = -1;
-        let idx = self.get_value("select");
-        if let Some(hid) = self.idxhid.get(&idx) {
-            if let Some(house) = self.houses.get(&hid) {
-                let house = house.to_owned();
-                let house = house.borrow();
-                self.set_value("id", &house.id);
-                self.set_value("kind", HOUSE_TYPES.iter().position(|&r| r == house.kind).unwrap());
-                self.set_value("street", &house.street);
-                self.set_value("number", &house.number);
-                self.set_value("floor", &house.floor);
-                self.set_value("postcode", &house.postcode);
-                self.set_value("rooms", &house.rooms);
-                self.set_value("baths", &house.baths);
-                self.set_value("area", &house.area);
-                self.hid_select = house.id;
-            }
-        }
+    fn checked_tags(&self) -> Vec<String> {
+        self.get_value("tags")
+            .split(',')
+            .filter_map(|index| index.parse::<usize>().ok())
+            .map(|index| self.tags[index - 1].name.clone())
+            .collect()
     }
 
-    fn set_button_status(&mut self, key: Action, value: bool) {
-        let button = self.buttons.get_mut(&key).unwrap();
-        if value {
-            button.activate();
-        } else {
-            button.deactivate();
-        }
+    fn tags_index(&self, names: &[String]) -> String {
+        let checked: Vec<String> = self
+            .tags
+            .iter()
+            .enumerate()
+            .filter(|(_, tag)| names.contains(&tag.name))
+            .map(|(index, _)| (index + 1).to_string())
+            .collect();
+        checked.join(",")
     }
 
-    fn reset_buttons_color(&mut self) {
-        for button in self.buttons.values_mut() {
-            button.set_color(NORMAL_COLOR);
+    fn load_owners(&mut self) {
+        match self.dao.get_owners() {
+            Ok(owners) => self.owners = owners,
+            Err(error) => self.show_error("No se pudieron leer los propietarios", &error),
+        }
+        let choice = self.inputs.get_mut("owner").unwrap();
+        choice.clear();
+        for owner in &self.owners {
+            choice.add(&owner.name);
         }
-        self.win.redraw();
     }
 
-    fn reset_inputs_color(&mut self) {
-        for input in self.inputs.values_mut() {
-            // HoldBrowser is ignored by wrapper::Widget::set_color()
-            input.set_color(NORMAL_COLOR);
+    fn fill_owners(&mut self) {
+        let lines: Vec<String> = self
+            .house_owners
+            .iter()
+            .map(|ownership| format!("{} ({} %)", ownership.name, ownership.share))
+            .collect();
+        let browser = self.get_widget_mut("owners");
+        browser.clear();
+        for line in &lines {
+            browser.add(line);
         }
-        self.win.redraw();
+        self.show_owner();
     }
 
-    fn set_buttons_new_save_delete(&mut self, new: bool, save: bool, delete: bool) {
-        self.set_button_status(Action::New, new);
-        self.set_button_status(Action::Save, save);
-        self.set_button_status(Action::Delete, delete);
+    fn show_owner(&mut self) {
+        let index = self.get_value("owners").parse::<usize>().unwrap_or(0);
+        let owner = index
+            .checked_sub(1)
+            .and_then(|index| self.house_owners.get(index))
+            .and_then(|ownership| {
+                self.owners
+                    .iter()
+                    .find(|owner| owner.id == ownership.owner_id)
+            })
+            .cloned()
+            .unwrap_or_default();
+        self.set_value("phone", &owner.phone);
+        self.set_value("email", &owner.email);
+        self.set_value("tax_id", &owner.tax_id);
+        self.set_value("notes", &owner.notes);
     }
 
-    fn current_nothing_selected(&self) -> bool {
-        let input = self.get_widget("select");
-        let idx = input.get();
-        idx == "0"
+    fn add_owner(&mut self) {
+        let Some(owner) = self
+            .get_value("owner")
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| self.owners.get(index))
+        else {
+            self.open_message_dialog(
+                "Error",
+                "Seleccione el propietario a agregar",
+                vec![MDButton::new("Aceptar", 0)],
+            );
+            return;
+        };
+        let share = self.get_value("share");
+        let share = if share.is_empty() {
+            100.0 - self.house_owners.iter().map(|o| o.share).sum::<f32>()
+        } else {
+            share.parse::<f32>().unwrap_or(-1.0)
+        };
+        let mut owners = self.house_owners.clone();
+        owners.push(Ownership {
+            owner_id: owner.id,
+            name: owner.name.clone(),
+            share,
+        });
+        match Ownership::validate(&owners) {
+            Ok(_) => {
+                self.house_owners = owners;
+                self.set_value("owner", -1);
+                self.set_value("share", "");
+                self.fill_owners();
+                self.set_button_status(Action::Save, true);
+            }
+            Err(error) => self.show_error("No se pudo agregar el propietario", &error),
+        }
     }
 
-    fn current_is_new_house(&self) -> bool {
-        let input = self.get_widget("select");
-        let idx = input.get();
-        idx != "0" && input.get_text(&idx) == NEW_HOUSE
+    fn remove_owner(&mut self) {
+        let index = self.get_value("owners").parse::<usize>().unwrap_or(0);
+        if index > 0 && index <= self.house_owners.len() {
+            self.house_owners.remove(index - 1);
+            self.fill_owners();
+            self.set_button_status(Action::Save, true);
+        }
+    }
+
+    fn fill_prices(&mut self, id: i32) {
+        let prices = match self.dao.get_price_history(id) {
+            Ok(prices) => prices,
+            Err(error) => {
+                self.show_error("No se pudo leer el historial de precios", &error);
+                Vec::new()
+            }
+        };
+        let browser = self.get_widget_mut("prices");
+        browser.clear();
+        for change in &prices {
+            let date = change.changed_at.format("%Y-%m-%d %H:%M");
+            browser.add(&format!("{date}\t{} {}", change.currency, change.price));
+        }
+    }
+
+    fn fill_audit(&mut self, id: i32) {
+        let entries = match self.dao.get_audit_log(&base::AuditQuery::house(id)) {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.show_error("No se pudo leer el historial de cambios", &error);
+                Vec::new()
+            }
+        };
+        let browser = self.get_widget_mut("audit");
+        browser.clear();
+        for entry in &entries {
+            let date = entry.changed_at.format("%Y-%m-%d %H:%M");
+            browser.add(&format!("{date}\t{}\t{}", entry.action, entry.user));
+            for change in &entry.changes {
+                browser.add(&format!("\t{change}"));
+            }
+        }
+    }
+
+    fn operation_index(operation: base::Operation) -> i32 {
+        base::Operation::ALL
+            .iter()
+            .position(|&o| o == operation)
+            .map_or(-1, |index| index as i32)
+    }
+
+    fn load_tenants(&mut self) {
+        match self.dao.get_tenants() {
+            Ok(tenants) => self.tenants = tenants,
+            Err(error) => self.show_error("No se pudieron leer los inquilinos", &error),
+        }
+        let choice = self.inputs.get_mut("tenant").unwrap();
+        choice.clear();
+        for tenant in &self.tenants {
+            choice.add(&tenant.name);
+        }
+    }
+
+    fn fill_leases(&mut self, house_id: i32, select: i32) {
+        self.leases = match self.dao.get_leases(house_id) {
+            Ok(leases) => leases,
+            Err(error) => {
+                self.show_error("No se pudieron leer los contratos", &error);
+                Vec::new()
+            }
+        };
+        let lines: Vec<String> = self
+            .leases
+            .iter()
+            .map(|lease| {
+                format!(
+                    "{}\t{} a {}\t{}",
+                    lease.status, lease.start, lease.end, lease.tenant
+                )
+            })
+            .collect();
+        let browser = self.get_widget_mut("leases");
+        browser.clear();
+        for line in &lines {
+            browser.add(line);
+        }
+        if let Some(index) = self.leases.iter().position(|lease| lease.id == select) {
+            self.set_value("leases", index + 1);
+        }
+        self.show_lease();
+    }
+
+    fn selected_lease(&self) -> Option<&Lease> {
+        let index = self.get_value("leases").parse::<usize>().unwrap_or(0);
+        index
+            .checked_sub(1)
+            .and_then(|index| self.leases.get(index))
+    }
+
+    fn show_lease(&mut self) {
+        match self.selected_lease().cloned() {
+            Some(lease) => {
+                let tenant = self.tenants.iter().position(|t| t.id == lease.tenant_id);
+                let status = LeaseStatus::ALL.iter().position(|&s| s == lease.status);
+                self.set_value("tenant", tenant.map_or(-1, |index| index as i32));
+                self.set_value("start", lease.start);
+                self.set_value("end", lease.end);
+                self.set_value("rent", lease.rent);
+                self.set_value("lease_currency", &lease.currency);
+                self.set_value("deposit", lease.deposit);
+                self.set_value("status", status.map_or(-1, |index| index as i32));
+                self.set_value("index_months", lease.index_months);
+                self.set_value("index_rate", lease.index_rate);
+            }
+            None => {
+                for key in LEASE_KEYS {
+                    self.set_value(key, "");
+                }
+                self.set_value("lease_currency", self.get_value("currency"));
+                self.set_value("status", 0);
+                for key in ["deposit", "index_months", "index_rate"] {
+                    self.set_value(key, 0);
+                }
+            }
+        }
+        for key in LEASE_KEYS {
+            self.set_color(key, NORMAL_COLOR);
+        }
+        let selected = self.selected_lease().is_some();
+        self.set_button_status(Action::AddLease, self.hid_select > 0);
+        self.set_button_status(Action::SaveLease, selected);
+        self.set_button_status(Action::DeleteLease, selected);
+        self.set_button_status(Action::Payments, selected);
+    }
+
+    fn read_lease(&self) -> Lease {
+        let tenant = &self.tenants[self.get_value("tenant").parse::<usize>().unwrap()];
+        let date =
+            |key: &str| NaiveDate::parse_from_str(&self.get_value(key), DATE_FORMAT).unwrap();
+        Lease {
+            id: self.selected_lease().map_or(-1, |lease| lease.id),
+            house_id: self.hid_select,
+            tenant_id: tenant.id,
+            tenant: tenant.name.clone(),
+            start: date("start"),
+            end: date("end"),
+            rent: self.get_value("rent").parse().unwrap(),
+            currency: self.get_value("lease_currency").to_uppercase(),
+            deposit: self.get_value("deposit").parse().unwrap(),
+            index_months: self.get_value("index_months").parse().unwrap(),
+            index_rate: self.get_value("index_rate").parse().unwrap(),
+            status: LeaseStatus::ALL[self.get_value("status").parse::<usize>().unwrap()],
+        }
+    }
+
+    fn apply_lease(&mut self, action: Action) {
+        if action != Action::DeleteLease {
+            let mut count = 0;
+            for key in LEASE_KEYS {
+                count += if self.is_data_field_correct(key) {
+                    1
+                } else {
+                    0
+                };
+            }
+            self.win.redraw();
+            if count != LEASE_KEYS.len() {
+                self.open_message_dialog(
+                    "Error",
+                    "Los datos del contrato contienen errores\nverifíquelos para continuar",
+                    vec![MDButton::new("Aceptar", 0)],
+                );
+                return;
+            }
+        }
+        let result = match action {
+            Action::AddLease => self
+                .dao
+                .create_lease(&self.read_lease())
+                .map(|lease| lease.id),
+            Action::SaveLease => {
+                let lease = self.read_lease();
+                self.dao.update_lease(&lease).map(|_| lease.id)
+            }
+            _ => match self.selected_lease() {
+                Some(lease) => self.dao.delete_lease(lease.id).map(|_| -1),
+                None => return,
+            },
+        };
+        match result {
+            Ok(id) => self.fill_leases(self.hid_select, id),
+            Err(error) => self.show_error("No se pudo modificar el contrato", &error),
+        }
+    }
+
+    fn fill_attachments(&mut self, house_id: i32, select: i32) {
+        self.attachments = match self.dao.get_attachments(house_id) {
+            Ok(attachments) => attachments,
+            Err(error) => {
+                self.show_error("No se pudieron leer los adjuntos", &error);
+                Vec::new()
+            }
+        };
+        let lines: Vec<String> = self
+            .attachments
+            .iter()
+            .map(|attachment| {
+                let cover = if attachment.cover { "Portada" } else { "" };
+                format!(
+                    "{}\t{} KB\t{cover}",
+                    attachment.name,
+                    (attachment.size + 1023) / 1024
+                )
+            })
+            .collect();
+        let browser = self.get_widget_mut("attachments");
+        browser.clear();
+        for line in &lines {
+            browser.add(line);
+        }
+        if let Some(index) = self
+            .attachments
+            .iter()
+            .position(|attachment| attachment.id == select)
+        {
+            self.set_value("attachments", index + 1);
+        }
+        self.fill_strip();
+        self.show_attachment();
+    }
+
+    fn fill_strip(&mut self) {
+        let Some(mut strip) = self.strip.clone() else {
+            return;
+        };
+        let Widget::Browser(browser) = self.get_widget("attachments") else {
+            return;
+        };
+        let browser = browser.clone();
+        strip.clear();
+        strip.begin();
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            let mut button = Button::default().with_size(THUMBNAIL_SIZE, 0);
+            let image = attachment
+                .is_image()
+                .then(|| SharedImage::load(self.dao.attachment_path(attachment)).ok())
+                .flatten();
+            match image {
+                Some(mut image) => {
+                    image.scale(THUMBNAIL_SIZE, THUMBNAIL_SIZE, true, true);
+                    button.set_image(Some(image));
+                    button.set_label(&attachment.name);
+                    button.set_align(Align::Inside | Align::Clip); // the image over the text
+                }
+                None => {
+                    let extension = Path::new(&attachment.name).extension().unwrap_or_default();
+                    button.set_label(&format!(
+                        "{}\n{}",
+                        extension.to_string_lossy().to_uppercase(),
+                        attachment.name
+                    ));
+                    button.set_align(Align::Inside | Align::Clip | Align::Wrap);
+                }
+            }
+            button.set_label_size(12);
+            button.set_tooltip(&attachment.name);
+            button.set_color(NORMAL_COLOR);
+            button.set_callback({
+                let mut browser = browser.clone();
+                let sender = self.sender;
+                move |_| {
+                    browser.select(index as i32 + 1);
+                    sender.send(Action::PickAttachment);
+                }
+            });
+        }
+        strip.end();
+        if let Some(mut scroll) = strip.parent() {
+            scroll.redraw();
+        }
+    }
+
+    fn selected_attachment(&self) -> Option<&Attachment> {
+        let index = self.get_value("attachments").parse::<usize>().unwrap_or(0);
+        index
+            .checked_sub(1)
+            .and_then(|index| self.attachments.get(index))
+    }
+
+    fn show_attachment(&mut self) {
+        let selected = self.selected_attachment().cloned();
+        if let Some(strip) = &self.strip {
+            for (index, attachment) in self.attachments.iter().enumerate() {
+                if let Some(mut button) = strip.child(index as i32) {
+                    let chosen = selected.as_ref().map_or(false, |s| s.id == attachment.id);
+                    button.set_frame(if chosen {
+                        FrameType::DownBox
+                    } else {
+                        FrameType::UpBox
+                    });
+                    button.redraw();
+                }
+            }
+        }
+        let cover = selected
+            .as_ref()
+            .map_or(false, |a| a.is_image() && !a.cover);
+        self.set_button_status(Action::AddAttachment, self.hid_select > 0);
+        self.set_button_status(Action::RemoveAttachment, selected.is_some());
+        self.set_button_status(Action::CoverAttachment, cover);
+        self.set_button_status(Action::OpenAttachment, selected.is_some());
+    }
+
+    fn apply_attachment(&mut self, action: Action) {
+        let result = match action {
+            Action::AddAttachment => {
+                let Some(path) = choose_file("Agregar adjunto", ATTACHMENT_TYPES, None) else {
+                    return;
+                };
+                self.dao
+                    .add_attachment(self.hid_select, &path)
+                    .map(|attachment| attachment.id)
+            }
+            _ => match self.selected_attachment().map(|attachment| attachment.id) {
+                Some(id) if action == Action::CoverAttachment => self.dao.set_cover(id).map(|_| id),
+                Some(id) => self.dao.remove_attachment(id).map(|_| -1),
+                None => return,
+            },
+        };
+        match result {
+            Ok(id) => self.fill_attachments(self.hid_select, id),
+            Err(error) => self.show_error("No se pudo modificar los adjuntos", &error),
+        }
+    }
+
+    // A copy with the original name, so the system picks the application by the extension
+    // and the file in the store is never modified
+    fn open_attachment(&mut self) {
+        let Some(attachment) = self.selected_attachment().cloned() else {
+            return;
+        };
+        let copy = || -> io::Result<PathBuf> {
+            let dir = env::temp_dir().join("wohnen").join(&attachment.hash);
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(&attachment.name);
+            fs::copy(self.dao.attachment_path(&attachment), &path)?;
+            Ok(path)
+        };
+        if let Err(error) = copy().and_then(open::that) {
+            self.show_error("No se pudo abrir el adjunto", &error);
+        }
+    }
+
+    fn kind_index(&self, kind: &str) -> i32 {
+        self.kind_choices
+            .iter()
+            .position(|k| k == kind)
+            .map_or(-1, |index| index as i32)
+    }
+
+    fn set_new_house(&mut self) {
+        self.set_value("id", NEW_HOUSE);
+        self.add_value("select", NEW_HOUSE);
+        self.set_value("select", i32::MAX);
+    }
+
+    fn clear_house(&mut self) {
+        for widget in self.inputs.values_mut() {
+            widget.set("");
+        }
+        self.house_owners.clear();
+        self.fill_owners();
+        self.get_widget_mut("prices").clear();
+        self.get_widget_mut("audit").clear();
+        self.get_widget_mut("leases").clear();
+        self.leases.clear();
+        self.show_lease();
+        self.get_widget_mut("attachments").clear();
+        self.attachments.clear();
+        self.fill_strip();
+        self.show_attachment();
+        // The leases and attachments belong to a saved house, show_house() enables them again
+        self.set_button_status(Action::AddLease, false);
+        self.set_button_status(Action::AddAttachment, false);
+    }
+
+    fn show_house(&mut self) {
+        self.hid_select = -1;
+        self.fill_kind("");
+        let idx = self.get_value("select");
+        if let Some(hid) = self.idxhid.get(&idx) {
+            if let Some(house) = self.houses.get(&hid) {
+                let house = house.to_owned();
+                let house = house.borrow();
+                self.fill_kind(&house.kind);
+                self.set_value("id", &house.id);
+                self.set_value("kind", self.kind_index(&house.kind));
+                self.set_value("street", &house.street);
+                self.set_value("number", &house.number);
+                self.set_value("floor", &house.floor);
+                self.set_value("postcode", &house.postcode);
+                self.set_value("rooms", &house.rooms);
+                self.set_value("baths", &house.baths);
+                self.set_value("area", &house.area);
+                self.set_value("operation", Self::operation_index(house.operation));
+                self.set_value("price", house.price);
+                self.set_value("currency", &house.currency);
+                self.set_value("expenses", house.expenses);
+                self.set_value("tags", self.tags_index(&house.tags));
+                self.set_value("city", &house.city);
+                self.set_value("province", &house.province);
+                self.set_value("country", &house.country);
+                self.set_value("latitude", Self::coordinate_text(house.latitude));
+                self.set_value("longitude", Self::coordinate_text(house.longitude));
+                self.fill_prices(house.id);
+                self.fill_audit(house.id);
+                self.house_owners = house.owners.clone();
+                self.fill_owners();
+                self.hid_select = house.id;
+                self.fill_leases(house.id, -1);
+                self.fill_attachments(house.id, -1);
+            }
+        }
+    }
+
+    fn set_button_status(&mut self, key: Action, value: bool) {
+        let button = self.buttons.get_mut(&key).unwrap();
+        if value {
+            button.activate();
+        } else {
+            button.deactivate();
+        }
+    }
+
+    fn reset_buttons_color(&mut self) {
+        for button in self.buttons.values_mut() {
+            button.set_color(NORMAL_COLOR);
+        }
+        self.win.redraw();
+    }
+
+    // Offset of the last page once a house is added, fill_select() moves back if it does not match the filter
+    fn last_page_with_one_more(&self) -> i64 {
+        self.total / PAGE_SIZE * PAGE_SIZE
+    }
+
+    fn set_search(&mut self, text: &str) {
+        if let Some(search) = self.search.as_mut() {
+            search.set_value(text);
+        }
+    }
+
+    fn reset_inputs_color(&mut self) {
+        for input in self.inputs.values_mut() {
+            // HoldBrowser is ignored by wrapper::Widget::set_color()
+            input.set_color(NORMAL_COLOR);
+        }
+        self.win.redraw();
+    }
+
+    fn set_buttons_new_save_delete(&mut self, new: bool, save: bool, delete: bool) {
+        self.set_button_status(Action::New, new);
+        self.set_button_status(Action::Save, save);
+        self.set_button_status(Action::Delete, delete);
+    }
+
+    fn current_is_new_house(&self) -> bool {
+        let input = self.get_widget("select");
+        let idx = input.get();
+        idx != "0" && input.get_text(&idx) == NEW_HOUSE
     }
 
     fn is_data_field_completed_and_correct(&mut self) -> bool {
-        let keys = [
-            "kind", "street", "number", "floor", "postcode", "rooms", "baths", "area",
-        ];
         let mut count = 0;
-        for key in keys {
+        for key in DATA_KEYS {
             count += if self.is_data_field_correct(key) { 1 } else { 0 };
         }
         self.win.redraw();
-        count == keys.len()
+        count == DATA_KEYS.len()
+    }
+
+    // The form is compared with the loaded house (hid_select), not with the
+    // browser, which already shows the new selection when this is checked
+    fn is_dirty(&self) -> bool {
+        match self.houses.get(&self.hid_select) {
+            Some(house) => {
+                let house = house.borrow();
+                let values = [
+                    self.kind_index(&house.kind).to_string(),
+                    house.street.clone(),
+                    house.number.to_string(),
+                    house.floor.to_string(),
+                    house.postcode.to_string(),
+                    house.rooms.to_string(),
+                    house.baths.to_string(),
+                    house.area.to_string(),
+                    Self::operation_index(house.operation).to_string(),
+                    house.price.to_string(),
+                    house.currency.clone(),
+                    house.expenses.to_string(),
+                    house.city.clone(),
+                    house.province.clone(),
+                    house.country.clone(),
+                    Self::coordinate_text(house.latitude),
+                    Self::coordinate_text(house.longitude),
+                ];
+                DATA_KEYS
+                    .iter()
+                    .zip(values)
+                    .any(|(key, value)| self.get_value(key) != value)
+                    || house.owners != self.house_owners
+                    || house.tags != self.checked_tags()
+            }
+            None => {
+                !self.house_owners.is_empty()
+                    || !self.get_value("tags").is_empty()
+                    || DATA_KEYS.iter().any(|&key| match key {
+                        "kind" | "operation" => self.get_value(key) != "-1",
+                        _ => !self.get_value(key).is_empty(),
+                    })
+            }
+        }
+    }
+
+    fn ask_unsaved_changes(&mut self) -> Unsaved {
+        if !self.is_dirty() {
+            return Unsaved::Clean;
+        }
+        let buttons = vec![
+            MDButton::new("Cancelar", 0),
+            MDButton::new("Descartar", 1),
+            MDButton::new("Guardar", 2),
+        ];
+        let message = "La vivienda tiene cambios sin guardar\n¿Qué desea hacer con ellos?";
+        match self.open_message_dialog("Cambios sin guardar", message, buttons) {
+            2 if self.save() => Unsaved::Saved,
+            1 => Unsaved::Discarded,
+            _ => Unsaved::Cancelled,
+        }
+    }
+
+    fn resolve_conflict(&mut self, mine: &House, caption: &str) -> Conflict {
+        loop {
+            let current = match self.dao.get_house(mine.id) {
+                Ok(current) => current,
+                Err(error) => {
+                    // In the trash or purged, the list no longer has it
+                    self.show_error("La vivienda ya no está en la base de datos", &error);
+                    return Conflict::Reload;
+                }
+            };
+            let buttons = vec![
+                MDButton::new("Cancelar", 0),
+                MDButton::new("Comparar", 1),
+                MDButton::new("Recargar", 2),
+                MDButton::new(caption, 3),
+            ];
+            let message = "Otro usuario modificó la vivienda desde que se leyó\n¿Qué desea hacer?";
+            match self.open_message_dialog("Conflicto", message, buttons) {
+                1 => {
+                    let changes = AuditEntry::diff(Some(&current), Some(mine));
+                    let message = if changes.is_empty() {
+                        "Los datos son iguales a los guardados".to_owned()
+                    } else {
+                        let lines: Vec<String> =
+                            changes.iter().map(|change| change.to_string()).collect();
+                        format!(
+                            "De los datos guardados a los suyos:\n\n{}",
+                            lines.join("\n")
+                        )
+                    };
+                    self.open_message_dialog(
+                        "Comparar",
+                        &message,
+                        vec![MDButton::new("Aceptar", 0)],
+                    );
+                }
+                2 => return Conflict::Reload,
+                3 => return Conflict::Overwrite(Box::new(current)),
+                _ => return Conflict::Cancelled,
+            }
+        }
+    }
+
+    fn restore_selection(&mut self) {
+        let hid = self.hid_select;
+        let idx = self
+            .idxhid
+            .iter()
+            .find(|(_, &id)| id == hid)
+            .map(|(idx, _)| idx.clone());
+        match idx {
+            Some(idx) => self.set_value("select", idx),
+            None => {
+                // Unsaved data that does not belong to any house is a new house
+                let select = self.get_widget("select");
+                if select.get_text(&select.get_size()) != NEW_HOUSE {
+                    self.set_value("id", NEW_HOUSE);
+                    self.add_value("select", NEW_HOUSE);
+                }
+                self.set_value("select", i32::MAX);
+            }
+        }
+    }
+
+    fn save(&mut self) -> bool {
+        if !self.is_data_field_completed_and_correct() {
+            self.open_message_dialog(
+                "Error",
+                "Los datos cargados contienen errores\nverifíquelos para continuar",
+                vec![MDButton::new("Aceptar", 0)],
+            );
+            return false;
+        }
+
+        let saved = match self.houses.get(&self.hid_select).cloned() {
+            None => {
+                let mut house = House::default();
+                self.update_house(&mut house);
+                if !self.check_street(&mut house) {
+                    return false;
+                }
+                match self.dao.create_house(&house) {
+                    Ok(house) => {
+                        // New houses get the highest id, so they are on the last page
+                        self.hid_select = house.id;
+                        self.offset = self.last_page_with_one_more();
+                        self.history.record(Operation::Create(house));
+                        true
+                    }
+                    Err(error) => {
+                        self.show_error("No se pudo guardar la vivienda", &error);
+                        false
+                    }
+                }
+            }
+            Some(stored) => {
+                let mut before = stored.borrow().clone();
+                let mut house = before.clone();
+                self.update_house(&mut house);
+                // The addresses that are not changed are not checked again
+                let moved = house.street != before.street || house.postcode != before.postcode;
+                if moved && !self.check_street(&mut house) {
+                    return false;
+                }
+                loop {
+                    match self.dao.update_house(&house) {
+                        Ok(version) => {
+                            house.version = version;
+                            before.version = version;
+                            *stored.borrow_mut() = house.clone();
+                            self.history.record(Operation::Update {
+                                before: Box::new(before),
+                                after: Box::new(house),
+                            });
+                            break true;
+                        }
+                        Err(Error::Conflict) => match self.resolve_conflict(&house, "Sobrescribir")
+                        {
+                            Conflict::Overwrite(current) => {
+                                // Undoing the overwrite brings back the house of the other user
+                                house.version = current.version;
+                                before = *current;
+                            }
+                            // The form is refilled from the database, like after saving
+                            Conflict::Reload => break true,
+                            Conflict::Cancelled => break false,
+                        },
+                        Err(error) => {
+                            self.show_error("No se pudo guardar la vivienda", &error);
+                            break false;
+                        }
+                    }
+                }
+            }
+        };
+        if saved {
+            self.set_button_status(Action::Save, false);
+        }
+        saved
     }
 
     fn is_data_field_correct(&mut self, key: &str) -> bool {
@@ -469,16 +2033,43 @@ impl<'a> Gui<'a> {
 
     fn is_data_value_correct(&self, key: &str) -> bool {
         match key {
-            "kind" => self.get_value(key) != "-1",
+            "kind" | "operation" => self.get_value(key) != "-1",
             "street" => self.get_value(key) != "",
             "area" => test_positive_and_zero_number::<f32>(&self.get_value(key)),
+            "price" | "expenses" | "rent" | "deposit" | "index_rate" => {
+                test_positive_and_zero_number::<f64>(&self.get_value(key))
+            }
+            "tenant" | "status" => self.get_value(key) != "-1",
+            "city" | "province" | "country" => true,
+            // Both coordinates or none
+            "latitude" | "longitude" => {
+                let (other, limit) = if key == "latitude" {
+                    ("longitude", 90.0)
+                } else {
+                    ("latitude", 180.0)
+                };
+                match self.get_value(key) {
+                    value if value.is_empty() => self.get_value(other).is_empty(),
+                    value => {
+                        let inside = value
+                            .parse::<f64>()
+                            .map_or(false, |value| (-limit..=limit).contains(&value));
+                        inside && !self.get_value(other).is_empty()
+                    }
+                }
+            }
+            "start" | "end" => NaiveDate::parse_from_str(&self.get_value(key), DATE_FORMAT).is_ok(),
+            "currency" | "lease_currency" => {
+                let currency = self.get_value(key);
+                currency.len() == 3 && currency.chars().all(|c| c.is_ascii_alphabetic())
+            }
             _ => test_positive_and_zero_number::<i32>(&self.get_value(key)),
         }
     }
 
     fn update_house(&self, house: &mut House) {
-        let kind = self.get_widget("kind");
-        house.kind = kind.get_text(&kind.get());
+        let index = self.get_value("kind").parse::<usize>().unwrap();
+        house.kind = self.kind_choices[index].clone();
         house.street = self.get_value("street");
         house.number = self.get_value("number").parse::<i32>().unwrap();
         house.floor = self.get_value("floor").parse::<i32>().unwrap();
@@ -486,13 +2077,35 @@ impl<'a> Gui<'a> {
         house.rooms = self.get_value("rooms").parse::<i32>().unwrap();
         house.baths = self.get_value("baths").parse::<i32>().unwrap();
         house.area = self.get_value("area").parse::<f32>().unwrap();
+        let index = self.get_value("operation").parse::<usize>().unwrap();
+        house.operation = base::Operation::ALL[index];
+        house.price = self.get_value("price").parse::<f64>().unwrap();
+        house.currency = self.get_value("currency").to_uppercase();
+        house.expenses = self.get_value("expenses").parse::<f64>().unwrap();
+        house.owners = self.house_owners.clone();
+        house.tags = self.checked_tags();
+        house.city = self.get_value("city");
+        house.province = self.get_value("province");
+        house.country = self.get_value("country");
+        house.latitude = self.get_value("latitude").parse::<f64>().ok();
+        house.longitude = self.get_value("longitude").parse::<f64>().ok();
+    }
+
+    fn coordinate_text(coordinate: Option<f64>) -> String {
+        coordinate
+            .map(|coordinate| coordinate.to_string())
+            .unwrap_or_default()
     }
 
     fn open_message_dialog(&mut self, title: &str, message: &str, buttons: Vec<MDButton>) -> i32 {
         self.win.deactivate();
 
-        let width = 360;
-        let height = 200;
+        draw::set_font(Font::Helvetica, app::font_size());
+        let (w, h) = draw::measure(message, false);
+        let width = 360
+            .max(w + 4 * MARGIN_SIZE)
+            .max(5 * MARGIN_SIZE + buttons.len() as i32 * BUTTON_WIDTH);
+        let height = 200.max(h + 5 * MARGIN_SIZE + 2 * BUTTON_HEIGHT);
         let (x, y) = self.get_pos(width, height);
         let mut dialog =
             MessageDialog::new(x, y, width, height, title.to_owned(), message.to_owned(), buttons);
@@ -502,12 +2115,85 @@ impl<'a> Gui<'a> {
         answer
     }
 
+    fn show_error<E: Debug + Display>(&mut self, message: &str, error: &E) {
+        eprintln!("{message}: {error:?}");
+        self.open_message_dialog(
+            "Error",
+            &format!("{message}\n{error}"),
+            vec![MDButton::new("Aceptar", 0)],
+        );
+    }
+
+    fn export_houses(&mut self) {
+        let filtered = if self.current_filter != Filter::default() {
+            let buttons = vec![
+                MDButton::new("Cancelar", 0),
+                MDButton::new("Filtradas", 1),
+                MDButton::new("Todas", 2),
+            ];
+            match self.open_message_dialog("Exportar", "¿Qué viviendas desea exportar?", buttons)
+            {
+                0 => return,
+                answer => answer == 1,
+            }
+        } else {
+            false
+        };
+        let Some(path) = choose_file("Exportar viviendas", FILE_TYPES, Some("viviendas.csv"))
+        else {
+            return;
+        };
+
+        let all = Filter::default();
+        let filter = if filtered { &self.current_filter } else { &all };
+        match export_file(self.dao.as_mut(), filter, &path) {
+            Ok(count) => {
+                self.open_message_dialog(
+                    "Exportar",
+                    &format!("Se exportaron {count} viviendas a\n{}", path.display()),
+                    vec![MDButton::new("Aceptar", 0)],
+                );
+            }
+            Err(error) => self.show_error("No se pudo exportar el archivo", &error),
+        }
+    }
+
+    fn import_houses(&mut self) {
+        let Some(path) = choose_file("Importar viviendas", FILE_TYPES, None) else {
+            return;
+        };
+        match import_file(self.dao.as_mut(), &path) {
+            Ok(report) => {
+                // Only the first errors fit on the screen
+                let mut lines: Vec<String> =
+                    report.to_string().lines().map(str::to_owned).collect();
+                if lines.len() > 12 {
+                    let more = lines.len() - 12;
+                    lines.truncate(12);
+                    lines.push(format!("… y {more} filas más"));
+                }
+                self.open_message_dialog(
+                    "Importar",
+                    &lines.join("\n"),
+                    vec![MDButton::new("Aceptar", 0)],
+                );
+            }
+            Err(error) => self.show_error("No se pudo importar el archivo", &error),
+        }
+    }
+
     pub fn run(&mut self) {
         self.build();
+        self.set_title();
+        self.fill_menu();
         self.set_buttons_new_save_delete(true, false, false);
+        self.load_kinds();
+        self.load_tags();
+        self.load_owners();
+        self.load_tenants();
         self.fill_select();
-        self.fill_kind();
         self.win.show();
+        self.load_gazetteer();
 
         while self.app.wait() {
             if let Some(action) = self.receiver.recv() {
@@ -515,6 +2201,11 @@ impl<'a> Gui<'a> {
 
                 match action {
                     Action::New => {
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.hid_select = -1;
+                        self.fill_kind("");
                         self.clear_house();
                         self.set_new_house();
                         self.set_buttons_new_save_delete(false, false, false);
@@ -522,61 +2213,32 @@ impl<'a> Gui<'a> {
                     }
 
                     Action::Save => {
-                        if self.is_data_field_completed_and_correct() {
-                            if self.current_nothing_selected() || self.current_is_new_house() {
-                                let mut house = House::default();
-                                self.update_house(&mut house);
-                                match self.dao.create_house(&house) {
-                                    Ok(house) => self.hid_select = house.id,
-                                    Err(_) => {
-                                        self.open_message_dialog(
-                                            "Error",
-                                            "No se pudo guardar la vivienda",
-                                            vec![MDButton::new("Aceptar", 0)],
-                                        );
-                                    }
-                                }
-                            } else {
-                                let houses = self.houses.clone();
-                                let house = houses.get(&self.hid_select).unwrap();
-                                let mut house = house.borrow_mut();
-                                self.update_house(&mut house);
-                                match self.dao.update_house(&house) {
-                                    Ok(_) => {}
-                                    Err(_) => {
-                                        self.open_message_dialog(
-                                            "Error",
-                                            "No se pudo guardar la vivienda",
-                                            vec![MDButton::new("Aceptar", 0)],
-                                        );
-                                    }
-                                }
-                            }
-                            self.set_button_status(Action::Save, false);
+                        if self.save() {
                             // TODO A better option would be to update only Browser and BTreeMap
                             self.fill_select(); // update delete button
-                        } else {
-                            self.open_message_dialog(
-                                "Error",
-                                "Los datos cargados contienen errores\nverifíquelos para continuar",
-                                vec![MDButton::new("Aceptar", 0)],
-                            );
                         }
                     }
 
                     Action::Delete => {
-                        // TODO Should be asked if delete is desired
-                        let key = self.hid_select;
-                        match self.dao.delete_house(key) {
-                            Ok(_) => {
-                                self.hid_select = -1;
-                            }
-                            Err(_) => {
-                                self.open_message_dialog(
-                                    "Error",
-                                    "No se pudo borrar la vivienda",
-                                    vec![MDButton::new("Aceptar", 0)],
-                                );
+                        // There is no confirmation, a mistaken delete is reverted with Ctrl+Z or from the trash
+                        let mut house = self.houses[&self.hid_select].borrow().clone();
+                        loop {
+                            match self.dao.delete_house(&house) {
+                                Ok(_) => {
+                                    self.hid_select = -1;
+                                    self.history.record(Operation::Delete(house));
+                                    break;
+                                }
+                                Err(Error::Conflict) => {
+                                    match self.resolve_conflict(&house, "Borrar") {
+                                        Conflict::Overwrite(current) => house = *current,
+                                        Conflict::Reload | Conflict::Cancelled => break,
+                                    }
+                                }
+                                Err(error) => {
+                                    self.show_error("No se pudo borrar la vivienda", &error);
+                                    break;
+                                }
                             }
                         }
                         self.set_buttons_new_save_delete(true, false, false);
@@ -586,7 +2248,28 @@ impl<'a> Gui<'a> {
                     }
 
                     Action::Select => {
-                        // TODO Should be checked if there are unsaved changes to ask what to do
+                        let target = self.idxhid.get(&self.get_value("select")).copied();
+                        let same = match target {
+                            Some(hid) => hid == self.hid_select,
+                            None => self.hid_select == -1 && self.current_is_new_house(),
+                        };
+                        if same {
+                            continue; // the edits of the current house are kept
+                        }
+                        let offset = self.offset;
+                        match self.ask_unsaved_changes() {
+                            Unsaved::Cancelled => {
+                                self.restore_selection();
+                                continue;
+                            }
+                            Unsaved::Saved => {
+                                // Saving refills the browser, the clicked house is selected again
+                                self.hid_select = target.unwrap_or(-1);
+                                self.offset = offset;
+                                self.fill_select();
+                            }
+                            Unsaved::Clean | Unsaved::Discarded => {}
+                        }
 
                         let input = self.get_widget_mut("select");
                         let idx = input.get();
@@ -609,37 +2292,491 @@ impl<'a> Gui<'a> {
                         self.win.deactivate();
 
                         let width = 800;
-                        let height = 360;
+                        let height = 628;
                         let (x, y) = self.get_pos(width, height);
 
-                        let mut dialog = FilterDialog::new(x, y, width, height);
+                        let kinds = self.kinds.iter().map(|kind| kind.name.clone()).collect();
+                        let tags = self.tags.iter().map(|tag| tag.name.clone()).collect();
+                        let mut dialog = FilterDialog::new(x, y, width, height, kinds, tags);
                         let filter = dialog.run();
 
-                        self.set_buttons_new_save_delete(true, false, false);
-                        if let Some(filter) = filter {
-                            self.current_filter = filter;
-                            self.fill_select(); // update delete button
-                        }
-
                         self.win.activate();
+
+                        let Some(mut filter) = filter else {
+                            continue;
+                        };
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.set_buttons_new_save_delete(true, false, false);
+                        // The dialog does not know the search field, which applies on top of it
+                        filter.search = mem::take(&mut self.current_filter.search);
+                        self.current_filter = filter;
+                        self.offset = 0;
+                        self.fill_select(); // update delete button
                         self.reset_inputs_color();
                     }
 
                     Action::Unfilter => {
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
                         self.set_buttons_new_save_delete(true, false, false);
                         self.current_filter = Filter::default();
+                        self.set_search("");
+                        self.offset = 0;
+                        self.fill_select(); // update delete button
+                        self.reset_inputs_color();
+                    }
+
+                    Action::Search => {
+                        let text = self
+                            .search
+                            .as_ref()
+                            .map(|search| search.value())
+                            .unwrap_or_default();
+                        if text == self.current_filter.search.text {
+                            continue;
+                        }
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            // The list keeps showing the houses of the previous search
+                            let previous = self.current_filter.search.text.clone();
+                            self.set_search(&previous);
+                            continue;
+                        }
+                        self.set_buttons_new_save_delete(true, false, false);
+                        self.current_filter.search.text = text;
+                        self.offset = 0;
+                        self.fill_select(); // update delete button
+                        self.reset_inputs_color();
+                    }
+
+                    Action::PrevPage | Action::NextPage => {
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.offset = if action == Action::PrevPage {
+                            (self.offset - PAGE_SIZE).max(0)
+                        } else {
+                            self.offset + PAGE_SIZE
+                        };
+                        self.set_buttons_new_save_delete(true, false, false);
                         self.fill_select(); // update delete button
                         self.reset_inputs_color();
                     }
 
+                    Action::Undo | Action::Redo => {
+                        let undo = action == Action::Undo;
+                        let available = if undo {
+                            self.history.can_undo()
+                        } else {
+                            self.history.can_redo()
+                        };
+                        // The shortcut works even with the button disabled
+                        if !available || self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
+                        let result = if undo {
+                            self.history.undo(self.dao.as_mut())
+                        } else {
+                            self.history.redo(self.dao.as_mut())
+                        };
+                        match result {
+                            Ok(Some(id)) => {
+                                // A house created again gets the highest id, so it is on the last page
+                                if !self.houses.contains_key(&id) {
+                                    self.offset = self.last_page_with_one_more();
+                                }
+                                self.hid_select = id;
+                            }
+                            Ok(None) => self.hid_select = -1,
+                            Err(error) => self
+                                .show_error("No se pudo deshacer o rehacer la operación", &error),
+                        }
+                        self.set_buttons_new_save_delete(true, false, false);
+                        self.fill_select(); // update delete button
+                        self.reset_inputs_color();
+                    }
+
+                    Action::Import => {
+                        if self.ask_unsaved_changes() == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.import_houses();
+                        self.set_buttons_new_save_delete(true, false, false);
+                        self.fill_select(); // update delete button
+                        self.reset_inputs_color();
+                    }
+
+                    Action::Export => self.export_houses(),
+
+                    Action::Kinds => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.win.deactivate();
+
+                        let width = 640;
+                        let height = 420;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog = KindsDialog::new(x, y, width, height, self.dao.as_mut());
+                        let changed = dialog.run();
+
+                        self.win.activate();
+
+                        if changed || unsaved == Unsaved::Discarded {
+                            // A renamed kind changes the houses that use it
+                            self.load_kinds();
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
+                    Action::Tags => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.win.deactivate();
+
+                        let width = 640;
+                        let height = 420;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog = TagsDialog::new(x, y, width, height, self.dao.as_mut());
+                        let changed = dialog.run();
+
+                        self.win.activate();
+
+                        if changed || unsaved == Unsaved::Discarded {
+                            // A renamed tag changes the houses that have it
+                            self.load_tags();
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
                     Action::Change => {
                         self.set_button_status(Action::Save, true);
                         self.is_data_field_completed_and_correct();
                     }
 
+                    Action::OpenDatabase | Action::NewDatabase | Action::Recent(_) => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        let (path, create) = match action {
+                            Action::Recent(index) => {
+                                (self.config.recent.get(index).cloned(), false)
+                            }
+                            Action::NewDatabase => {
+                                let path = choose_file(
+                                    "Nueva base de datos",
+                                    DATABASE_TYPES,
+                                    Some("viviendas.sqlite"),
+                                );
+                                match path {
+                                    // An existing file is never replaced, it can only be opened
+                                    Some(path) if path.exists() => {
+                                        let message = format!(
+                                            "«{}» ya existe\n¿Desea abrirla?",
+                                            path.display()
+                                        );
+                                        let buttons = vec![
+                                            MDButton::new("Cancelar", 0),
+                                            MDButton::new("Abrir", 1),
+                                        ];
+                                        match self.open_message_dialog(
+                                            "Nueva base de datos",
+                                            &message,
+                                            buttons,
+                                        ) {
+                                            1 => (Some(path), false),
+                                            _ => (None, false),
+                                        }
+                                    }
+                                    path => (path, true),
+                                }
+                            }
+                            _ => (
+                                choose_file("Abrir base de datos", DATABASE_TYPES, None),
+                                false,
+                            ),
+                        };
+                        match path {
+                            Some(path) => self.open_database(&path, create),
+                            None if unsaved == Unsaved::Discarded => {
+                                self.set_buttons_new_save_delete(true, false, false);
+                                self.fill_select(); // update delete button
+                                self.reset_inputs_color();
+                            }
+                            None => {}
+                        }
+                    }
+
+                    Action::PickOwner => self.show_owner(),
+
+                    Action::AddOwner => self.add_owner(),
+
+                    Action::RemoveOwner => self.remove_owner(),
+
+                    Action::Owners => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.win.deactivate();
+
+                        let width = 680;
+                        let height = 520;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog =
+                            ContactsDialog::<Owner>::new(x, y, width, height, self.dao.as_mut());
+                        let changed = dialog.run();
+
+                        self.win.activate();
+
+                        if changed || unsaved == Unsaved::Discarded {
+                            // A renamed owner changes the houses that have it
+                            self.load_owners();
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
+                    Action::PickLease => self.show_lease(),
+
+                    Action::PickAttachment => self.show_attachment(),
+
+                    Action::AddAttachment | Action::RemoveAttachment | Action::CoverAttachment => {
+                        self.apply_attachment(action)
+                    }
+
+                    Action::OpenAttachment => self.open_attachment(),
+
+                    Action::OpenGazetteer => {
+                        if let Some(path) = choose_file("Abrir callejero", GAZETTEER_TYPES, None) {
+                            self.set_gazetteer(Some(path));
+                        }
+                    }
+
+                    Action::CloseGazetteer => self.set_gazetteer(None),
+
+                    Action::AddLease | Action::SaveLease | Action::DeleteLease => {
+                        self.apply_lease(action)
+                    }
+
+                    Action::Payments => {
+                        let Some(lease) = self.selected_lease().cloned() else {
+                            continue;
+                        };
+                        self.win.deactivate();
+
+                        let width = 760;
+                        let height = 520;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog =
+                            AccountDialog::new(x, y, width, height, self.dao.as_mut(), lease);
+                        dialog.run();
+
+                        self.win.activate();
+                    }
+
+                    Action::Tenants => {
+                        self.win.deactivate();
+
+                        let width = 680;
+                        let height = 520;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog =
+                            ContactsDialog::<Tenant>::new(x, y, width, height, self.dao.as_mut());
+                        let changed = dialog.run();
+
+                        self.win.activate();
+
+                        if changed {
+                            // Only the leases show the tenants, the house form is kept
+                            self.load_tenants();
+                            let select = self.selected_lease().map_or(-1, |lease| lease.id);
+                            if self.hid_select > 0 {
+                                self.fill_leases(self.hid_select, select);
+                            }
+                        }
+                    }
+
+                    Action::Expiring => {
+                        self.win.deactivate();
+
+                        let width = 760;
+                        let height = 480;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog = ExpiringDialog::new(
+                            x,
+                            y,
+                            width,
+                            height,
+                            self.dao.as_mut(),
+                            self.expiring_days,
+                        );
+                        self.expiring_days = dialog.run();
+
+                        self.win.activate();
+                    }
+
+                    Action::Overdue => {
+                        self.win.deactivate();
+
+                        let width = 760;
+                        let height = 480;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog = OverdueDialog::new(x, y, width, height, self.dao.as_mut());
+                        dialog.run();
+
+                        self.win.activate();
+                    }
+
+                    Action::Trash => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        self.win.deactivate();
+
+                        let width = 640;
+                        let height = 420;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let retention = self.config.retention;
+                        let mut dialog =
+                            TrashDialog::new(x, y, width, height, self.dao.as_mut(), retention);
+                        let changed = dialog.run();
+                        let retention = dialog.retention();
+
+                        self.win.activate();
+
+                        if retention != self.config.retention {
+                            self.config.retention = retention;
+                            if let Err(error) = self.config.save() {
+                                self.show_error("No se pudo guardar la configuración", &error);
+                            }
+                        }
+                        if changed || unsaved == Unsaved::Discarded {
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
+                    Action::Bulk => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        // The new house is not saved yet, it has no id
+                        let houses: Vec<House> = self
+                            .get_widget("select")
+                            .get_selected()
+                            .iter()
+                            .filter_map(|idx| self.idxhid.get(idx))
+                            .map(|id| self.houses[id].borrow().clone())
+                            .collect();
+                        if houses.is_empty() {
+                            self.open_message_dialog(
+                                "Editar selección",
+                                "Seleccione las viviendas a modificar\ncon Ctrl o Mayús y clic en la lista",
+                                vec![MDButton::new("Aceptar", 0)],
+                            );
+                            continue;
+                        }
+                        self.win.deactivate();
+
+                        let width = 720;
+                        let height = 520;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let mut dialog =
+                            BulkDialog::new(x, y, width, height, self.dao.as_mut(), houses);
+                        let operation = dialog.run();
+
+                        self.win.activate();
+
+                        let applied = operation.is_some();
+                        if let Some(operation) = operation {
+                            self.history.record(operation);
+                        }
+                        if applied || unsaved == Unsaved::Discarded {
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
+                    Action::Table => {
+                        let unsaved = self.ask_unsaved_changes();
+                        if unsaved == Unsaved::Cancelled {
+                            continue;
+                        }
+                        let page = Page {
+                            offset: 0,
+                            limit: Some(TABLE_ROWS),
+                        };
+                        let (houses, total) = match self.dao.get_houses(&self.current_filter, page)
+                        {
+                            Ok(page) => (page.houses, page.total),
+                            Err(error) => {
+                                self.show_error("No se pudieron leer las viviendas", &error);
+                                continue;
+                            }
+                        };
+                        self.win.deactivate();
+
+                        let width = 1000;
+                        let height = 600;
+                        let (x, y) = self.get_pos(width, height);
+
+                        let layout = self.config.table.clone().unwrap_or_default();
+                        let mut dialog =
+                            TableDialog::new(x, y, width, height, &houses, total, &layout);
+                        let chosen = dialog.run();
+                        let layout = dialog.layout();
+
+                        self.win.activate();
+
+                        if self.config.table.as_ref() != Some(&layout) {
+                            self.config.table = Some(layout);
+                            if let Err(error) = self.config.save() {
+                                self.show_error("No se pudo guardar la configuración", &error);
+                            }
+                        }
+                        if let Some(id) = chosen {
+                            // The page of the list that has the house
+                            let index =
+                                houses.iter().position(|house| house.id == id).unwrap_or(0) as i64;
+                            self.offset = index / PAGE_SIZE * PAGE_SIZE;
+                            self.hid_select = id;
+                        }
+                        if chosen.is_some() || unsaved == Unsaved::Discarded {
+                            self.set_buttons_new_save_delete(true, false, false);
+                            self.fill_select(); // update delete button
+                            self.reset_inputs_color();
+                        }
+                    }
+
                     Action::Close => {
-                        // TODO Should be checked if there are unsaved changes to ask what to do
-                        self.app.quit();
+                        if self.ask_unsaved_changes() != Unsaved::Cancelled {
+                            self.app.quit();
+                        }
                     }
                 }
             }
@@ -1,38 +1,167 @@
-use super::{wrapper::Widget, BUTTON_HEIGHT, BUTTON_WIDTH, HOVER_COLOR, MARGIN_SIZE, NORMAL_COLOR};
-use crate::base::{Filter, Range, HOUSE_TYPES};
-
-use std::collections::HashMap;
+use super::{
+    history::Operation, wrapper::Widget, BUTTON_HEIGHT, BUTTON_WIDTH, DATE_FORMAT, HOVER_COLOR,
+    MARGIN_SIZE, NORMAL_COLOR,
+};
+use crate::base::{
+    normalize, AuditEntry, Change, Deleted, Error, Filter, House, HouseType, Lease, Near, Owner,
+    Payment, PaymentMethod, Range, Tag, Tenant, DAO,
+};
+use crate::config::{Column, TableLayout};
+use crate::gazetteer::Candidate;
+use crate::json;
+use crate::ledger::{self, Entry};
+
+use std::{
+    cell::RefCell, cmp::Ordering, collections::HashMap, fmt::Display, iter, path::PathBuf, rc::Rc,
+    str::FromStr,
+};
 
+use chrono::{Days, Local, NaiveDate};
 use fltk::{
     app::{self, channel, Receiver, Sender},
-    button::Button,
-    enums::{Event, Font, FrameType, Key},
+    browser::{CheckBrowser, HoldBrowser},
+    button::{Button, CheckButton},
+    dialog::{NativeFileChooser, NativeFileChooserOptions, NativeFileChooserType},
+    draw,
+    enums::{Align, CallbackTrigger, Color, Event, Font, FrameType, Key},
     frame::Frame,
     group::Flex,
     input::{FloatInput, Input, IntInput},
     menu::Choice,
     prelude::*,
+    table::{TableContext, TableRow, TableRowSelectFlag, TableRowSelectMode},
     window::DoubleWindow,
 };
 
 pub const ALL_TYPE: &str = "«Todos los tipos»";
 
+pub fn choose_file(title: &str, filter: &str, save: Option<&str>) -> Option<PathBuf> {
+    let mut chooser = NativeFileChooser::new(match save {
+        Some(_) => NativeFileChooserType::BrowseSaveFile,
+        None => NativeFileChooserType::BrowseFile,
+    });
+    chooser.set_title(title);
+    chooser.set_filter(filter);
+    if let Some(name) = save {
+        chooser.set_option(NativeFileChooserOptions::SaveAsConfirm);
+        chooser.set_preset_file(name);
+    }
+    chooser.show();
+    let path = chooser.filename();
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Signal {
+    Accept,
+    Cancel,
+    Open,
+    Save,
+    Pick,
+    Add,
+    Rename,
+    Retire,
+    Delete,
+    Restore,
+    Empty,
+    Preview,
+    Columns,
+    Sort(i32), // visible column
+}
+
+fn create_button(
+    window: &DoubleWindow,
+    sender: Sender<Signal>,
+    caption: &str,
+    signal: Signal,
+) -> Button {
+    let mut button = Button::default().with_label(caption);
+    button.set_color(NORMAL_COLOR);
+    button.handle(move |b, ev| match ev {
+        Event::Enter => {
+            b.set_color(HOVER_COLOR);
+            b.redraw();
+            true
+        }
+        Event::Leave => {
+            b.set_color(NORMAL_COLOR);
+            b.redraw();
+            true
+        }
+        _ => false,
+    });
+    button.set_callback({
+        let mut win = window.clone();
+        move |_| {
+            if signal == Signal::Accept || signal == Signal::Cancel {
+                win.hide();
+            }
+            sender.send(signal);
+        }
+    });
+    button
+}
+
+fn show_message(parent: &DoubleWindow, title: &str, message: &str) {
+    let width = 480;
+    let height = 200;
+    let x = parent.x() + (parent.w() - width) / 2;
+    let y = parent.y() + (parent.h() - height) / 2;
+    let buttons = vec![MDButton::new("Aceptar", 0)];
+    let mut dialog = MessageDialog::new(
+        x,
+        y,
+        width,
+        height,
+        title.to_owned(),
+        message.to_owned(),
+        buttons,
+    );
+    dialog.run();
+}
+
+fn confirm(parent: &DoubleWindow, title: &str, message: &str, caption: &str) -> bool {
+    let width = 480;
+    let height = 200;
+    let x = parent.x() + (parent.w() - width) / 2;
+    let y = parent.y() + (parent.h() - height) / 2;
+    let buttons = vec![MDButton::new("Cancelar", 0), MDButton::new(caption, 1)];
+    let mut dialog = MessageDialog::new(
+        x,
+        y,
+        width,
+        height,
+        title.to_owned(),
+        message.to_owned(),
+        buttons,
+    );
+    dialog.run() == 1
+}
+
 pub struct FilterDialog {
     window: DoubleWindow,
     inputs: HashMap<String, Vec<Widget>>,
-    sender: Sender<bool>,
-    receiver: Receiver<bool>,
+    kinds: Vec<String>,
+    tags: Vec<String>,
+    sender: Sender<Signal>,
+    receiver: Receiver<Signal>,
 }
 
 impl FilterDialog {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, kinds: Vec<String>, tags: Vec<String>) -> Self {
         let w_min = 4 * MARGIN_SIZE + 2 * BUTTON_WIDTH;
         debug_assert!(w > w_min, "width({w}) > {w_min}");
 
-        let (sender, receiver) = channel::<bool>();
+        let (sender, receiver) = channel::<Signal>();
         Self {
             window: DoubleWindow::new(x, y, w, h, None),
             inputs: HashMap::new(),
+            kinds,
+            tags,
             sender,
             receiver,
         }
@@ -70,6 +199,7 @@ impl FilterDialog {
             self.create_input("number", "Número");
             self.create_input("floor", "Piso");
             self.create_input("postcode", "Código postal");
+            self.create_input("price", "Precio");
 
             let text = self.set_text_min_max();
             left.set_size(&text, 12);
@@ -80,6 +210,7 @@ impl FilterDialog {
             let mut right = Flex::default().column();
 
             self.create_input("street", "Calle");
+            self.create_input("owner", "Propietario");
             self.create_input("rooms", "Habitaciones");
             self.create_input("baths", "Baños");
             self.create_input("area", "Superficie (m²)");
@@ -94,13 +225,35 @@ impl FilterDialog {
         let sep = Frame::default();
         main.set_size(&sep, 16);
 
+        {
+            let tags = Flex::default().row();
+            self.create_input("all_tags", "Tiene todas");
+            self.create_input("any_tags", "Tiene alguna");
+            tags.end();
+            main.set_size(&tags, 5 * BUTTON_HEIGHT);
+        }
+
+        let sep = Frame::default();
+        main.set_size(&sep, 16);
+
+        {
+            let near = Flex::default().row();
+            self.create_input("near", "Cerca de (latitud, longitud y radio en km)");
+            near.end();
+            main.set_size(&near, BUTTON_HEIGHT);
+        }
+
+        let sep = Frame::default();
+        main.set_size(&sep, 16);
+
         {
             let buttons = Flex::default().row();
 
+            self.create_button("Abrir…", Signal::Open);
+            self.create_button("Guardar…", Signal::Save);
             Frame::default();
-            self.create_button("Cancelar");
-            self.create_button("Aceptar");
-            Frame::default();
+            self.create_button("Cancelar", Signal::Cancel);
+            self.create_button("Aceptar", Signal::Accept);
 
             buttons.end();
             main.set_size(&buttons, BUTTON_HEIGHT);
@@ -111,16 +264,16 @@ impl FilterDialog {
         self.window.end();
 
         self.window.handle({
-            let sender = self.sender.clone();
+            let sender = self.sender;
             move |w, ev| match ev {
                 Event::KeyDown => match app::event_key() {
                     Key::Enter | Key::KPEnter => {
-                        sender.send(true);
+                        sender.send(Signal::Accept);
                         w.hide();
                         true
                     }
                     Key::Escape => {
-                        sender.send(false);
+                        sender.send(Signal::Cancel);
                         w.hide();
                         true
                     }
@@ -131,10 +284,10 @@ impl FilterDialog {
         });
 
         self.window.set_callback({
-            let sender = self.sender.clone();
+            let sender = self.sender;
             move |w| {
                 if app::event() == Event::Close {
-                    sender.send(false);
+                    sender.send(Signal::Cancel);
                     w.hide();
                 }
             }
@@ -165,6 +318,22 @@ impl FilterDialog {
                 input.set_tooltip("Ingrese la dirección exacta o aproximada");
                 vec.push(Widget::TInput(input));
             }
+            "owner" => {
+                let mut input = Input::default();
+                input.set_tooltip(
+                    "Ingrese el nombre exacto o aproximado de alguno de los propietarios",
+                );
+                vec.push(Widget::TInput(input));
+            }
+            "all_tags" | "any_tags" => {
+                let mut checks = CheckBrowser::default();
+                checks.set_tooltip(if key == "all_tags" {
+                    "La vivienda debe tener todas las características marcadas"
+                } else {
+                    "La vivienda debe tener al menos una de las características marcadas"
+                });
+                vec.push(Widget::Checks(checks));
+            }
             "area" => {
                 let row = Flex::default().row();
 
@@ -178,6 +347,34 @@ impl FilterDialog {
 
                 row.end();
             }
+            "near" => {
+                let row = Flex::default().row();
+
+                for tooltip in [
+                    "Latitud del punto en grados decimales, negativa al sur",
+                    "Longitud del punto en grados decimales, negativa al oeste",
+                    "Distancia máxima al punto en km, vacía para ordenar sin límite",
+                ] {
+                    let mut input = FloatInput::default();
+                    input.set_tooltip(tooltip);
+                    vec.push(Widget::FInput(input));
+                }
+
+                row.end();
+            }
+            "price" => {
+                let row = Flex::default().row();
+
+                let mut input = FloatInput::default();
+                input.set_tooltip("Desde (valor mínimo), en la moneda de cada vivienda");
+                vec.push(Widget::FInput(input));
+
+                let mut input = FloatInput::default();
+                input.set_tooltip("Hasta (valor máximo), en la moneda de cada vivienda");
+                vec.push(Widget::FInput(input));
+
+                row.end();
+            }
             _ => {
                 let row = Flex::default().row();
 
@@ -196,42 +393,51 @@ impl FilterDialog {
         self.inputs.insert(key.to_owned(), vec);
     }
 
-    fn create_button(&self, caption: &str) {
-        let mut button = Button::default().with_label(caption);
-        button.set_color(NORMAL_COLOR);
-        button.handle(move |b, ev| match ev {
-            Event::Enter => {
-                b.set_color(HOVER_COLOR);
-                b.redraw();
-                true
-            }
-            Event::Leave => {
-                b.set_color(NORMAL_COLOR);
-                b.redraw();
-                true
-            }
-            _ => false,
-        });
-        let signal = caption == "Aceptar";
-        button.set_callback({
-            let mut win = self.window.clone();
-            let sender = self.sender.clone();
-            move |_| {
-                win.hide();
-                sender.send(signal);
-            }
-        });
+    fn create_button(&self, caption: &str, signal: Signal) {
+        create_button(&self.window, self.sender, caption, signal);
     }
 
     fn fill_kind(&mut self) {
         let widgets = self.inputs.get_mut("kind").unwrap();
         widgets[0].add(ALL_TYPE);
-        for value in HOUSE_TYPES {
+        for value in &self.kinds {
             widgets[0].add(value);
         }
         widgets[0].set("0");
     }
 
+    fn fill_tags(&mut self) {
+        for key in ["all_tags", "any_tags"] {
+            let widgets = self.inputs.get_mut(key).unwrap();
+            for tag in &self.tags {
+                widgets[0].add(tag);
+            }
+        }
+    }
+
+    fn get_tags(&self, key: &str) -> Vec<String> {
+        let widgets = self.inputs.get(key).unwrap();
+        widgets[0]
+            .get()
+            .split(',')
+            .filter_map(|index| index.parse::<usize>().ok())
+            .map(|index| self.tags[index - 1].clone())
+            .collect()
+    }
+
+    // Tags of a saved filter that are no longer in the catalogue are dropped
+    fn set_tags(&mut self, key: &str, names: &[String]) {
+        let checked: Vec<String> = self
+            .tags
+            .iter()
+            .enumerate()
+            .filter(|(_, tag)| names.iter().any(|name| Tag::same(tag, name)))
+            .map(|(index, _)| (index + 1).to_string())
+            .collect();
+        let widgets = self.inputs.get_mut(key).unwrap();
+        widgets[0].set(checked.join(","));
+    }
+
     fn get_value_i32(&self, key: &str) -> Range<i32> {
         let widgets = self.inputs.get(key).unwrap();
         Range {
@@ -248,6 +454,39 @@ impl FilterDialog {
         }
     }
 
+    fn get_value_f64(&self, key: &str) -> Range<f64> {
+        let widgets = self.inputs.get(key).unwrap();
+        Range {
+            min: widgets[0].get().parse().unwrap_or(0.0),
+            max: widgets[1].get().parse().unwrap_or(f64::MAX),
+        }
+    }
+
+    fn get_near(&self) -> Option<Near> {
+        let widgets = self.inputs.get("near").unwrap();
+        let latitude = widgets[0].get().parse().ok()?;
+        let longitude = widgets[1].get().parse().ok()?;
+        Some(Near {
+            latitude,
+            longitude,
+            radius: widgets[2].get().parse().unwrap_or(f64::MAX),
+        })
+    }
+
+    fn set_near(&mut self, near: Option<&Near>) {
+        let widgets = self.inputs.get_mut("near").unwrap();
+        for widget in widgets.iter_mut() {
+            widget.set("");
+        }
+        if let Some(near) = near {
+            widgets[0].set(near.latitude);
+            widgets[1].set(near.longitude);
+            if near.is_limited() {
+                widgets[2].set(near.radius);
+            }
+        }
+    }
+
     fn get_filter(&self) -> Filter {
         let mut filter = Filter::default();
 
@@ -256,30 +495,126 @@ impl FilterDialog {
         filter.kind.text = if index == 0 {
             "".to_owned()
         } else {
-            HOUSE_TYPES[index - 1].to_owned()
+            self.kinds[index - 1].clone()
         };
 
         let widgets = self.inputs.get("street").unwrap();
         filter.street.text = widgets[0].get();
 
+        let widgets = self.inputs.get("owner").unwrap();
+        filter.owner.text = widgets[0].get();
+
         filter.number = self.get_value_i32("number");
         filter.floor = self.get_value_i32("floor");
         filter.postcode = self.get_value_i32("postcode");
         filter.rooms = self.get_value_i32("rooms");
         filter.baths = self.get_value_i32("baths");
         filter.area = self.get_value_f32("area");
+        filter.price = self.get_value_f64("price");
+        filter.all_tags = self.get_tags("all_tags");
+        filter.any_tags = self.get_tags("any_tags");
+        filter.near = self.get_near();
 
         filter
     }
 
+    fn set_range<T>(&mut self, key: &str, range: &Range<T>)
+    where
+        T: PartialOrd + FromStr + Display,
+        Range<T>: Default,
+    {
+        // The default bounds are left empty, as when the dialog opens
+        let default = Range::<T>::default();
+        let widgets = self.inputs.get_mut(key).unwrap();
+        if range.min != default.min {
+            widgets[0].set(&range.min);
+        }
+        if range.max != default.max {
+            widgets[1].set(&range.max);
+        }
+    }
+
+    fn set_filter(&mut self, filter: &Filter) {
+        // A kind out of the catalogue, retired or written by another tool, is kept as free text
+        let text = &filter.kind.text;
+        let index = match self.kinds.iter().position(|kind| kind == text) {
+            Some(index) => index + 1,
+            None if text.is_empty() => 0,
+            None => {
+                self.kinds.push(text.clone());
+                self.inputs.get_mut("kind").unwrap()[0].add(text);
+                self.kinds.len()
+            }
+        };
+        let widgets = self.inputs.get_mut("kind").unwrap();
+        widgets[0].set(index);
+
+        let widgets = self.inputs.get_mut("street").unwrap();
+        widgets[0].set(&filter.street.text);
+
+        let widgets = self.inputs.get_mut("owner").unwrap();
+        widgets[0].set(&filter.owner.text);
+
+        for key in [
+            "number", "floor", "postcode", "rooms", "baths", "area", "price",
+        ] {
+            for widget in self.inputs.get_mut(key).unwrap() {
+                widget.set("");
+            }
+        }
+        self.set_range("number", &filter.number);
+        self.set_range("floor", &filter.floor);
+        self.set_range("postcode", &filter.postcode);
+        self.set_range("rooms", &filter.rooms);
+        self.set_range("baths", &filter.baths);
+        self.set_range("area", &filter.area);
+        self.set_range("price", &filter.price);
+        self.set_tags("all_tags", &filter.all_tags);
+        self.set_tags("any_tags", &filter.any_tags);
+        self.set_near(filter.near.as_ref());
+    }
+
+    fn open_filter(&mut self) {
+        if let Some(path) = choose_file("Abrir filtro", "JSON\t*.json", None) {
+            match json::load_filter(&path) {
+                Ok(filter) => self.set_filter(&filter),
+                Err(error) => show_message(
+                    &self.window,
+                    "Error",
+                    &format!("No se pudo abrir el filtro\n{error}"),
+                ),
+            }
+        }
+    }
+
+    fn save_filter(&self) {
+        if let Some(path) = choose_file("Guardar filtro", "JSON\t*.json", Some("filtro.json")) {
+            if let Err(error) = json::save_filter(&path, &self.get_filter()) {
+                show_message(
+                    &self.window,
+                    "Error",
+                    &format!("No se pudo guardar el filtro\n{error}"),
+                );
+            }
+        }
+    }
+
     pub fn run(&mut self) -> Option<Filter> {
         self.build();
         self.fill_kind();
+        self.fill_tags();
         self.window.show();
+        let mut accepted = false;
         while self.window.shown() {
             app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Open) => self.open_filter(),
+                Some(Signal::Save) => self.save_filter(),
+                Some(signal) => accepted = signal == Signal::Accept,
+                None => {}
+            }
         }
-        if self.receiver.recv().unwrap_or(false) {
+        if accepted {
             Some(self.get_filter())
         } else {
             None
@@ -287,6 +622,2158 @@ impl FilterDialog {
     }
 }
 
+pub struct KindsDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    types: Vec<HouseType>,
+    browser: HoldBrowser,
+    name: Input,
+    retire: Button,
+    changed: bool,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> KindsDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Tipos de vivienda");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Los tipos retirados se conservan en las viviendas que los usan\n\
+             pero no se ofrecen para las viviendas nuevas",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.emit(sender, Signal::Pick);
+
+        let mut name;
+        {
+            let row = Flex::default().row();
+            Frame::default().with_label("Nombre");
+            name = Input::default();
+            name.set_tooltip("Nombre del tipo a agregar, o nuevo nombre del tipo seleccionado");
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        let retire;
+        {
+            let buttons = Flex::default().row();
+
+            create_button(&window, sender, "Agregar", Signal::Add);
+            create_button(&window, sender, "Renombrar", Signal::Rename);
+            retire = create_button(&window, sender, "Retirar", Signal::Retire);
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            types: Vec::new(),
+            browser,
+            name,
+            retire,
+            changed: false,
+            receiver,
+        }
+    }
+
+    fn fill_types(&mut self, select: i32) {
+        match self.dao.get_house_types() {
+            Ok(types) => self.types = types,
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudieron leer los tipos\n{error}"),
+            ),
+        }
+        self.browser.clear();
+        for house_type in &self.types {
+            if house_type.retired {
+                self.browser.add(&format!("{} (retirado)", house_type.name));
+            } else {
+                self.browser.add(&house_type.name);
+            }
+        }
+        if let Some(index) = self.types.iter().position(|t| t.id == select) {
+            self.browser.select(index as i32 + 1);
+        }
+        self.pick();
+    }
+
+    fn selected(&self) -> Option<&HouseType> {
+        let index = self.browser.value();
+        if index > 0 {
+            self.types.get(index as usize - 1)
+        } else {
+            None
+        }
+    }
+
+    fn pick(&mut self) {
+        let (name, retired) = match self.selected() {
+            Some(house_type) => (house_type.name.clone(), house_type.retired),
+            None => (String::new(), false),
+        };
+        self.name.set_value(&name);
+        self.retire
+            .set_label(if retired { "Restaurar" } else { "Retirar" });
+        if self.selected().is_some() {
+            self.retire.activate();
+        } else {
+            self.retire.deactivate();
+        }
+    }
+
+    fn apply(&mut self, signal: Signal) {
+        let name = self.name.value();
+        let selected = self.selected().cloned();
+        let result = match (signal, selected) {
+            (Signal::Add, _) => self.dao.create_house_type(&name).map(|t| t.id),
+            (Signal::Rename, Some(house_type)) => self
+                .dao
+                .rename_house_type(house_type.id, &name)
+                .map(|_| house_type.id),
+            (Signal::Retire, Some(house_type)) => self
+                .dao
+                .retire_house_type(house_type.id, !house_type.retired)
+                .map(|_| house_type.id),
+            _ => return,
+        };
+        match result {
+            Ok(id) => {
+                self.changed = true;
+                self.fill_types(id);
+            }
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudo modificar el tipo\n{error}"),
+            ),
+        }
+    }
+
+    pub fn run(&mut self) -> bool {
+        self.fill_types(-1);
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Pick) => self.pick(),
+                Some(signal @ (Signal::Add | Signal::Rename | Signal::Retire)) => {
+                    self.apply(signal)
+                }
+                _ => {}
+            }
+        }
+        self.changed
+    }
+}
+
+pub struct TagsDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    tags: Vec<Tag>,
+    browser: HoldBrowser,
+    name: Input,
+    changed: bool,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> TagsDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Características");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Solo se pueden borrar las características que no tiene ninguna vivienda,\n\
+             un cambio de nombre se aplica a todas las viviendas que la tienen",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.emit(sender, Signal::Pick);
+
+        let mut name;
+        {
+            let row = Flex::default().row();
+            Frame::default().with_label("Nombre");
+            name = Input::default();
+            name.set_tooltip(
+                "Nombre de la característica a agregar, o nuevo nombre de la seleccionada",
+            );
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        {
+            let buttons = Flex::default().row();
+
+            create_button(&window, sender, "Agregar", Signal::Add);
+            create_button(&window, sender, "Renombrar", Signal::Rename);
+            create_button(&window, sender, "Borrar", Signal::Delete);
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            tags: Vec::new(),
+            browser,
+            name,
+            changed: false,
+            receiver,
+        }
+    }
+
+    fn fill_tags(&mut self, select: i32) {
+        match self.dao.get_tags() {
+            Ok(tags) => self.tags = tags,
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudieron leer las características\n{error}"),
+            ),
+        }
+        self.browser.clear();
+        for tag in &self.tags {
+            self.browser.add(&tag.name);
+        }
+        if let Some(index) = self.tags.iter().position(|t| t.id == select) {
+            self.browser.select(index as i32 + 1);
+        }
+        self.pick();
+    }
+
+    fn selected(&self) -> Option<&Tag> {
+        let index = self.browser.value();
+        if index > 0 {
+            self.tags.get(index as usize - 1)
+        } else {
+            None
+        }
+    }
+
+    fn pick(&mut self) {
+        let name = self
+            .selected()
+            .map(|tag| tag.name.clone())
+            .unwrap_or_default();
+        self.name.set_value(&name);
+    }
+
+    fn apply(&mut self, signal: Signal) {
+        let name = self.name.value();
+        let selected = self.selected().cloned();
+        let result = match (signal, selected) {
+            (Signal::Add, _) => self.dao.create_tag(&name).map(|t| t.id),
+            (Signal::Rename, Some(tag)) => self.dao.rename_tag(tag.id, &name).map(|_| tag.id),
+            (Signal::Delete, Some(tag)) => self.dao.delete_tag(tag.id).map(|_| -1),
+            _ => return,
+        };
+        match result {
+            Ok(id) => {
+                self.changed = true;
+                self.fill_tags(id);
+            }
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudo modificar la característica\n{error}"),
+            ),
+        }
+    }
+
+    pub fn run(&mut self) -> bool {
+        self.fill_tags(-1);
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Pick) => self.pick(),
+                Some(signal @ (Signal::Add | Signal::Rename | Signal::Delete)) => {
+                    self.apply(signal)
+                }
+                _ => {}
+            }
+        }
+        self.changed
+    }
+}
+
+const CONTACT_FIELDS: [&str; 5] = [
+    "Nombre",
+    "Teléfono",
+    "Correo electrónico",
+    "Identificación fiscal",
+    "Notas",
+];
+
+pub trait Contact: Clone + Default {
+    const TITLE: &'static str;
+    const EXPLANATION: &'static str;
+    const ERROR: &'static str;
+
+    fn id(&self) -> i32;
+    fn values(self) -> [String; 5]; // in the order of CONTACT_FIELDS
+    fn with_values(id: i32, values: [String; 5]) -> Self;
+    fn load(dao: &mut dyn DAO) -> Result<Vec<Self>, Error>;
+    fn create(&self, dao: &mut dyn DAO) -> Result<i32, Error>;
+    fn update(&self, dao: &mut dyn DAO) -> Result<bool, Error>;
+    fn delete(&self, dao: &mut dyn DAO) -> Result<bool, Error>;
+}
+
+impl Contact for Owner {
+    const TITLE: &'static str = "Propietarios";
+    const EXPLANATION: &'static str = "Seleccione un propietario para modificar sus datos\n\
+                                       Solo se pueden borrar los propietarios que no figuran en ninguna vivienda";
+    const ERROR: &'static str = "No se pudo modificar el propietario";
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn values(self) -> [String; 5] {
+        [self.name, self.phone, self.email, self.tax_id, self.notes]
+    }
+
+    fn with_values(id: i32, [name, phone, email, tax_id, notes]: [String; 5]) -> Self {
+        Owner {
+            id,
+            name,
+            phone,
+            email,
+            tax_id,
+            notes,
+        }
+    }
+
+    fn load(dao: &mut dyn DAO) -> Result<Vec<Self>, Error> {
+        dao.get_owners()
+    }
+
+    fn create(&self, dao: &mut dyn DAO) -> Result<i32, Error> {
+        dao.create_owner(self).map(|owner| owner.id)
+    }
+
+    fn update(&self, dao: &mut dyn DAO) -> Result<bool, Error> {
+        dao.update_owner(self)
+    }
+
+    fn delete(&self, dao: &mut dyn DAO) -> Result<bool, Error> {
+        dao.delete_owner(self.id)
+    }
+}
+
+impl Contact for Tenant {
+    const TITLE: &'static str = "Inquilinos";
+    const EXPLANATION: &'static str = "Seleccione un inquilino para modificar sus datos\n\
+                                       Solo se pueden borrar los inquilinos que no figuran en ningún contrato";
+    const ERROR: &'static str = "No se pudo modificar el inquilino";
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn values(self) -> [String; 5] {
+        [self.name, self.phone, self.email, self.tax_id, self.notes]
+    }
+
+    fn with_values(id: i32, [name, phone, email, tax_id, notes]: [String; 5]) -> Self {
+        Tenant {
+            id,
+            name,
+            phone,
+            email,
+            tax_id,
+            notes,
+        }
+    }
+
+    fn load(dao: &mut dyn DAO) -> Result<Vec<Self>, Error> {
+        dao.get_tenants()
+    }
+
+    fn create(&self, dao: &mut dyn DAO) -> Result<i32, Error> {
+        dao.create_tenant(self).map(|tenant| tenant.id)
+    }
+
+    fn update(&self, dao: &mut dyn DAO) -> Result<bool, Error> {
+        dao.update_tenant(self)
+    }
+
+    fn delete(&self, dao: &mut dyn DAO) -> Result<bool, Error> {
+        dao.delete_tenant(self.id)
+    }
+}
+
+pub struct ContactsDialog<'a, T: Contact> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    contacts: Vec<T>,
+    browser: HoldBrowser,
+    fields: Vec<Input>, // in the order of CONTACT_FIELDS
+    changed: bool,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a, T: Contact> ContactsDialog<'a, T> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label(T::TITLE);
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(T::EXPLANATION);
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.emit(sender, Signal::Pick);
+
+        let mut fields = Vec::new();
+        for text in CONTACT_FIELDS {
+            let row = Flex::default().row();
+            Frame::default().with_label(text);
+            fields.push(Input::default());
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        {
+            let buttons = Flex::default().row();
+
+            create_button(&window, sender, "Agregar", Signal::Add);
+            create_button(&window, sender, "Guardar", Signal::Save);
+            create_button(&window, sender, "Borrar", Signal::Delete);
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            contacts: Vec::new(),
+            browser,
+            fields,
+            changed: false,
+            receiver,
+        }
+    }
+
+    fn fill_contacts(&mut self, select: i32) {
+        match T::load(self.dao) {
+            Ok(contacts) => self.contacts = contacts,
+            Err(error) => {
+                let message = format!(
+                    "No se pudieron leer los {}\n{error}",
+                    T::TITLE.to_lowercase()
+                );
+                show_message(&self.window, "Error", &message);
+            }
+        }
+        self.browser.clear();
+        for contact in &self.contacts {
+            let [name, ..] = contact.clone().values();
+            self.browser.add(&name);
+        }
+        if let Some(index) = self.contacts.iter().position(|c| c.id() == select) {
+            self.browser.select(index as i32 + 1);
+        }
+        self.pick();
+    }
+
+    fn selected(&self) -> Option<&T> {
+        let index = self.browser.value();
+        if index > 0 {
+            self.contacts.get(index as usize - 1)
+        } else {
+            None
+        }
+    }
+
+    fn pick(&mut self) {
+        let values = self.selected().cloned().unwrap_or_default().values();
+        for (field, value) in self.fields.iter_mut().zip(values) {
+            field.set_value(&value);
+        }
+    }
+
+    fn get_contact(&self) -> T {
+        let mut values: [String; 5] = Default::default();
+        for (value, field) in values.iter_mut().zip(&self.fields) {
+            *value = field.value().trim().to_owned();
+        }
+        T::with_values(self.selected().map_or(-1, |contact| contact.id()), values)
+    }
+
+    fn apply(&mut self, signal: Signal) {
+        let contact = self.get_contact();
+        let selected = self.selected().is_some();
+        let result = match signal {
+            Signal::Add => contact.create(self.dao),
+            Signal::Save if selected => contact.update(self.dao).map(|_| contact.id()),
+            Signal::Delete if selected => contact.delete(self.dao).map(|_| -1),
+            _ => return,
+        };
+        match result {
+            Ok(id) => {
+                self.changed = true;
+                self.fill_contacts(id);
+            }
+            Err(error) => show_message(&self.window, "Error", &format!("{}\n{error}", T::ERROR)),
+        }
+    }
+
+    pub fn run(&mut self) -> bool {
+        self.fill_contacts(-1);
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Pick) => self.pick(),
+                Some(signal @ (Signal::Add | Signal::Save | Signal::Delete)) => self.apply(signal),
+                _ => {}
+            }
+        }
+        self.changed
+    }
+}
+
+pub struct ExpiringDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    days: IntInput,
+    browser: HoldBrowser,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> ExpiringDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO, days: i64) -> Self {
+        let w_min = 4 * MARGIN_SIZE + 3 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Contratos por vencer");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Contratos vigentes que terminan dentro de los días indicados\n\
+             También se muestran los que ya terminaron y siguen vigentes",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let input = {
+            let mut row = Flex::default().row();
+            let label = Frame::default().with_label("Días");
+            row.set_size(&label, BUTTON_WIDTH / 2);
+            let mut input = IntInput::default();
+            input.set_value(&days.to_string());
+            row.set_size(&input, BUTTON_WIDTH);
+            create_button(&window, sender, "Actualizar", Signal::Open);
+            Frame::default();
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+            input
+        };
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[120, 80, 300]); // end, days left, house and tenant
+
+        {
+            let buttons = Flex::default().row();
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown => match app::event_key() {
+                Key::Enter | Key::KPEnter => {
+                    sender.send(Signal::Open);
+                    true
+                }
+                Key::Escape => {
+                    sender.send(Signal::Cancel);
+                    w.hide();
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            days: input,
+            browser,
+            receiver,
+        }
+    }
+
+    fn fill_leases(&mut self) {
+        let today = Local::now().date_naive();
+        let days = self.days.value().parse::<u64>().unwrap_or(0);
+        // Too many days to add reach every lease
+        let until = today
+            .checked_add_days(Days::new(days))
+            .unwrap_or(NaiveDate::MAX);
+        let leases = match self.dao.get_expiring_leases(until) {
+            Ok(leases) => leases,
+            Err(error) => {
+                show_message(
+                    &self.window,
+                    "Error",
+                    &format!("No se pudieron leer los contratos\n{error}"),
+                );
+                Vec::new()
+            }
+        };
+        self.browser.clear();
+        self.browser.add("@bVence\t@bDías\t@bVivienda\t@bInquilino");
+        for lease in &leases {
+            let house = match self.dao.get_house(lease.house_id) {
+                Ok(house) => format!("{} al {}", house.street, house.number),
+                Err(_) => lease.house_id.to_string(),
+            };
+            let line = format!(
+                "{}\t{}\t{house}\t{}",
+                lease.end,
+                lease.days_left(today),
+                lease.tenant
+            );
+            self.browser.add(&line);
+        }
+    }
+
+    pub fn run(&mut self) -> i64 {
+        self.fill_leases();
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            if let Some(Signal::Open) = self.receiver.recv() {
+                self.fill_leases();
+            }
+        }
+        self.days.value().parse().unwrap_or(0)
+    }
+}
+
+pub struct AccountDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    lease: Lease,
+    entries: Vec<Entry>,
+    browser: HoldBrowser,
+    date: Input,
+    amount: FloatInput,
+    method: Choice,
+    notes: Input,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> AccountDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO, lease: Lease) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title =
+            Frame::default().with_label(&format!("Cuenta corriente de {}", lease.tenant));
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(&format!(
+            "Cargos mensuales de sus contratos y pagos, el saldo es lo adeudado en cada moneda\n\
+             Los pagos se registran en el contrato {} y en su moneda ({})",
+            lease.id, lease.currency
+        ));
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[100, 110, 110, 140]); // date, charge, payment, balance and concept
+
+        let today = Local::now().date_naive();
+        let (mut date, mut amount) = {
+            let row = Flex::default().row();
+            Frame::default().with_label("Fecha");
+            let date = Input::default();
+            Frame::default().with_label("Importe");
+            let amount = FloatInput::default();
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+            (date, amount)
+        };
+        date.set_value(&today.format(DATE_FORMAT).to_string());
+        amount.set_value(&format!("{:.2}", lease.rent_at(today)));
+
+        let (mut method, notes) = {
+            let row = Flex::default().row();
+            Frame::default().with_label("Medio");
+            let method = Choice::default();
+            Frame::default().with_label("Notas");
+            let notes = Input::default();
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+            (method, notes)
+        };
+        for value in PaymentMethod::ALL {
+            method.add_choice(&value.to_string());
+        }
+        let default = PaymentMethod::ALL
+            .iter()
+            .position(|&m| m == PaymentMethod::default());
+        method.set_value(default.map_or(0, |index| index as i32));
+
+        {
+            let buttons = Flex::default().row();
+
+            create_button(&window, sender, "Registrar", Signal::Add);
+            create_button(&window, sender, "Borrar pago", Signal::Delete);
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            lease,
+            entries: Vec::new(),
+            browser,
+            date,
+            amount,
+            method,
+            notes,
+            receiver,
+        }
+    }
+
+    fn fill_account(&mut self) {
+        let today = Local::now().date_naive();
+        match ledger::account(self.dao, self.lease.tenant_id, today) {
+            Ok(entries) => self.entries = entries,
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudo leer la cuenta\n{error}"),
+            ),
+        }
+        let amount = |value: f64| {
+            if value > 0.0 {
+                format!("{value:.2}")
+            } else {
+                String::new()
+            }
+        };
+        self.browser.clear();
+        self.browser
+            .add("@bFecha\t@bCargo\t@bPago\t@bSaldo\t@bConcepto");
+        for entry in &self.entries {
+            let line = format!(
+                "{}\t{}\t{}\t{:.2} {}\t{}",
+                entry.date,
+                amount(entry.charge),
+                amount(entry.payment),
+                entry.balance,
+                entry.currency,
+                entry.concept
+            );
+            self.browser.add(&line);
+        }
+        self.browser.bottom_line(self.browser.size());
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        // The first line is the header
+        let index = self.browser.value();
+        if index > 1 {
+            self.entries.get(index as usize - 2)
+        } else {
+            None
+        }
+    }
+
+    fn apply(&mut self, signal: Signal) {
+        let result = match signal {
+            Signal::Add => {
+                let Ok(date) = NaiveDate::parse_from_str(self.date.value().trim(), DATE_FORMAT)
+                else {
+                    let today = Local::now().date_naive();
+                    let message =
+                        format!("La fecha debe tener el formato AAAA-MM-DD, como {today}");
+                    show_message(&self.window, "Error", &message);
+                    return;
+                };
+                let payment = Payment {
+                    id: -1,
+                    lease_id: self.lease.id,
+                    date,
+                    amount: self.amount.value().trim().parse().unwrap_or(0.0),
+                    method: PaymentMethod::ALL[self.method.value().max(0) as usize],
+                    notes: self.notes.value().trim().to_owned(),
+                };
+                self.dao.create_payment(&payment).map(|_| ())
+            }
+            _ => match self.selected().and_then(|entry| entry.payment_id) {
+                Some(id) => self.dao.delete_payment(id).map(|_| ()),
+                None => {
+                    show_message(&self.window, "Error", "Seleccione un pago de la cuenta");
+                    return;
+                }
+            },
+        };
+        match result {
+            Ok(()) => {
+                self.notes.set_value("");
+                self.fill_account();
+            }
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudo registrar el cambio\n{error}"),
+            ),
+        }
+    }
+
+    pub fn run(&mut self) {
+        self.fill_account();
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            if let Some(signal @ (Signal::Add | Signal::Delete)) = self.receiver.recv() {
+                self.apply(signal);
+            }
+        }
+    }
+}
+
+pub struct OverdueDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    browser: HoldBrowser,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> OverdueDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO) -> Self {
+        let w_min = 4 * MARGIN_SIZE + 3 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Morosos");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Viviendas con contratos vigentes que tienen cargos vencidos sin pagar\n\
+             Los pagos cancelan los cargos en el orden en que vencieron",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[100, 60, 140, 220]); // since, days, owed, house and tenant
+
+        {
+            let buttons = Flex::default().row();
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            browser,
+            receiver,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let today = Local::now().date_naive();
+        let report = match ledger::overdue(self.dao, today) {
+            Ok(report) => report,
+            Err(error) => {
+                show_message(
+                    &self.window,
+                    "Error",
+                    &format!("No se pudieron leer los pagos\n{error}"),
+                );
+                Vec::new()
+            }
+        };
+        self.browser
+            .add("@bDesde\t@bDías\t@bDeuda\t@bVivienda\t@bInquilino");
+        for overdue in &report {
+            let line = format!(
+                "{}\t{}\t{} {:.2}\t{}\t{}",
+                overdue.since,
+                overdue.days(today),
+                overdue.lease.currency,
+                overdue.owed,
+                overdue.house,
+                overdue.lease.tenant
+            );
+            self.browser.add(&line);
+        }
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            // Only Cancel is sent, it is taken so it does not reach another dialog
+            self.receiver.recv();
+        }
+    }
+}
+
+pub struct TrashDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    deleted: Vec<Deleted>,
+    browser: HoldBrowser,
+    retention: IntInput,
+    changed: bool,
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> TrashDialog<'a> {
+    pub fn new(
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        dao: &'a mut dyn DAO,
+        retention: Option<u64>,
+    ) -> Self {
+        let w_min = 6 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Papelera");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Las viviendas borradas se pueden restaurar con sus precios y adjuntos\n\
+             Eliminarlas definitivamente borra también sus precios y adjuntos, y no se puede deshacer",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[140, 50, 260]); // deleted at, id, address and kind
+
+        let mut input;
+        {
+            let row = Flex::default().row();
+            Frame::default().with_label("Eliminar después de (días)");
+            input = IntInput::default();
+            input.set_tooltip("Días que las viviendas quedan en la papelera, vacío para conservarlas hasta eliminarlas");
+            if let Some(days) = retention {
+                input.set_value(&days.to_string());
+            }
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        {
+            let buttons = Flex::default().row();
+
+            create_button(&window, sender, "Restaurar", Signal::Restore);
+            create_button(&window, sender, "Eliminar", Signal::Delete);
+            create_button(&window, sender, "Vaciar", Signal::Empty);
+            Frame::default();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            deleted: Vec::new(),
+            browser,
+            retention: input,
+            changed: false,
+            receiver,
+        }
+    }
+
+    fn fill_deleted(&mut self) {
+        match self.dao.get_deleted_houses() {
+            Ok(deleted) => self.deleted = deleted,
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudo leer la papelera\n{error}"),
+            ),
+        }
+        self.browser.clear();
+        for deleted in &self.deleted {
+            let house = &deleted.house;
+            let line = format!(
+                "{}\t{}\t{} al {}\t{}",
+                deleted.deleted_at.format("%Y-%m-%d %H:%M"),
+                house.id,
+                house.street,
+                house.number,
+                house.kind
+            );
+            self.browser.add(&line);
+        }
+    }
+
+    fn selected(&self) -> Option<i32> {
+        let index = self.browser.value();
+        if index > 0 {
+            self.deleted
+                .get(index as usize - 1)
+                .map(|deleted| deleted.house.id)
+        } else {
+            None
+        }
+    }
+
+    fn apply(&mut self, signal: Signal) {
+        let ids = match signal {
+            Signal::Empty => self
+                .deleted
+                .iter()
+                .map(|deleted| deleted.house.id)
+                .collect(),
+            _ => self.selected().into_iter().collect::<Vec<i32>>(),
+        };
+        if ids.is_empty() {
+            return;
+        }
+        if signal != Signal::Restore {
+            let message = format!(
+                "Se eliminarán definitivamente {} viviendas\n¿Desea continuar?",
+                ids.len()
+            );
+            if !confirm(&self.window, "Eliminar", &message, "Eliminar") {
+                return;
+            }
+        }
+        for id in ids {
+            let result = match signal {
+                Signal::Restore => self.dao.restore_house(id),
+                _ => self.dao.purge_house(id),
+            };
+            if let Err(error) = result {
+                show_message(
+                    &self.window,
+                    "Error",
+                    &format!("No se pudo modificar la papelera\n{error}"),
+                );
+                break;
+            }
+            self.changed |= signal == Signal::Restore;
+        }
+        self.fill_deleted();
+    }
+
+    pub fn retention(&self) -> Option<u64> {
+        self.retention.value().trim().parse().ok()
+    }
+
+    pub fn run(&mut self) -> bool {
+        self.fill_deleted();
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            if let Some(signal @ (Signal::Restore | Signal::Delete | Signal::Empty)) =
+                self.receiver.recv()
+            {
+                self.apply(signal);
+            }
+        }
+        self.changed
+    }
+}
+
+pub struct BulkDialog<'a> {
+    window: DoubleWindow,
+    dao: &'a mut dyn DAO,
+    houses: Vec<House>,
+    kinds: Vec<String>,
+    tags: Vec<String>,
+    kind: Widget,
+    postcode: IntInput,
+    tag: Widget,
+    delete: CheckButton,
+    browser: HoldBrowser,
+    operation: Option<Operation>, // for the history, once the changes are applied
+    receiver: Receiver<Signal>,
+}
+
+impl<'a> BulkDialog<'a> {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, dao: &'a mut dyn DAO, houses: Vec<House>) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 3 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Editar selección");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(&format!(
+            "Los cambios se aplican a las {} viviendas seleccionadas en una sola operación\n\
+             Si alguno no se puede aplicar no se aplica ninguno",
+            houses.len()
+        ));
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut kind;
+        let mut postcode;
+        let mut tag;
+        let mut delete;
+        {
+            let mut row = Flex::default().row();
+            let label = Frame::default().with_label("Tipo");
+            row.set_size(&label, 2 * BUTTON_WIDTH);
+            kind = Choice::default();
+            kind.set_callback(move |_| sender.send(Signal::Preview));
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+        {
+            let mut row = Flex::default().row();
+            let label = Frame::default().with_label("Sumar al código postal");
+            row.set_size(&label, 2 * BUTTON_WIDTH);
+            postcode = IntInput::default();
+            postcode.set_tooltip("Cantidad que se suma al código postal, negativa para restar");
+            postcode.set_trigger(CallbackTrigger::Changed);
+            postcode.set_callback(move |_| sender.send(Signal::Preview));
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+        {
+            let mut row = Flex::default().row();
+            let label = Frame::default().with_label("Agregar característica");
+            row.set_size(&label, 2 * BUTTON_WIDTH);
+            tag = Choice::default();
+            tag.set_callback(move |_| sender.send(Signal::Preview));
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+        {
+            let mut row = Flex::default().row();
+            let label = Frame::default();
+            row.set_size(&label, 2 * BUTTON_WIDTH);
+            delete = CheckButton::default().with_label("Enviar a la papelera");
+            delete.set_callback(move |_| sender.send(Signal::Preview));
+            row.end();
+            main.set_size(&row, BUTTON_HEIGHT);
+        }
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[220]); // address and what happens to the house
+
+        {
+            let buttons = Flex::default().row();
+            create_button(&window, sender, "Cancelar", Signal::Cancel);
+            Frame::default();
+            create_button(&window, sender, "Aplicar", Signal::Save);
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            dao,
+            houses,
+            kinds: Vec::new(),
+            tags: Vec::new(),
+            kind: Widget::Choice(kind),
+            postcode,
+            tag: Widget::Choice(tag),
+            delete,
+            browser,
+            operation: None,
+            receiver,
+        }
+    }
+
+    // The first item of each choice leaves the houses as they are
+    fn fill_choices(&mut self) {
+        match self.dao.get_house_types() {
+            Ok(kinds) => {
+                self.kinds = kinds
+                    .into_iter()
+                    .filter(|kind| !kind.retired)
+                    .map(|kind| kind.name)
+                    .collect()
+            }
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se pudieron leer los tipos\n{error}"),
+            ),
+        }
+        match self.dao.get_tags() {
+            Ok(tags) => self.tags = tags.into_iter().map(|tag| tag.name).collect(),
+            Err(error) => {
+                let message = format!("No se pudieron leer las características\n{error}");
+                show_message(&self.window, "Error", &message);
+            }
+        }
+        self.kind.add("«Sin cambios»");
+        for kind in &self.kinds {
+            self.kind.add(kind);
+        }
+        self.kind.set(0);
+        self.tag.add("«Ninguna»");
+        for tag in &self.tags {
+            self.tag.add(tag);
+        }
+        self.tag.set(0);
+    }
+
+    // `None` when it is sent to the trash
+    fn changed(&self, house: &House) -> Result<Option<House>, Error> {
+        if self.delete.is_checked() {
+            return Ok(None);
+        }
+        let mut changed = house.clone();
+        let index = |choice: &Widget| choice.get().parse::<usize>().unwrap_or(0).checked_sub(1);
+        if let Some(kind) = index(&self.kind).and_then(|index| self.kinds.get(index)) {
+            changed.kind.clone_from(kind);
+        }
+        let shift = match self.postcode.value().trim() {
+            "" => 0,
+            value => value.parse::<i32>().map_err(|_| {
+                Error::Validation(format!(
+                    "«{value}» no es una cantidad para el código postal"
+                ))
+            })?,
+        };
+        changed.postcode = house.postcode.checked_add(shift).ok_or_else(|| {
+            Error::Validation(format!(
+                "el código postal {} más {shift} es demasiado grande",
+                house.postcode
+            ))
+        })?;
+        if let Some(tag) = index(&self.tag).and_then(|index| self.tags.get(index)) {
+            if !changed.tags.iter().any(|t| Tag::same(t, tag)) {
+                changed.tags.push(tag.clone());
+                changed.tags.sort();
+            }
+        }
+        changed.validate()?;
+        Ok(Some(changed))
+    }
+
+    // The houses that stay the same are left out, fails with the first one that cannot take the changes.
+    // Each change comes with the operation that the history records once the batch is applied
+    fn changes(&self) -> Result<Vec<(Change, Operation)>, (&House, Error)> {
+        let mut changes = Vec::new();
+        for house in &self.houses {
+            match self.changed(house) {
+                Ok(None) => {
+                    changes.push((
+                        Change::Delete(house.clone()),
+                        Operation::Delete(house.clone()),
+                    ));
+                }
+                Ok(Some(changed)) if AuditEntry::diff(Some(house), Some(&changed)).is_empty() => {}
+                Ok(Some(changed)) => {
+                    // Both houses with the version that the update writes, like the form records them
+                    let version = house.version + 1;
+                    let operation = Operation::Update {
+                        before: Box::new(House {
+                            version,
+                            ..house.clone()
+                        }),
+                        after: Box::new(House {
+                            version,
+                            ..changed.clone()
+                        }),
+                    };
+                    changes.push((Change::Update(changed), operation));
+                }
+                Err(error) => return Err((house, error)),
+            }
+        }
+        Ok(changes)
+    }
+
+    fn preview(&mut self) {
+        self.browser.clear();
+        self.browser.add("@bVivienda\t@bCambios");
+        for house in &self.houses {
+            let effect = match self.changed(house) {
+                Err(error) => format!("@C1{error}"), // in red
+                Ok(None) => "Se envía a la papelera".to_owned(),
+                Ok(Some(changed)) => {
+                    let changes = AuditEntry::diff(Some(house), Some(&changed));
+                    if changes.is_empty() {
+                        "Sin cambios".to_owned()
+                    } else {
+                        changes
+                            .iter()
+                            .map(|change| change.to_string())
+                            .collect::<Vec<String>>()
+                            .join("; ")
+                    }
+                }
+            };
+            self.browser
+                .add(&format!("{} al {}\t{effect}", house.street, house.number));
+        }
+    }
+
+    fn apply(&mut self) {
+        let (changes, operations): (Vec<Change>, Vec<Operation>) = match self.changes() {
+            Ok(changes) => changes.into_iter().unzip(),
+            Err((house, error)) => {
+                let message = format!(
+                    "No se puede modificar {} al {}\n{error}",
+                    house.street, house.number
+                );
+                show_message(&self.window, "Error", &message);
+                return;
+            }
+        };
+        if changes.is_empty() {
+            show_message(&self.window, "Editar selección", "Ninguna vivienda cambia");
+            return;
+        }
+        let message = format!(
+            "Se modificarán {} viviendas\n¿Desea continuar?",
+            changes.len()
+        );
+        if !confirm(&self.window, "Editar selección", &message, "Aplicar") {
+            return;
+        }
+        match self.dao.apply_batch(&changes) {
+            Ok(_) => {
+                self.operation = Some(Operation::Batch(operations));
+                self.window.hide();
+            }
+            Err(Error::Batch(index, error)) => {
+                let house = match &changes[index] {
+                    Change::Create(house)
+                    | Change::Update(house)
+                    | Change::Delete(house)
+                    | Change::Restore(house) => house,
+                };
+                let message = format!(
+                    "No se aplicó ningún cambio, falló el de {} al {}\n{error}",
+                    house.street, house.number
+                );
+                show_message(&self.window, "Error", &message);
+            }
+            Err(error) => show_message(
+                &self.window,
+                "Error",
+                &format!("No se aplicó ningún cambio\n{error}"),
+            ),
+        }
+    }
+
+    // The operation for the history, `None` when nothing was applied
+    pub fn run(&mut self) -> Option<Operation> {
+        self.fill_choices();
+        self.preview();
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Preview) => self.preview(),
+                Some(Signal::Save) => self.apply(),
+                _ => {}
+            }
+        }
+        self.operation.take()
+    }
+}
+
+fn default_width(field: &str) -> i32 {
+    match field {
+        "street" | "owners" | "tags" => 200,
+        "kind" | "operation" | "city" | "province" | "country" => 120,
+        _ => 90,
+    }
+}
+
+// Numbers by their value and texts regardless of case and accents, an empty text goes first
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => normalize(a).cmp(&normalize(b)),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Grid {
+    titles: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+pub struct TableDialog {
+    window: DoubleWindow,
+    table: TableRow,
+    checks: CheckBrowser,
+    fields: Vec<&'static str>,     // "id" and the names of House::fields()
+    rows: Vec<(i32, Vec<String>)>, // id and the text of each field
+    columns: Vec<Column>,
+    sort: Option<String>,
+    descending: bool,
+    grid: Rc<RefCell<Grid>>,
+    receiver: Receiver<Signal>,
+}
+
+impl TableDialog {
+    // `total` counts the houses of the filter, `houses` can be only the first ones
+    pub fn new(
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        houses: &[House],
+        total: i64,
+        layout: &TableLayout,
+    ) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = if (houses.len() as i64) < total {
+            let label = format!(
+                "Tabla de viviendas, las primeras {} de {total}",
+                houses.len()
+            );
+            let mut title = Frame::default().with_label(&label);
+            title.set_tooltip("Filtre las viviendas para ver las demás");
+            title
+        } else {
+            Frame::default().with_label("Tabla de viviendas")
+        };
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(
+            "Un clic en el título de una columna ordena por ella, arrastrar su borde cambia el ancho\n\
+             Doble clic en una vivienda la muestra en la ventana principal",
+        );
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let grid = Rc::new(RefCell::new(Grid::default()));
+        let mut checks;
+        let mut table;
+        {
+            let mut row = Flex::default().row();
+
+            checks = CheckBrowser::default();
+            checks.set_tooltip("Columnas visibles");
+            checks.set_callback(move |_| sender.send(Signal::Columns));
+            row.set_size(&checks, 2 * BUTTON_WIDTH);
+
+            table = TableRow::default();
+            table.set_type(TableRowSelectMode::Single);
+            table.set_col_header(true);
+            table.set_row_header(false);
+            table.set_col_resize(true);
+            table.set_col_resize_min(20);
+            table.set_row_height_all(BUTTON_HEIGHT * 2 / 3);
+            table.set_col_header_height(BUTTON_HEIGHT * 3 / 4);
+            table.end();
+            table.draw_cell({
+                let grid = Rc::clone(&grid);
+                move |t, context, row, col, x, y, w, h| {
+                    let grid = grid.borrow();
+                    match context {
+                        TableContext::StartPage => {
+                            draw::set_font(Font::Helvetica, app::font_size())
+                        }
+                        TableContext::ColHeader => {
+                            let text = grid.titles.get(col as usize).map_or("", String::as_str);
+                            draw::push_clip(x, y, w, h);
+                            draw::draw_box(FrameType::ThinUpBox, x, y, w, h, Color::FrameDefault);
+                            draw::set_draw_color(Color::Foreground);
+                            draw::draw_text2(text, x + 4, y, w - 8, h, Align::Left);
+                            draw::pop_clip();
+                        }
+                        TableContext::Cell => {
+                            let text = grid
+                                .rows
+                                .get(row as usize)
+                                .and_then(|r| r.get(col as usize));
+                            let text = text.map_or("", String::as_str);
+                            let background = if t.row_selected(row) {
+                                t.selection_color()
+                            } else {
+                                Color::Background2
+                            };
+                            // The numbers are aligned to the right, like in a spreadsheet
+                            let align = if text.parse::<f64>().is_ok() {
+                                Align::Right
+                            } else {
+                                Align::Left
+                            };
+                            draw::push_clip(x, y, w, h);
+                            draw::draw_rect_fill(x, y, w, h, background);
+                            draw::set_draw_color(Color::contrast(Color::Foreground, background));
+                            draw::draw_text2(text, x + 4, y, w - 8, h, align);
+                            draw::set_draw_color(Color::Light2);
+                            draw::draw_rect(x, y, w, h);
+                            draw::pop_clip();
+                        }
+                        _ => {}
+                    }
+                }
+            });
+            table.set_callback(move |t| match t.callback_context() {
+                // The release ends the resizing of a column too, only a click on a title sorts
+                TableContext::ColHeader
+                    if app::event() == Event::Released && app::event_is_click() =>
+                {
+                    sender.send(Signal::Sort(t.callback_col()))
+                }
+                TableContext::Cell if app::event_clicks() => sender.send(Signal::Pick),
+                _ => {}
+            });
+
+            row.end();
+        }
+
+        {
+            let buttons = Flex::default().row();
+            create_button(&window, sender, "Cerrar", Signal::Cancel);
+            Frame::default();
+            create_button(&window, sender, "Ir a la vivienda", Signal::Pick);
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                sender.send(Signal::Cancel);
+                w.hide();
+                true
+            }
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        let fields: Vec<&'static str> = iter::once("id")
+            .chain(
+                House::default()
+                    .fields()
+                    .into_iter()
+                    .map(|(field, _)| field),
+            )
+            .collect();
+        let rows = houses
+            .iter()
+            .map(|house| {
+                let cells = iter::once(house.id.to_string())
+                    .chain(house.fields().into_iter().map(|(_, text)| text));
+                (house.id, cells.collect())
+            })
+            .collect();
+        // The columns that the configuration does not know go at the end, the unknown ones are dropped
+        let mut columns: Vec<Column> = layout
+            .columns
+            .iter()
+            .filter(|c| fields.contains(&c.field.as_str()))
+            .cloned()
+            .collect();
+        for field in &fields {
+            if !columns.iter().any(|c| c.field == *field) {
+                columns.push(Column {
+                    field: (*field).to_owned(),
+                    width: default_width(field),
+                    hidden: false,
+                });
+            }
+        }
+
+        Self {
+            window,
+            table,
+            checks,
+            fields,
+            rows,
+            columns,
+            sort: layout.sort.clone(),
+            descending: layout.descending,
+            grid,
+            receiver,
+        }
+    }
+
+    fn visible(&self) -> Vec<&Column> {
+        self.columns.iter().filter(|c| !c.hidden).collect()
+    }
+
+    // The widths dragged by the user are kept before the visible columns change
+    fn keep_widths(&mut self) {
+        let widths: Vec<i32> = (0..self.table.cols())
+            .map(|col| self.table.col_width(col))
+            .collect();
+        for (column, width) in self.columns.iter_mut().filter(|c| !c.hidden).zip(widths) {
+            column.width = width;
+        }
+    }
+
+    fn sort_rows(&mut self) {
+        let Some(index) = self
+            .sort
+            .as_ref()
+            .and_then(|sort| self.fields.iter().position(|f| f == sort))
+        else {
+            return;
+        };
+        // Stable, the rows with the same value keep the order of the DAO
+        self.rows
+            .sort_by(|a, b| compare_cells(&a.1[index], &b.1[index]));
+        if self.descending {
+            self.rows.reverse();
+        }
+    }
+
+    fn fill_grid(&mut self) {
+        let visible = self.visible();
+        let indexes: Vec<usize> = visible
+            .iter()
+            .map(|column| {
+                self.fields
+                    .iter()
+                    .position(|f| *f == column.field)
+                    .unwrap_or(0)
+            })
+            .collect();
+        let titles = visible
+            .iter()
+            .map(|column| {
+                let label = House::field_label(&column.field);
+                match self.sort.as_ref() {
+                    Some(sort) if *sort == column.field && self.descending => format!("{label} ▼"),
+                    Some(sort) if *sort == column.field => format!("{label} ▲"),
+                    _ => label.to_owned(),
+                }
+            })
+            .collect();
+        let rows = self
+            .rows
+            .iter()
+            .map(|(_, cells)| indexes.iter().map(|&index| cells[index].clone()).collect())
+            .collect();
+        let widths: Vec<i32> = visible.iter().map(|column| column.width).collect();
+        *self.grid.borrow_mut() = Grid { titles, rows };
+        self.table.set_rows(self.rows.len() as i32);
+        self.table.set_cols(widths.len() as i32);
+        for (index, width) in widths.into_iter().enumerate() {
+            self.table.set_col_width(index as i32, width);
+        }
+        self.table.redraw();
+    }
+
+    fn fill_checks(&mut self) {
+        self.checks.clear();
+        for column in &self.columns {
+            self.checks
+                .add(House::field_label(&column.field), !column.hidden);
+        }
+    }
+
+    fn change_columns(&mut self) {
+        self.keep_widths();
+        let checked: Vec<bool> = (1..=self.checks.nitems() as i32)
+            .map(|index| self.checks.checked(index))
+            .collect();
+        if !checked.contains(&true) {
+            self.fill_checks();
+            return;
+        }
+        for (column, checked) in self.columns.iter_mut().zip(checked) {
+            column.hidden = !checked;
+        }
+        self.fill_grid();
+    }
+
+    fn change_sort(&mut self, col: i32) {
+        let Some(field) = self
+            .visible()
+            .get(col as usize)
+            .map(|column| column.field.clone())
+        else {
+            return;
+        };
+        self.keep_widths();
+        if self.sort.as_ref() == Some(&field) {
+            self.descending = !self.descending;
+        } else {
+            self.sort = Some(field);
+            self.descending = false;
+        }
+        self.table.select_all_rows(TableRowSelectFlag::Deselect);
+        self.sort_rows();
+        self.fill_grid();
+    }
+
+    fn selected(&mut self) -> Option<i32> {
+        let row = (0..self.rows.len() as i32).find(|&row| self.table.row_selected(row))?;
+        self.rows.get(row as usize).map(|(id, _)| *id)
+    }
+
+    pub fn layout(&mut self) -> TableLayout {
+        self.keep_widths();
+        TableLayout {
+            sort: self.sort.clone(),
+            descending: self.descending,
+            columns: self.columns.clone(),
+        }
+    }
+
+    pub fn run(&mut self) -> Option<i32> {
+        self.sort_rows();
+        self.fill_checks();
+        self.fill_grid();
+        self.window.show();
+        let mut chosen = None;
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Columns) => self.change_columns(),
+                Some(Signal::Sort(col)) => self.change_sort(col),
+                Some(Signal::Pick) => match self.selected() {
+                    Some(id) => {
+                        chosen = Some(id);
+                        self.window.hide();
+                    }
+                    None => show_message(&self.window, "Tabla de viviendas", "Elija una vivienda"),
+                },
+                _ => {}
+            }
+        }
+        chosen
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreetChoice {
+    Use(usize), // index of the candidate
+    Keep,
+    Cancel,
+}
+
+pub struct StreetsDialog {
+    window: DoubleWindow,
+    browser: HoldBrowser,
+    receiver: Receiver<Signal>,
+}
+
+impl StreetsDialog {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, street: &str, candidates: &[Candidate]) -> Self {
+        let w_min = 5 * MARGIN_SIZE + 4 * BUTTON_WIDTH;
+        debug_assert!(w > w_min, "width({w}) > {w_min}");
+
+        let (sender, receiver) = channel::<Signal>();
+
+        let mut window = DoubleWindow::new(x, y, w, h, None);
+        window.set_frame(FrameType::BorderBox);
+        window.set_border(false);
+        window.make_modal(true);
+        window.begin();
+
+        let mut main = Flex::default_fill().column();
+        main.set_margin(2 * MARGIN_SIZE);
+
+        let mut title = Frame::default().with_label("Calle dudosa");
+        title.set_label_font(Font::HelveticaBold);
+        title.set_label_size(24);
+        main.set_size(&title, BUTTON_HEIGHT);
+
+        let mut explanation = Frame::default().with_label(&format!(
+            "«{street}» no figura tal cual en el callejero, estas son las calles más parecidas\n\
+             La elegida corrige la calle y completa el código postal, la ciudad y las coordenadas que falten"
+        ));
+        explanation.set_label_size(12);
+        main.set_size(&explanation, BUTTON_HEIGHT);
+
+        let mut browser = HoldBrowser::default();
+        browser.set_column_widths(&[100, 260]); // score, street and place
+        browser.add("@bCoincidencia\t@bCalle\t@bC.P., ciudad y provincia");
+        for candidate in candidates {
+            let score = format!("{:.0} %", 100.0 * candidate.score);
+            browser.add(&format!(
+                "{score}\t{}\t{}",
+                candidate.street.name,
+                candidate.street.place()
+            ));
+        }
+        browser.select(2);
+
+        {
+            let buttons = Flex::default().row();
+            create_button(&window, sender, "Cancelar", Signal::Cancel);
+            Frame::default();
+            create_button(&window, sender, "Dejar como está", Signal::Save);
+            create_button(&window, sender, "Usar la elegida", Signal::Pick);
+            buttons.end();
+            main.set_size(&buttons, BUTTON_HEIGHT);
+        }
+
+        main.end();
+
+        window.end();
+
+        window.handle(move |w, ev| match ev {
+            Event::KeyDown => match app::event_key() {
+                Key::Enter | Key::KPEnter => {
+                    sender.send(Signal::Pick);
+                    true
+                }
+                Key::Escape => {
+                    sender.send(Signal::Cancel);
+                    w.hide();
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        });
+
+        window.set_callback(move |w| {
+            if app::event() == Event::Close {
+                sender.send(Signal::Cancel);
+                w.hide();
+            }
+        });
+
+        Self {
+            window,
+            browser,
+            receiver,
+        }
+    }
+
+    pub fn run(&mut self) -> StreetChoice {
+        self.window.show();
+        let mut choice = StreetChoice::Cancel;
+        while self.window.shown() {
+            app::wait();
+            match self.receiver.recv() {
+                Some(Signal::Save) => {
+                    choice = StreetChoice::Keep;
+                    self.window.hide();
+                }
+                // The first line is the header
+                Some(Signal::Pick) if self.browser.value() > 1 => {
+                    choice = StreetChoice::Use(self.browser.value() as usize - 2);
+                    self.window.hide();
+                }
+                Some(Signal::Pick) => {
+                    show_message(&self.window, "Calle dudosa", "Elija una de las calles")
+                }
+                _ => {}
+            }
+        }
+        choice
+    }
+}
+
 pub struct MDButton {
     text: String,
     value: i32,
@@ -0,0 +1,289 @@
+use crate::base::{Change, Error, House, DAO};
+
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Create(House),
+    Update {
+        before: Box<House>,
+        after: Box<House>,
+    },
+    Delete(House),
+    // Applied together with DAO::apply_batch(), like the bulk edit that recorded them
+    Batch(Vec<Operation>),
+}
+
+// The changes that revert the operations, the last one first
+fn reverting(operations: &[Operation], changes: &mut Vec<Change>) {
+    for operation in operations.iter().rev() {
+        match operation {
+            Operation::Create(house) => changes.push(Change::Delete(house.clone())),
+            Operation::Update { before, .. } => changes.push(Change::Update(*before.clone())),
+            Operation::Delete(house) => changes.push(Change::Restore(house.clone())),
+            Operation::Batch(operations) => reverting(operations, changes),
+        }
+    }
+}
+
+fn repeating(operations: &[Operation], changes: &mut Vec<Change>) {
+    for operation in operations {
+        match operation {
+            Operation::Create(house) => changes.push(Change::Restore(house.clone())),
+            Operation::Update { after, .. } => changes.push(Change::Update(*after.clone())),
+            Operation::Delete(house) => changes.push(Change::Delete(house.clone())),
+            Operation::Batch(operations) => repeating(operations, changes),
+        }
+    }
+}
+
+// Each update of the batch wrote one version more
+fn written(operations: &mut [Operation]) {
+    for operation in operations {
+        match operation {
+            Operation::Update { before, after } => {
+                before.version += 1;
+                after.version += 1;
+            }
+            Operation::Batch(operations) => written(operations),
+            Operation::Create(_) | Operation::Delete(_) => {}
+        }
+    }
+}
+
+// A deleted house is restored from the trash, so it keeps its id, its prices and its attachments.
+// Both houses of an update keep the version it wrote, so reverting it only fails when someone
+// else changed the house since
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+impl History {
+    pub fn record(&mut self, operation: Operation) {
+        self.undo.push(operation);
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, dao: &mut dyn DAO) -> Result<Option<i32>, Error> {
+        let Some(mut operation) = self.undo.pop() else {
+            return Ok(None);
+        };
+        let result = match &mut operation {
+            Operation::Create(house) => dao.delete_house(house).map(|_| None),
+            Operation::Update { before, after } => dao.update_house(before).map(|version| {
+                before.version = version;
+                after.version = version;
+                Some(before.id)
+            }),
+            Operation::Delete(house) => dao.restore_house(house.id).map(|_| Some(house.id)),
+            Operation::Batch(operations) => {
+                let mut changes = Vec::new();
+                reverting(operations, &mut changes);
+                dao.apply_batch(&changes).map(|_| {
+                    written(operations);
+                    None
+                })
+            }
+        };
+        match result {
+            Ok(id) => {
+                self.redo.push(operation);
+                Ok(id)
+            }
+            Err(error) => {
+                self.undo.push(operation);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn redo(&mut self, dao: &mut dyn DAO) -> Result<Option<i32>, Error> {
+        let Some(mut operation) = self.redo.pop() else {
+            return Ok(None);
+        };
+        let result = match &mut operation {
+            Operation::Create(house) => dao.restore_house(house.id).map(|_| Some(house.id)),
+            Operation::Update { before, after } => dao.update_house(after).map(|version| {
+                before.version = version;
+                after.version = version;
+                Some(after.id)
+            }),
+            Operation::Delete(house) => dao.delete_house(house).map(|_| None),
+            Operation::Batch(operations) => {
+                let mut changes = Vec::new();
+                repeating(operations, &mut changes);
+                dao.apply_batch(&changes).map(|_| {
+                    written(operations);
+                    None
+                })
+            }
+        };
+        match result {
+            Ok(id) => {
+                self.undo.push(operation);
+                Ok(id)
+            }
+            Err(error) => {
+                self.redo.push(operation);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Memory;
+
+    fn create(dao: &mut Memory, history: &mut History) -> House {
+        let house = House {
+            street: "Calle Nueva".to_owned(),
+            ..dao.get_house(1).unwrap()
+        };
+        let house = dao.create_house(&house).unwrap();
+        history.record(Operation::Create(house.clone()));
+        house
+    }
+
+    // As the form records it, both houses with the version that the update wrote
+    fn update(dao: &mut Memory, history: &mut History, id: i32) -> (House, House) {
+        let mut before = dao.get_house(id).unwrap();
+        let mut after = House {
+            rooms: before.rooms + 1,
+            ..before.clone()
+        };
+        let version = dao.update_house(&after).unwrap();
+        before.version = version;
+        after.version = version;
+        history.record(Operation::Update {
+            before: Box::new(before.clone()),
+            after: Box::new(after.clone()),
+        });
+        (before, after)
+    }
+
+    #[test]
+    fn created_houses_are_undone_and_redone() {
+        let mut dao = Memory::demo("test");
+        let mut history = History::default();
+        let house = create(&mut dao, &mut history);
+        assert_eq!(history.undo(&mut dao).unwrap(), None);
+        assert!(matches!(dao.get_house(house.id), Err(Error::NotFound)));
+        assert!(!history.can_undo() && history.can_redo());
+        assert_eq!(history.redo(&mut dao).unwrap(), Some(house.id));
+        assert_eq!(dao.get_house(house.id).unwrap().street, "Calle Nueva");
+        assert!(history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn updates_follow_the_version_they_write() {
+        let mut dao = Memory::demo("test");
+        let mut history = History::default();
+        let (before, after) = update(&mut dao, &mut history, 1);
+        assert_eq!(history.undo(&mut dao).unwrap(), Some(1));
+        let stored = dao.get_house(1).unwrap();
+        assert_eq!(
+            (stored.rooms, stored.version),
+            (before.rooms, before.version + 1)
+        );
+        assert_eq!(history.redo(&mut dao).unwrap(), Some(1));
+        let stored = dao.get_house(1).unwrap();
+        assert_eq!(
+            (stored.rooms, stored.version),
+            (after.rooms, after.version + 2)
+        );
+        // Each step keeps the version it wrote, so the next one is not a conflict
+        assert_eq!(history.undo(&mut dao).unwrap(), Some(1));
+        assert_eq!(dao.get_house(1).unwrap().rooms, before.rooms);
+    }
+
+    #[test]
+    fn deleted_houses_are_undone_and_redone() {
+        let mut dao = Memory::demo("test");
+        let mut history = History::default();
+        let house = dao.get_house(1).unwrap();
+        dao.delete_house(&house).unwrap();
+        history.record(Operation::Delete(house));
+        assert_eq!(history.undo(&mut dao).unwrap(), Some(1));
+        assert!(dao.get_deleted_houses().unwrap().is_empty());
+        assert_eq!(history.redo(&mut dao).unwrap(), None);
+        assert!(matches!(dao.get_house(1), Err(Error::NotFound)));
+        assert_eq!(history.undo(&mut dao).unwrap(), Some(1));
+        assert!(dao.get_house(1).is_ok());
+    }
+
+    #[test]
+    fn batches_are_undone_and_redone_together() {
+        let mut dao = Memory::demo("test");
+        let mut history = History::default();
+        // The demo has an active lease in the second house, not in the first one
+        let first = dao.get_house(2).unwrap();
+        let second = dao.get_house(1).unwrap();
+        let changed = House {
+            rooms: first.rooms + 1,
+            ..first.clone()
+        };
+        dao.apply_batch(&[
+            Change::Update(changed.clone()),
+            Change::Delete(second.clone()),
+        ])
+        .unwrap();
+        // As the bulk edit records it
+        let version = first.version + 1;
+        history.record(Operation::Batch(vec![
+            Operation::Update {
+                before: Box::new(House {
+                    version,
+                    ..first.clone()
+                }),
+                after: Box::new(House { version, ..changed }),
+            },
+            Operation::Delete(second.clone()),
+        ]));
+        assert_eq!(history.undo(&mut dao).unwrap(), None);
+        assert_eq!(dao.get_house(2).unwrap().rooms, first.rooms);
+        assert!(dao.get_house(1).is_ok());
+        assert_eq!(history.redo(&mut dao).unwrap(), None);
+        assert_eq!(dao.get_house(2).unwrap().rooms, first.rooms + 1);
+        assert!(matches!(dao.get_house(1), Err(Error::NotFound)));
+        // When one change fails none is applied and the batch stays to be undone
+        dao.restore_house(1).unwrap();
+        assert!(matches!(history.undo(&mut dao), Err(Error::Batch(0, _))));
+        assert_eq!(dao.get_house(2).unwrap().rooms, first.rooms + 1);
+        dao.delete_house(&second).unwrap();
+        assert_eq!(history.undo(&mut dao).unwrap(), None);
+        assert_eq!(dao.get_house(2).unwrap().rooms, first.rooms);
+    }
+
+    #[test]
+    fn failed_undo_keeps_the_operation() {
+        let mut dao = Memory::demo("test");
+        let mut history = History::default();
+        update(&mut dao, &mut history, 1);
+        // Someone else changes the house after the update
+        let theirs = House {
+            baths: 4,
+            ..dao.get_house(1).unwrap()
+        };
+        dao.update_house(&theirs).unwrap();
+        assert!(matches!(history.undo(&mut dao), Err(Error::Conflict)));
+        assert!(history.can_undo() && !history.can_redo());
+        assert_eq!(dao.get_house(1).unwrap().baths, 4);
+
+        let house = create(&mut dao, &mut history);
+        assert_eq!(history.undo(&mut dao).unwrap(), None);
+        // Purged from the trash, it cannot come back
+        dao.purge_house(house.id).unwrap();
+        assert!(matches!(history.redo(&mut dao), Err(Error::NotFound)));
+        assert!(history.can_redo());
+    }
+}
@@ -1,13 +1,29 @@
 use std::fmt::Display;
 
 use fltk::{
-    browser::HoldBrowser,
+    browser::{CheckBrowser, HoldBrowser, MultiBrowser},
     enums::Color,
     input::{FloatInput, Input, IntInput},
     menu::Choice,
     prelude::*,
 };
 
+// Escapes what FLTK interprets in the menu labels, the text of the items is never read back
+pub fn menu_label(text: &str) -> String {
+    let mut label = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '/' | '_' => {
+                label.push('\\');
+                label.push(c);
+            }
+            '&' => label.push_str("&&"),
+            _ => label.push(c),
+        }
+    }
+    label
+}
+
 // https://users.rust-lang.org/t/how-to-create-a-vector-with-different-types-of-gui-widgets
 
 #[derive(Debug)]
@@ -17,6 +33,8 @@ pub enum Widget {
     FInput(FloatInput),
     Choice(Choice),       // start 0
     Browser(HoldBrowser), // start 1
+    Multi(MultiBrowser),  // start 1, the last clicked item
+    Checks(CheckBrowser), // start 1, the checked items separated by commas
 }
 
 impl Widget {
@@ -27,6 +45,12 @@ impl Widget {
             Self::FInput(w) => w.value(),
             Self::Choice(w) => w.value().to_string(),
             Self::Browser(w) => w.value().to_string(),
+            Self::Multi(w) => w.value().to_string(),
+            Self::Checks(w) => (1..=w.nitems() as i32)
+                .filter(|&index| w.checked(index))
+                .map(|index| index.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
         }
     }
 
@@ -48,6 +72,28 @@ impl Widget {
                     w.select(index);
                 }
             }
+            Self::Multi(w) => {
+                // select() adds to the selection, only refilling the browser clears it
+                let position = w.position();
+                let lines: Vec<String> = (1..=w.size()).filter_map(|line| w.text(line)).collect();
+                w.clear();
+                for line in &lines {
+                    w.add(line);
+                }
+                w.set_position(position);
+                let index: i32 = value.parse().unwrap_or(-1);
+                if index == i32::MAX {
+                    w.select(w.size());
+                } else {
+                    w.select(index);
+                }
+            }
+            Self::Checks(w) => {
+                w.check_none();
+                for index in value.split(',').filter_map(|index| index.parse().ok()) {
+                    w.set_checked(index);
+                }
+            }
         }
         self
     }
@@ -58,7 +104,7 @@ impl Widget {
             Self::IInput(w) => w.set_color(color),
             Self::FInput(w) => w.set_color(color),
             Self::Choice(w) => w.set_color(color),
-            Self::Browser(_) => {}
+            Self::Browser(_) | Self::Multi(_) | Self::Checks(_) => {}
         }
     }
 
@@ -66,6 +112,8 @@ impl Widget {
         match self {
             Self::Choice(w) => w.clear(),
             Self::Browser(w) => w.clear(),
+            Self::Multi(w) => w.clear(),
+            Self::Checks(w) => w.clear(),
             _ => panic!("unsupported operation"),
         }
         self
@@ -74,11 +122,18 @@ impl Widget {
     pub fn add(&mut self, value: &str) -> &Self {
         match self {
             Self::Choice(w) => {
-                w.add_choice(value);
+                // add_choice() splits the items at '|'
+                w.add_choice(&menu_label(value).replace('|', "¦"));
             }
             Self::Browser(w) => {
                 w.add(value);
             }
+            Self::Multi(w) => {
+                w.add(value);
+            }
+            Self::Checks(w) => {
+                w.add(value, false);
+            }
             _ => panic!("unsupported operation"),
         }
         self
@@ -90,6 +145,9 @@ impl Widget {
             Self::Browser(w) => {
                 w.remove(index);
             }
+            Self::Multi(w) => {
+                w.remove(index);
+            }
             _ => panic!("unsupported operation"),
         }
         self
@@ -100,6 +158,7 @@ impl Widget {
         match self {
             Self::Choice(w) => w.text(index).unwrap(),
             Self::Browser(w) => w.text(index).unwrap_or("".to_owned()), // can be deselected
+            Self::Multi(w) => w.text(index).unwrap_or("".to_owned()),
             _ => panic!("unsupported operation"),
         }
     }
@@ -107,6 +166,18 @@ impl Widget {
     pub fn get_size(&self) -> String {
         match self {
             Self::Browser(w) => w.size().to_string(),
+            Self::Multi(w) => w.size().to_string(),
+            _ => panic!("unsupported operation"),
+        }
+    }
+
+    // The clicked one is the last
+    pub fn get_selected(&self) -> Vec<String> {
+        match self {
+            Self::Multi(w) => (1..=w.size())
+                .filter(|&index| w.selected(index))
+                .map(|index| index.to_string())
+                .collect(),
             _ => panic!("unsupported operation"),
         }
     }
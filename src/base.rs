@@ -1,14 +1,321 @@
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::{self, Display},
+    ops,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use chrono::{Months, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
 use strsim::{jaro_winkler, sorensen_dice};
 use unidecode::unidecode;
 
-pub struct Error;
+#[derive(Debug)]
+pub enum Error {
+    NotFound,
+    Open(String),
+    Constraint(String),
+    Locked(String),
+    Connection(String),
+    Migration(String),
+    Validation(String),
+    Database(String),
+    File(String),
+    Conflict, // the house was changed or deleted by someone else since it was read
+    Batch(usize, Box<Error>), // index of the change that failed, none was applied
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "El registro no existe en la base de datos"),
+            Self::Open(cause) => write!(f, "No se pudo abrir la base de datos: {cause}"),
+            Self::Constraint(cause) => {
+                write!(f, "Se violó una restricción de la base de datos: {cause}")
+            }
+            Self::Locked(cause) => write!(
+                f,
+                "La base de datos está bloqueada por otro proceso: {cause}"
+            ),
+            Self::Connection(cause) => {
+                write!(f, "Se perdió la conexión con la base de datos: {cause}")
+            }
+            Self::Migration(cause) => write!(
+                f,
+                "No se pudo actualizar el esquema de la base de datos: {cause}"
+            ),
+            Self::Validation(cause) => write!(f, "Los datos no son válidos: {cause}"),
+            Self::Database(cause) => write!(f, "Error de la base de datos: {cause}"),
+            Self::File(cause) => write!(f, "No se pudo acceder al archivo adjunto: {cause}"),
+            Self::Conflict => write!(
+                f,
+                "Otro usuario modificó o borró la vivienda desde que se leyó"
+            ),
+            Self::Batch(index, error) => write!(
+                f,
+                "No se aplicó ningún cambio, falló el cambio {}: {error}",
+                index + 1
+            ),
+        }
+    }
+}
 
-pub const HOUSE_TYPES: &[&str] = &["Casa", "Loft", "Chalet", "Dúplex", "Apartamento"];
+impl std::error::Error for Error {}
 
-#[derive(Debug, Default)]
+// A retired kind stays for the houses that use it, it is not offered for new ones
+#[derive(Debug, Clone, Serialize)]
+pub struct HouseType {
+    pub id: i32,
+    pub name: String,
+    pub retired: bool,
+}
+
+impl HouseType {
+    pub fn validate_name(name: &str) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Validation(
+                "el nombre del tipo de vivienda no puede estar vacío".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn unknown(name: &str) -> Error {
+        Error::Validation(format!("tipo de vivienda desconocido «{name}»"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+impl Tag {
+    pub fn validate_name(name: &str) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Validation(
+                "el nombre de la característica no puede estar vacío".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn unknown(name: &str) -> Error {
+        Error::Validation(format!("característica desconocida «{name}»"))
+    }
+
+    pub fn in_use(count: i64) -> Error {
+        Error::Constraint(format!("la característica figura en {count} viviendas"))
+    }
+
+    // Regardless of case and accents, as it is typed in the filters and the files
+    pub fn same(a: &str, b: &str) -> bool {
+        normalize(a.trim()) == normalize(b.trim())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Owner {
+    #[serde(default)]
+    pub id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub phone: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub tax_id: String,
+    #[serde(default)]
+    pub notes: String,
+}
+
+fn validate_contact(who: &str, name: &str, email: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::Validation(format!(
+            "el nombre del {who} no puede estar vacío"
+        )));
+    }
+    if !email.is_empty() && !email.contains('@') {
+        return Err(Error::Validation(format!(
+            "«{email}» no es un correo electrónico"
+        )));
+    }
+    Ok(())
+}
+
+impl Owner {
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_contact("propietario", &self.name, &self.email)
+    }
+
+    pub fn in_use(count: i64) -> Error {
+        Error::Constraint(format!("el propietario figura en {count} viviendas"))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tenant {
+    #[serde(default)]
+    pub id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub phone: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub tax_id: String,
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl Tenant {
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_contact("inquilino", &self.name, &self.email)
+    }
+
+    pub fn in_use(count: i64) -> Error {
+        Error::Constraint(format!("el inquilino figura en {count} contratos"))
+    }
+}
+
+// The name is filled by the DAO
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ownership {
+    pub owner_id: i32,
+    #[serde(default)]
+    pub name: String,
+    pub share: f32, // percentage
+}
+
+impl Ownership {
+    pub fn validate(owners: &[Ownership]) -> Result<(), Error> {
+        for (index, ownership) in owners.iter().enumerate() {
+            if !(ownership.share > 0.0 && ownership.share <= 100.0) {
+                return Err(Error::Validation(
+                    "el porcentaje de cada propietario debe ser mayor a 0 y hasta 100".to_owned(),
+                ));
+            }
+            if owners[..index]
+                .iter()
+                .any(|o| o.owner_id == ownership.owner_id)
+            {
+                return Err(Error::Validation(format!(
+                    "«{}» ya figura entre los propietarios",
+                    ownership.name
+                )));
+            }
+        }
+        if owners.iter().map(|o| o.share).sum::<f32>() > 100.01 {
+            return Err(Error::Validation(
+                "los porcentajes de los propietarios suman más de 100".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub const CURRENCY: &str = "ARS";
+
+fn default_currency() -> String {
+    CURRENCY.to_owned()
+}
+
+fn validate_currency(currency: &str) -> Result<(), Error> {
+    if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(Error::Validation(format!(
+            "«{currency}» no es un código de moneda, como ARS o USD"
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    #[default]
+    Sale,
+    Rent,
+    Both,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Sale, Operation::Rent, Operation::Both];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Sale => "sale",
+            Self::Rent => "rent",
+            Self::Both => "both",
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Sale => write!(f, "Venta"),
+            Self::Rent => write!(f, "Alquiler"),
+            Self::Both => write!(f, "Venta y alquiler"),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match unidecode(s.trim()).to_lowercase().as_str() {
+            "sale" | "venta" => Ok(Self::Sale),
+            "rent" | "alquiler" => Ok(Self::Rent),
+            "both" | "ambas" | "venta y alquiler" => Ok(Self::Both),
+            _ => Err(Error::Validation(format!("operación desconocida «{s}»"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceChange {
+    pub price: f64,
+    pub currency: String,
+    pub changed_at: NaiveDateTime,
+}
+
+fn validate_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> Result<(), Error> {
+    match (latitude, longitude) {
+        (None, None) => Ok(()),
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) {
+                return Err(Error::Validation(
+                    "la latitud debe estar entre -90 y 90 grados".to_owned(),
+                ));
+            }
+            if !(-180.0..=180.0).contains(&longitude) {
+                return Err(Error::Validation(
+                    "la longitud debe estar entre -180 y 180 grados".to_owned(),
+                ));
+            }
+            Ok(())
+        }
+        _ => Err(Error::Validation(
+            "se deben indicar ambas coordenadas o ninguna".to_owned(),
+        )),
+    }
+}
+
+const EARTH_RADIUS: f64 = 6371.0088;
+
+pub fn haversine(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (to.1 - from.1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct House {
+    #[serde(default)] // new listings written by other tools have no id yet
     pub id: i32,
     pub kind: String,
     pub street: String,
@@ -18,28 +325,592 @@ pub struct House {
     pub rooms: i32,
     pub baths: i32,
     pub area: f32,
+    #[serde(default)]
+    pub owners: Vec<Ownership>,
+    #[serde(default)]
+    pub operation: Operation,
+    #[serde(default)]
+    pub price: f64, // monthly when it is rented
+    #[serde(default = "default_currency")]
+    pub currency: String, // ISO 4217 code
+    #[serde(default)]
+    pub expenses: f64, // monthly
+    #[serde(default)]
+    pub tags: Vec<String>, // names, sorted
+    #[serde(default)]
+    pub city: String,
+    #[serde(default)]
+    pub province: String,
+    #[serde(default)]
+    pub country: String,
+    #[serde(default)]
+    pub latitude: Option<f64>, // decimal degrees, both coordinates or none
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub version: i32, // times it was saved, filled by the DAO
+}
+
+impl House {
+    // The kind is checked by the DAO, against its house types
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.street.trim().is_empty() {
+            return Err(Error::Validation(
+                "la calle no puede estar vacía".to_owned(),
+            ));
+        }
+        let numbers = [
+            self.number,
+            self.floor,
+            self.postcode,
+            self.rooms,
+            self.baths,
+        ];
+        // NaN is neither negative nor positive, and the database cannot keep it
+        if !(self.area.is_finite() && self.price.is_finite() && self.expenses.is_finite()) {
+            return Err(Error::Validation(
+                "los valores numéricos deben ser números finitos".to_owned(),
+            ));
+        }
+        if numbers.iter().any(|&n| n < 0)
+            || self.area < 0.0
+            || self.price < 0.0
+            || self.expenses < 0.0
+        {
+            return Err(Error::Validation(
+                "los valores numéricos no pueden ser negativos".to_owned(),
+            ));
+        }
+        validate_currency(&self.currency)?;
+        validate_coordinates(self.latitude, self.longitude)?;
+        Ownership::validate(&self.owners)
+    }
+
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    // Every field but the id, as the audit log compares them
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let owners: Vec<String> = self
+            .owners
+            .iter()
+            .map(|o| format!("{} ({} %)", o.name, o.share))
+            .collect();
+        let coordinate =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        vec![
+            ("kind", self.kind.clone()),
+            ("street", self.street.clone()),
+            ("number", self.number.to_string()),
+            ("floor", self.floor.to_string()),
+            ("postcode", self.postcode.to_string()),
+            ("rooms", self.rooms.to_string()),
+            ("baths", self.baths.to_string()),
+            ("area", self.area.to_string()),
+            ("owners", owners.join(", ")),
+            ("operation", self.operation.to_string()),
+            ("price", self.price.to_string()),
+            ("currency", self.currency.clone()),
+            ("expenses", self.expenses.to_string()),
+            ("tags", self.tags.join(", ")),
+            ("city", self.city.clone()),
+            ("province", self.province.clone()),
+            ("country", self.country.clone()),
+            ("latitude", coordinate(self.latitude)),
+            ("longitude", coordinate(self.longitude)),
+        ]
+    }
+
+    pub fn field_label(field: &str) -> &str {
+        match field {
+            "id" => "Registro",
+            "kind" => "Tipo",
+            "street" => "Calle",
+            "number" => "Número",
+            "floor" => "Piso",
+            "postcode" => "Código postal",
+            "rooms" => "Habitaciones",
+            "baths" => "Baños",
+            "area" => "Superficie",
+            "owners" => "Propietarios",
+            "operation" => "Operación",
+            "price" => "Precio",
+            "currency" => "Moneda",
+            "expenses" => "Expensas",
+            "tags" => "Características",
+            "city" => "Ciudad",
+            "province" => "Provincia",
+            "country" => "País",
+            "latitude" => "Latitud",
+            "longitude" => "Longitud",
+            _ => field,
+        }
+    }
+
+    pub fn price_text(&self) -> String {
+        match self.operation {
+            _ if self.price <= 0.0 => String::new(),
+            Operation::Rent => format!("{} {}/mes", self.currency, self.price),
+            _ => format!("{} {}", self.currency, self.price),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+}
+
+impl AuditAction {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Purge => "purge",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Create => write!(f, "Alta"),
+            Self::Update => write!(f, "Modificación"),
+            Self::Delete => write!(f, "Baja"),
+            Self::Restore => write!(f, "Recuperación"),
+            Self::Purge => write!(f, "Eliminación"),
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "create" | "alta" => Ok(Self::Create),
+            "update" | "modificación" | "modificacion" => Ok(Self::Update),
+            "delete" | "baja" => Ok(Self::Delete),
+            "restore" | "recuperación" | "recuperacion" => Ok(Self::Restore),
+            "purge" | "eliminación" | "eliminacion" => Ok(Self::Purge),
+            _ => Err(Error::Validation(format!("operación desconocida «{s}»"))),
+        }
+    }
+}
+
+// Empty text when the house did not exist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String, // name in House::fields()
+    pub before: String,
+    pub after: String,
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = House::field_label(&self.field);
+        match (self.before.is_empty(), self.after.is_empty()) {
+            (true, _) => write!(f, "{label}: «{}»", self.after),
+            (false, true) => write!(f, "{label}: «{}» (quitado)", self.before),
+            (false, false) => write!(f, "{label}: «{}» → «{}»", self.before, self.after),
+        }
+    }
+}
+
+// Only appended to, the DAO writes one with each operation on a house
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub house_id: i32,
+    pub action: AuditAction,
+    pub changes: Vec<FieldChange>,
+    pub user: String,
+    pub changed_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+    // `None` is the house that does not exist
+    pub fn diff(before: Option<&House>, after: Option<&House>) -> Vec<FieldChange> {
+        let before = before.map(House::fields).unwrap_or_default();
+        let after = after.map(House::fields).unwrap_or_default();
+        let mut changes = Vec::new();
+        for index in 0..before.len().max(after.len()) {
+            let (field, old) = before
+                .get(index)
+                .map_or(("", ""), |(field, value)| (*field, value.as_str()));
+            let (field, new) = after
+                .get(index)
+                .map_or((field, ""), |(field, value)| (*field, value.as_str()));
+            if old != new {
+                changes.push(FieldChange {
+                    field: field.to_owned(),
+                    before: old.to_owned(),
+                    after: new.to_owned(),
+                });
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub house_id: Option<i32>,
+    pub user: Option<String>, // exact name
+    pub since: Option<NaiveDate>,
+    pub limit: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn house(house_id: i32) -> Self {
+        AuditQuery {
+            house_id: Some(house_id),
+            ..AuditQuery::default()
+        }
+    }
+
+    pub fn valid(&self, entry: &AuditEntry) -> bool {
+        self.house_id.map_or(true, |id| entry.house_id == id)
+            && self.user.as_ref().map_or(true, |user| entry.user == *user)
+            && self
+                .since
+                .map_or(true, |since| entry.changed_at.date() >= since)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Change {
+    Create(House),
+    Update(House),
+    Delete(House),
+    Restore(House), // from the trash, only the id is used
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Deleted {
+    pub house: House,
+    pub deleted_at: NaiveDateTime,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseStatus {
+    #[default]
+    Active,
+    Ended,
+    Cancelled,
+}
+
+impl LeaseStatus {
+    pub const ALL: [LeaseStatus; 3] = [
+        LeaseStatus::Active,
+        LeaseStatus::Ended,
+        LeaseStatus::Cancelled,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Ended => "ended",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl Display for LeaseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Active => write!(f, "Vigente"),
+            Self::Ended => write!(f, "Finalizado"),
+            Self::Cancelled => write!(f, "Rescindido"),
+        }
+    }
+}
+
+impl FromStr for LeaseStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.code() == s)
+            .ok_or_else(|| Error::Validation(format!("estado de contrato desconocido «{s}»")))
+    }
+}
+
+// The rent grows `index_rate` percent every `index_months` months, 0 months is a fixed rent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lease {
+    pub id: i32,
+    pub house_id: i32,
+    pub tenant_id: i32,
+    pub tenant: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate, // last day
+    pub rent: f64,      // monthly, at the start
+    pub currency: String,
+    pub deposit: f64,
+    pub index_months: i32,
+    pub index_rate: f64, // percentage
+    pub status: LeaseStatus,
+}
+
+impl Lease {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.end <= self.start {
+            return Err(Error::Validation(
+                "el contrato debe terminar después de comenzar".to_owned(),
+            ));
+        }
+        if !(self.rent.is_finite() && self.deposit.is_finite() && self.index_rate.is_finite()) {
+            return Err(Error::Validation(
+                "los valores numéricos deben ser números finitos".to_owned(),
+            ));
+        }
+        if self.rent <= 0.0 {
+            return Err(Error::Validation(
+                "el alquiler debe ser mayor a 0".to_owned(),
+            ));
+        }
+        if self.deposit < 0.0 || self.index_months < 0 || self.index_rate < 0.0 {
+            return Err(Error::Validation(
+                "los valores numéricos no pueden ser negativos".to_owned(),
+            ));
+        }
+        validate_currency(&self.currency)
+    }
+
+    pub fn already_active() -> Error {
+        Error::Constraint("la vivienda ya tiene un contrato vigente".to_owned())
+    }
+
+    pub fn still_active() -> Error {
+        Error::Constraint("la vivienda tiene un contrato vigente".to_owned())
+    }
+
+    // The leases and their payments are kept, they are the history of the tenants
+    pub fn in_house(count: i64) -> Error {
+        Error::Constraint(format!(
+            "la vivienda tiene {count} contratos de alquiler y no se puede eliminar definitivamente"
+        ))
+    }
+
+    // Negative when it already ended
+    pub fn days_left(&self, today: NaiveDate) -> i64 {
+        (self.end - today).num_days()
+    }
+
+    pub fn rent_at(&self, date: NaiveDate) -> f64 {
+        if self.index_months <= 0 || date < self.start {
+            return self.rent;
+        }
+        // Counted from the start each time, so the 31st does not drift to the 28th
+        let months = self.index_months as u32;
+        let mut updates = 0;
+        while let Some(next) = self
+            .start
+            .checked_add_months(Months::new((updates + 1) * months))
+        {
+            if next > date {
+                break;
+            }
+            updates += 1;
+        }
+        self.rent * (1.0 + self.index_rate / 100.0).powi(updates as i32)
+    }
+
+    pub fn with_payments(count: i64) -> Error {
+        Error::Constraint(format!("el contrato tiene {count} pagos registrados"))
+    }
+
+    // Due on the day of the start of each month the lease lasts
+    pub fn charges(&self, until: NaiveDate) -> Vec<(NaiveDate, f64)> {
+        let last = until.min(self.end);
+        let mut charges = Vec::new();
+        // Counted from the start each time, like the updates of the rent
+        while let Some(due) = self
+            .start
+            .checked_add_months(Months::new(charges.len() as u32))
+        {
+            if due > last {
+                break;
+            }
+            charges.push((due, self.rent_at(due)));
+        }
+        charges
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentMethod {
+    Cash,
+    #[default]
+    Transfer,
+    Check,
+    Card,
+    Other,
+}
+
+impl PaymentMethod {
+    pub const ALL: [PaymentMethod; 5] = [
+        PaymentMethod::Cash,
+        PaymentMethod::Transfer,
+        PaymentMethod::Check,
+        PaymentMethod::Card,
+        PaymentMethod::Other,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Cash => "cash",
+            Self::Transfer => "transfer",
+            Self::Check => "check",
+            Self::Card => "card",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl Display for PaymentMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cash => write!(f, "Efectivo"),
+            Self::Transfer => write!(f, "Transferencia"),
+            Self::Check => write!(f, "Cheque"),
+            Self::Card => write!(f, "Tarjeta"),
+            Self::Other => write!(f, "Otro"),
+        }
+    }
+}
+
+impl FromStr for PaymentMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = unidecode(s.trim()).to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|method| {
+                method.code() == name || unidecode(&method.to_string()).to_lowercase() == name
+            })
+            .ok_or_else(|| Error::Validation(format!("medio de pago desconocido «{s}»")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Payment {
+    pub id: i32,
+    pub lease_id: i32,
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub method: PaymentMethod,
+    pub notes: String,
+}
+
+impl Payment {
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.amount.is_finite() || self.amount <= 0.0 {
+            return Err(Error::Validation("el pago debe ser mayor a 0".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "bmp", "svg"];
+
+// The content is kept in the store under its hash
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attachment {
+    pub id: i32,
+    pub house_id: i32,
+    pub hash: String,
+    pub name: String,
+    pub size: i64, // bytes
+    pub cover: bool,
+    pub added_at: NaiveDateTime,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        Path::new(&self.name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| {
+                IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            })
+    }
+
+    pub fn duplicate(name: &str) -> Error {
+        Error::Constraint(format!("la vivienda ya tiene un adjunto igual a «{name}»"))
+    }
+}
+
+pub fn normalize(text: &str) -> String {
+    unidecode(&text.to_lowercase())
+}
+
+pub const SIMILAR: f64 = 0.7;
+
+// Between 0 and 1 for normalized texts, the shared bigrams weigh more than the common prefix
+pub fn similarity(typed: &str, text: &str) -> f64 {
+    (jaro_winkler(typed, text) + 1.5 * sorensen_dice(typed, text)) / 2.5
 }
 
 pub trait Apply {
     fn apply<U: Display>(&self, value: &U) -> bool;
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Text {
     pub text: String,
 }
 
 impl Apply for Text {
     fn apply<U: Display>(&self, value: &U) -> bool {
-        let text1 = unidecode(&value.to_string().to_lowercase());
-        let text2 = unidecode(&self.text.to_owned().to_lowercase());
-        let jw = jaro_winkler(&text2, &text1);
-        let sd = sorensen_dice(&text2, &text1);
-        text2 == "" || text1.contains(&text2) || (jw + 1.5 * sd) / 2.5 > 0.7
+        let text1 = normalize(&value.to_string());
+        let text2 = normalize(&self.text);
+        text2 == "" || text1.contains(&text2) || similarity(&text2, &text1) > SIMILAR
     }
 }
 
-#[derive(Debug)]
+impl Text {
+    // Bytes of the value that match, all of them when it is only similar
+    pub fn position(&self, value: &str) -> Option<ops::Range<usize>> {
+        let text = normalize(&self.text);
+        // The value is normalized char by char to map the match back to its bytes
+        let mut normalized = String::new();
+        let mut starts = Vec::new();
+        for (index, c) in value.char_indices() {
+            starts.push((normalized.len(), index));
+            normalized.push_str(&normalize(c.encode_utf8(&mut [0; 4])));
+        }
+        starts.push((normalized.len(), value.len()));
+        match normalized.find(&text) {
+            Some(start) => {
+                let end = start + text.len();
+                let first = starts.iter().rev().find(|(n, _)| *n <= start)?.1;
+                let last = starts.iter().find(|(n, _)| *n >= end)?.1;
+                Some(first..last)
+            }
+            None if self.apply(&value) => Some(0..value.len()),
+            None => None,
+        }
+    }
+}
+
+// A missing bound is the default one, {"min": 2} has no maximum
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, bound(deserialize = "T: Deserialize<'de>, Range<T>: Default"))]
 pub struct Range<T: PartialOrd + FromStr> {
     pub min: T,
     pub max: T,
@@ -59,6 +930,7 @@ macro_rules! range_default_impl {
 }
 range_default_impl!(i32, i32::MAX);
 range_default_impl!(f32, f32::MAX);
+range_default_impl!(f64, f64::MAX);
 
 impl<T: PartialOrd + FromStr> Apply for Range<T> {
     fn apply<U: Display>(&self, value: &U) -> bool {
@@ -69,34 +941,466 @@ impl<T: PartialOrd + FromStr> Apply for Range<T> {
     }
 }
 
-#[derive(Debug, Default)]
+// The houses without coordinates only fall inside an unlimited circle, after the others
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Near {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default = "unlimited")]
+    pub radius: f64, // km
+}
+
+fn unlimited() -> f64 {
+    f64::MAX
+}
+
+impl Near {
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_coordinates(Some(self.latitude), Some(self.longitude))?;
+        if self.radius.is_nan() || self.radius < 0.0 {
+            return Err(Error::Validation(
+                "el radio no puede ser negativo".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.radius < f64::MAX
+    }
+
+    pub fn distance(&self, house: &House) -> Option<f64> {
+        house
+            .coordinates()
+            .map(|point| haversine((self.latitude, self.longitude), point))
+    }
+
+    pub fn contains(&self, house: &House) -> bool {
+        match self.distance(house) {
+            Some(distance) => distance <= self.radius,
+            None => !self.is_limited(),
+        }
+    }
+
+    // Box of (latitude, longitude) ranges around the circle, so that the database can discard
+    // most of the houses; the longitude is not bounded near the poles or the 180th meridian
+    pub fn bounding_box(&self) -> Option<(Range<f64>, Option<Range<f64>>)> {
+        if !self.is_limited() {
+            return None;
+        }
+        let dlat = (self.radius / EARTH_RADIUS).to_degrees();
+        let latitude = Range {
+            min: self.latitude - dlat,
+            max: self.latitude + dlat,
+        };
+        if latitude.min <= -90.0 || latitude.max >= 90.0 {
+            return Some((latitude, None));
+        }
+        let dlon = (self.radius / (EARTH_RADIUS * self.latitude.to_radians().cos())).to_degrees();
+        let longitude = Range {
+            min: self.longitude - dlon,
+            max: self.longitude + dlon,
+        };
+        if longitude.min < -180.0 || longitude.max > 180.0 {
+            return Some((latitude, None));
+        }
+        Some((latitude, Some(longitude)))
+    }
+}
+
+// Missing fields impose no restriction, so other tools can write partial filters
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Filter {
     pub kind: Text,
     pub street: Text,
+    pub owner: Text,
     pub number: Range<i32>,
     pub floor: Range<i32>,
     pub postcode: Range<i32>,
     pub rooms: Range<i32>,
     pub baths: Range<i32>,
     pub area: Range<f32>,
+    pub price: Range<f64>,     // in the currency of each house
+    pub all_tags: Vec<String>, // the house has every one
+    pub any_tags: Vec<String>, // the house has at least one, when not empty
+    pub near: Option<Near>,
+    pub search: Text, // any of the street, the kind or the postcode, as typed above the list
 }
 
 impl Filter {
+    // The text conditions are fuzzy and the distances need trigonometry, they can only be
+    // evaluated in Rust; the ranges can be delegated to the database
+    pub fn in_rust(&self) -> bool {
+        !self.kind.text.is_empty()
+            || !self.street.text.is_empty()
+            || !self.owner.text.is_empty()
+            || !self.search.text.is_empty()
+            || self.near.is_some()
+    }
+
+    // The street first; any field matches an empty search
+    pub fn search_match(&self, house: &House) -> Option<&'static str> {
+        if self.search.apply(&house.street) {
+            Some("street")
+        } else if self.search.apply(&house.kind) {
+            Some("kind")
+        } else if self.search.apply(&house.postcode) {
+            Some("postcode")
+        } else {
+            None
+        }
+    }
+
+    pub fn sort(&self, houses: &mut [House]) {
+        if let Some(near) = &self.near {
+            let distance = |house: &House| near.distance(house).unwrap_or(f64::INFINITY);
+            houses.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        }
+    }
+
     pub fn valid(&self, house: &House) -> bool {
+        // Any of the owners can match, a house without owners only matches an empty text
+        let owner =
+            self.owner.text.is_empty() || house.owners.iter().any(|o| self.owner.apply(&o.name));
+        let has = |tag: &String| house.tags.iter().any(|t| Tag::same(t, tag));
+        let tags = self.all_tags.iter().all(has)
+            && (self.any_tags.is_empty() || self.any_tags.iter().any(has));
         self.kind.apply(&house.kind)
             && self.street.apply(&house.street)
+            && owner
             && self.number.apply(&house.number)
             && self.floor.apply(&house.floor)
             && self.postcode.apply(&house.postcode)
             && self.rooms.apply(&house.rooms)
             && self.baths.apply(&house.baths)
             && self.area.apply(&house.area)
+            && self.price.apply(&house.price)
+            && tags
+            && self.near.as_ref().map_or(true, |near| near.contains(house))
+            && self.search_match(house).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub offset: i64,
+    pub limit: Option<i64>, // None for every house after the offset
+}
+
+impl Page {
+    pub fn all() -> Self {
+        Page {
+            offset: 0,
+            limit: None,
+        }
     }
+
+    // For the houses filtered in Rust, the SQL ones are cut by the database
+    pub fn cut<T>(&self, items: Vec<T>) -> Vec<T> {
+        let items = items.into_iter().skip(self.offset as usize);
+        match self.limit {
+            Some(limit) => items.take(limit as usize).collect(),
+            None => items.collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct HousePage {
+    pub houses: Vec<House>,
+    pub total: i64, // number of houses that match the filter, across all pages
 }
 
 pub trait DAO {
-    fn get_houses(&mut self) -> Result<Vec<House>, Error>;
+    fn get_houses(&mut self, filter: &Filter, page: Page) -> Result<HousePage, Error>;
+    fn get_house(&mut self, id: i32) -> Result<House, Error>;
     fn create_house(&mut self, house: &House) -> Result<House, Error>;
-    fn update_house(&mut self, house: &House) -> Result<bool, Error>;
-    fn delete_house(&mut self, id: i32) -> Result<bool, Error>;
+    // Error::Conflict when the house changed since it was read
+    fn update_house(&mut self, house: &House) -> Result<i32, Error>;
+    fn delete_house(&mut self, house: &House) -> Result<bool, Error>;
+    // All the changes or none, the id of the house of each one
+    fn apply_batch(&mut self, changes: &[Change]) -> Result<Vec<i32>, Error>;
+    fn get_deleted_houses(&mut self) -> Result<Vec<Deleted>, Error>;
+    fn restore_house(&mut self, id: i32) -> Result<bool, Error>;
+    fn purge_house(&mut self, id: i32) -> Result<bool, Error>;
+    fn purge_deleted(&mut self, before: NaiveDateTime) -> Result<usize, Error>;
+    fn get_price_history(&mut self, id: i32) -> Result<Vec<PriceChange>, Error>;
+    fn get_audit_log(&mut self, query: &AuditQuery) -> Result<Vec<AuditEntry>, Error>;
+    fn get_leases(&mut self, house_id: i32) -> Result<Vec<Lease>, Error>;
+    fn get_expiring_leases(&mut self, until: NaiveDate) -> Result<Vec<Lease>, Error>;
+    fn create_lease(&mut self, lease: &Lease) -> Result<Lease, Error>;
+    fn update_lease(&mut self, lease: &Lease) -> Result<bool, Error>;
+    fn delete_lease(&mut self, id: i32) -> Result<bool, Error>;
+    fn get_active_leases(&mut self) -> Result<Vec<Lease>, Error>;
+    fn get_tenant_leases(&mut self, tenant_id: i32) -> Result<Vec<Lease>, Error>;
+    fn get_payments(&mut self, lease_id: i32) -> Result<Vec<Payment>, Error>;
+    fn create_payment(&mut self, payment: &Payment) -> Result<Payment, Error>;
+    fn delete_payment(&mut self, id: i32) -> Result<bool, Error>;
+    fn get_attachments(&mut self, house_id: i32) -> Result<Vec<Attachment>, Error>;
+    fn add_attachment(&mut self, house_id: i32, path: &Path) -> Result<Attachment, Error>;
+    fn remove_attachment(&mut self, id: i32) -> Result<bool, Error>;
+    fn set_cover(&mut self, id: i32) -> Result<bool, Error>;
+    fn attachment_path(&self, attachment: &Attachment) -> PathBuf;
+    fn get_house_types(&mut self) -> Result<Vec<HouseType>, Error>;
+    fn create_house_type(&mut self, name: &str) -> Result<HouseType, Error>;
+    fn rename_house_type(&mut self, id: i32, name: &str) -> Result<bool, Error>;
+    fn retire_house_type(&mut self, id: i32, retired: bool) -> Result<bool, Error>;
+    fn get_tags(&mut self) -> Result<Vec<Tag>, Error>;
+    fn create_tag(&mut self, name: &str) -> Result<Tag, Error>;
+    fn rename_tag(&mut self, id: i32, name: &str) -> Result<bool, Error>;
+    fn delete_tag(&mut self, id: i32) -> Result<bool, Error>;
+    fn get_owners(&mut self) -> Result<Vec<Owner>, Error>;
+    fn create_owner(&mut self, owner: &Owner) -> Result<Owner, Error>;
+    fn update_owner(&mut self, owner: &Owner) -> Result<bool, Error>;
+    fn delete_owner(&mut self, id: i32) -> Result<bool, Error>;
+    fn get_tenants(&mut self) -> Result<Vec<Tenant>, Error>;
+    fn create_tenant(&mut self, tenant: &Tenant) -> Result<Tenant, Error>;
+    fn update_tenant(&mut self, tenant: &Tenant) -> Result<bool, Error>;
+    fn delete_tenant(&mut self, id: i32) -> Result<bool, Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Text {
+        Text {
+            text: text.to_owned(),
+        }
+    }
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn house() -> House {
+        House {
+            kind: "Casa".to_owned(),
+            street: "Avenida Bustamante".to_owned(),
+            number: 1456,
+            postcode: 4321,
+            rooms: 3,
+            baths: 1,
+            area: 80.2,
+            currency: CURRENCY.to_owned(),
+            tags: vec!["Cochera".to_owned(), "Jardín".to_owned()],
+            latitude: Some(-34.6),
+            longitude: Some(-58.4),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ranges_include_their_bounds() {
+        let range = Range { min: 2, max: 4 };
+        assert!(range.apply(&2) && range.apply(&4));
+        assert!(!range.apply(&1) && !range.apply(&5));
+        assert!(!range.apply(&"dos"));
+        assert!(Range::<f32>::default().apply(&80.2));
+    }
+
+    #[test]
+    fn texts_match_regardless_of_case_and_accents() {
+        assert!(text("").apply(&"Mitre"));
+        assert!(text("PENA").apply(&"La Peña Sur"));
+        assert!(text("Bustamente").apply(&"Bustamante"));
+        assert!(!text("Mitre").apply(&"Avenida Bustamante"));
+    }
+
+    #[test]
+    fn position_of_the_text_in_the_value() {
+        assert_eq!(text("bust").position("Avenida Bustamante"), Some(8..12));
+        assert_eq!(text("pena").position("La Peña Sur"), Some(3..8));
+        assert_eq!(text("Bustamente").position("Bustamante"), Some(0..10));
+        assert_eq!(text("Mitre").position("La Peña Sur"), None);
+    }
+
+    #[test]
+    fn filter_checks_every_condition() {
+        let house = house();
+        assert!(Filter::default().valid(&house));
+        let filters = [
+            Filter {
+                street: text("Mitre"),
+                ..Default::default()
+            },
+            Filter {
+                owner: text("Pérez"), // the house has no owners
+                ..Default::default()
+            },
+            Filter {
+                rooms: Range { min: 4, max: 6 },
+                ..Default::default()
+            },
+            Filter {
+                all_tags: vec!["cochera".to_owned(), "Pileta".to_owned()],
+                ..Default::default()
+            },
+            Filter {
+                any_tags: vec!["Pileta".to_owned(), "Terraza".to_owned()],
+                ..Default::default()
+            },
+            Filter {
+                search: text("Córdoba"),
+                ..Default::default()
+            },
+        ];
+        for filter in &filters {
+            assert!(!filter.valid(&house), "{filter:?}");
+        }
+        let filter = Filter {
+            kind: text("casa"),
+            area: Range {
+                min: 80.2,
+                max: 80.2,
+            },
+            all_tags: vec!["jardin".to_owned()],
+            any_tags: vec!["Pileta".to_owned(), "Cochera".to_owned()],
+            search: text("4321"),
+            ..Default::default()
+        };
+        assert!(filter.valid(&house));
+        assert_eq!(filter.search_match(&house), Some("postcode"));
+    }
+
+    #[test]
+    fn near_keeps_the_houses_inside_the_circle() {
+        let near = Near {
+            latitude: -34.6,
+            longitude: -58.5,
+            radius: 10.0,
+        };
+        let mut house = house();
+        assert!(near.contains(&house)); // about 9 km to the east
+        house.longitude = Some(-58.3);
+        assert!(!near.contains(&house));
+        house.latitude = None;
+        house.longitude = None;
+        assert!(!near.contains(&house));
+        assert!(Near {
+            radius: f64::MAX,
+            ..near
+        }
+        .contains(&house));
+    }
+
+    #[test]
+    fn haversine_distances() {
+        let degree = EARTH_RADIUS * std::f64::consts::PI / 180.0;
+        assert!((haversine((10.0, 20.0), (11.0, 20.0)) - degree).abs() < 1e-9);
+        assert!((haversine((0.0, 179.5), (0.0, -179.5)) - degree).abs() < 1e-9);
+        assert_eq!(haversine((-34.6, -58.4), (-34.6, -58.4)), 0.0);
+        let (a, b) = ((-34.6037, -58.3816), (-31.4201, -64.1888));
+        assert_eq!(haversine(a, b), haversine(b, a));
+        assert!((haversine(a, b) - 646.0).abs() < 5.0, "{}", haversine(a, b));
+    }
+
+    #[test]
+    fn bounding_box_holds_the_circle() {
+        for (latitude, longitude) in [(-34.6, -58.4), (60.0, 10.0), (0.0, 0.0)] {
+            let near = Near {
+                latitude,
+                longitude,
+                radius: 50.0,
+            };
+            let (lat, lon) = near.bounding_box().unwrap();
+            let lon = lon.unwrap();
+            for i in -100..=100 {
+                for j in -100..=100 {
+                    let point = (latitude + i as f64 / 100.0, longitude + j as f64 / 50.0);
+                    if haversine((latitude, longitude), point) <= near.radius {
+                        assert!(
+                            lat.apply(&point.0) && lon.apply(&point.1),
+                            "{point:?} of {near:?}"
+                        );
+                    }
+                }
+            }
+        }
+        let near = |latitude, longitude, radius| Near {
+            latitude,
+            longitude,
+            radius,
+        };
+        assert!(near(-34.6, -58.4, f64::MAX).bounding_box().is_none());
+        assert!(near(89.9, 0.0, 50.0).bounding_box().unwrap().1.is_none());
+        assert!(near(0.0, 179.9, 50.0).bounding_box().unwrap().1.is_none());
+    }
+
+    fn lease() -> Lease {
+        Lease {
+            id: 1,
+            house_id: 1,
+            tenant_id: 1,
+            tenant: String::new(),
+            start: date("2024-01-31"),
+            end: date("2024-05-30"),
+            rent: 100.0,
+            currency: CURRENCY.to_owned(),
+            deposit: 0.0,
+            index_months: 2,
+            index_rate: 10.0,
+            status: LeaseStatus::Active,
+        }
+    }
+
+    #[test]
+    fn non_finite_numbers_are_invalid() {
+        assert!(house().validate().is_ok());
+        assert!(lease().validate().is_ok());
+        for number in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let houses = [
+                House {
+                    area: number as f32,
+                    ..house()
+                },
+                House {
+                    price: number,
+                    ..house()
+                },
+                House {
+                    expenses: number,
+                    ..house()
+                },
+            ];
+            for house in &houses {
+                assert!(house.validate().is_err(), "{house:?}");
+            }
+            let leases = [
+                Lease {
+                    rent: number,
+                    ..lease()
+                },
+                Lease {
+                    deposit: number,
+                    ..lease()
+                },
+                Lease {
+                    index_rate: number,
+                    ..lease()
+                },
+            ];
+            for lease in &leases {
+                assert!(lease.validate().is_err(), "{lease:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn lease_charges_with_updates() {
+        let lease = lease();
+        let charges = lease.charges(date("2025-01-01"));
+        let dates: Vec<NaiveDate> = charges.iter().map(|&(due, _)| due).collect();
+        // The 31st is kept in the months that have it
+        let expected = ["2024-01-31", "2024-02-29", "2024-03-31", "2024-04-30"];
+        assert_eq!(dates, expected.map(date));
+        let amounts: Vec<f64> = charges
+            .iter()
+            .map(|&(_, amount)| (amount * 100.0).round() / 100.0)
+            .collect();
+        assert_eq!(amounts, [100.0, 100.0, 110.0, 110.0]);
+        assert_eq!(lease.charges(date("2024-02-28")).len(), 1);
+        assert!(lease.charges(date("2024-01-30")).is_empty());
+    }
 }
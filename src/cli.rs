@@ -0,0 +1,1138 @@
+use crate::base::{
+    AuditQuery, Error, Filter, House, HousePage, Lease, Near, Page, Payment, PaymentMethod, Range,
+    Text, CURRENCY, DAO,
+};
+use crate::csv::{self, CsvError, Report};
+use crate::gazetteer::{Gazetteer, GazetteerError, Resolution};
+use crate::json::{self, JsonError};
+use crate::ledger;
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use chrono::{Days, Local};
+use serde::Serialize;
+
+pub const USAGE: &str = "\
+Uso: wohnen [--demo | --db ARCHIVO [--create]] [COMANDO]
+
+Sin COMANDO se abre la interfaz gráfica.
+
+Sin --db se usa la base de datos indicada en «database» del archivo de
+configuración ($XDG_CONFIG_HOME/wohnen/config.toml), o la última abierta, o
+la de DATABASE_URL (también desde un archivo .env), o si no hay ninguna se
+crea wohnen.sqlite en el directorio de datos del usuario. Con --db el archivo
+debe existir, salvo que se indique --create.
+
+Comandos:
+  list   [--offset N] [--limit N] [--json]
+  show   ID [--json]
+  add    --kind TIPO --street CALLE --number N --floor N --postcode N
+         --rooms N --baths N --area M2 [--operation OPERACIÓN]
+         [--price IMPORTE] [--currency MONEDA] [--expenses IMPORTE]
+         [--tags NOMBRES] [--city CIUDAD] [--province PROVINCIA]
+         [--country PAÍS] [--coords LAT,LON] [--json]
+  update ID [--kind TIPO] [--street CALLE] [--number N] [--floor N]
+         [--postcode N] [--rooms N] [--baths N] [--area M2]
+         [--operation OPERACIÓN] [--price IMPORTE] [--currency MONEDA]
+         [--expenses IMPORTE] [--tags NOMBRES] [--city CIUDAD]
+         [--province PROVINCIA] [--country PAÍS] [--coords LAT,LON] [--json]
+  delete ID
+  trash  [--json]
+  restore ID
+  purge  ID | --days N
+  prices ID [--json]
+  log    [ID] [--user NOMBRE] [--since AAAA-MM-DD] [--limit N] [--json]
+  search [--filter ARCHIVO] [--kind TEXTO] [--street TEXTO] [--owner TEXTO]
+         [--search TEXTO]
+         [--number MIN:MAX] [--floor MIN:MAX] [--postcode MIN:MAX]
+         [--rooms MIN:MAX] [--baths MIN:MAX] [--area MIN:MAX]
+         [--price MIN:MAX] [--tags NOMBRES] [--any-tags NOMBRES]
+         [--near LAT,LON [--radius KM]] [--save-filter ARCHIVO]
+         [--offset N] [--limit N] [--json]
+  export [ARCHIVO] [mismas opciones de filtrado que search] [--json]
+  import ARCHIVO
+  kinds  [--json]
+  tags   [--json]
+  owners [--json]
+  tenants [--json]
+  leases ID [--json]
+  expiring [--days N] [--json]
+  account ID [--json]
+  pay    ID --amount IMPORTE [--date AAAA-MM-DD] [--method MEDIO]
+         [--notes TEXTO] [--json]
+  overdue [--json]
+  attachments ID [--json]
+  attach ID ARCHIVO [--json]
+  streets CALLE [--postcode N] [--json]
+  help
+
+Los textos se buscan por similitud y los números dentro del rango indicado,
+si no se especifica un mínimo se asume 0 y si no se especifica un máximo se
+asumen todos, por ejemplo: wohnen search --street corrientes --rooms 2:
+
+La OPERACIÓN es venta, alquiler o ambas (venta por omisión); el precio de un
+alquiler es mensual, al igual que las expensas. La MONEDA es un código ISO 4217
+como ARS (por omisión) o USD, y el rango de --price se compara con el precio en
+la moneda de cada vivienda. El comando prices muestra los cambios de precio de
+una vivienda, el más reciente primero.
+
+El comando delete envía la vivienda a la papelera, donde conserva sus precios
+y adjuntos; trash muestra las viviendas de la papelera y restore devuelve una a
+la lista. El comando purge elimina definitivamente la vivienda ID de la
+papelera, o con --days las que están en ella desde hace más de N días (0 para
+vaciarla). Con «retention» en el archivo de configuración, las viviendas que
+llevan más de esos días en la papelera se eliminan al abrir la base de datos.
+Una vivienda con un contrato vigente no se puede borrar, y las que tienen
+contratos de alquiler quedan en la papelera para no perder sus pagos.
+
+El comando log muestra el historial de cambios, de la vivienda ID o de todas:
+cada alta, modificación y baja con la fecha, el usuario y los campos que
+cambiaron, lo más reciente primero. Con --user muestra solo los cambios de ese
+usuario y con --since los hechos desde esa fecha. El usuario es el indicado en
+«user» del archivo de configuración, o el del sistema. El historial no se puede
+modificar y se conserva aunque se borre la vivienda.
+
+Un filtro guardado con --save-filter se puede volver a usar con --filter, las
+opciones indicadas junto a --filter reemplazan a las del archivo.
+
+El comando owners muestra los propietarios con sus datos de contacto; los
+propietarios y su porcentaje de cada vivienda se editan desde la interfaz
+gráfica.
+
+El comando leases muestra los contratos de alquiler de una vivienda, y expiring
+los contratos vigentes que terminan dentro de N días (30 por omisión) o que ya
+terminaron; tenants muestra los inquilinos. Los contratos y los inquilinos se
+cargan desde la interfaz gráfica.
+
+El comando account muestra la cuenta corriente del inquilino ID: los cargos
+mensuales de sus contratos, que se calculan a partir del alquiler y sus
+actualizaciones, y los pagos, con el saldo de cada moneda. El comando pay
+registra un pago del contrato ID, en su moneda, con la fecha de hoy si no se
+indica otra; el MEDIO es efectivo, transferencia (por omisión), cheque, tarjeta
+u otro. El comando overdue muestra las viviendas con contratos vigentes que
+tienen cargos vencidos sin pagar, la deuda más antigua primero.
+
+El comando attachments muestra los archivos adjuntos de una vivienda, con la
+ruta donde se guardan, y attach agrega un archivo a la vivienda ID. Los
+adjuntos se copian a un directorio junto a la base de datos, con el nombre del
+archivo terminado en .files en lugar de .sqlite; la portada se elige y los
+adjuntos se quitan desde la interfaz gráfica.
+
+El comando kinds muestra los tipos de vivienda que admiten --kind, add y
+update; los tipos retirados solo se conservan para las viviendas que ya los
+usan. Los tipos se agregan, renombran y retiran desde la interfaz gráfica.
+
+El comando tags muestra las características (cochera, pileta, balcón…) que
+admiten --tags y --any-tags, separadas por comas. En add y update, --tags
+reemplaza las características de la vivienda; en search y export, --tags busca
+las viviendas que tienen todas las indicadas y --any-tags las que tienen alguna.
+Las mayúsculas y los acentos no se tienen en cuenta. Las características se
+agregan, renombran y borran desde la interfaz gráfica.
+
+Las coordenadas de --coords son la latitud y la longitud en grados decimales,
+negativas al sur y al oeste, por ejemplo --coords -34.6037,-58.3816; se cargan a
+mano o se importan, Wohnen no las busca en internet, y un --coords vacío las
+quita. Con --near, search y export ordenan las viviendas de la más cercana a la
+más lejana al punto LAT,LON, y search muestra la distancia en km; con --radius
+además dejan solo las que están a KM kilómetros o menos. Las viviendas sin
+coordenadas quedan al final, o se omiten si se indica un radio.
+
+El comando streets busca la CALLE en el callejero, un archivo CSV o una base de
+datos SQLite con las calles y sus códigos postales que se indica en «gazetteer»
+del archivo de configuración, y muestra las calles parecidas con su grado de
+coincidencia. Con un callejero, add y update corrigen la escritura de la calle
+cuando coincide con una sola del callejero, sin tener en cuenta las mayúsculas
+ni los acentos, y completan el código postal, la ciudad, la provincia, el país
+y las coordenadas que falten; si solo hay calles parecidas, la vivienda se
+guarda como se escribió y se muestran las candidatas.
+
+Los archivos CSV llevan una cabecera con las columnas id, kind, street, number,
+floor, postcode, rooms, baths, area, operation, price, currency, expenses,
+tags, city, province, country, latitude y longitude, con las características
+separadas por punto y coma (las diez últimas se pueden omitir al importar); los
+archivos terminados en .json usan el
+formato JSON versionado de Wohnen. Si se omite el ARCHIVO al exportar, se
+escribe en la salida estándar.
+";
+
+const HOUSE_FIELDS: &[&str] = &[
+    "kind", "street", "number", "floor", "postcode", "rooms", "baths", "area",
+];
+const PRICE_FIELDS: &[&str] = &["operation", "price", "currency", "expenses"];
+const TAG_FIELDS: &[&str] = &["tags"];
+const LOCATION_FIELDS: &[&str] = &["city", "province", "country", "coords"];
+const FILTER_FIELDS: &[&str] = &[
+    "filter",
+    "save-filter",
+    "owner",
+    "price",
+    "tags",
+    "any-tags",
+    "near",
+    "radius",
+    "search",
+];
+const PAGE_FIELDS: &[&str] = &["offset", "limit"];
+const EXPIRING_FIELDS: &[&str] = &["days"];
+const PAYMENT_FIELDS: &[&str] = &["amount", "date", "method", "notes"];
+const AUDIT_FIELDS: &[&str] = &["user", "since", "limit"];
+const PURGE_FIELDS: &[&str] = &["days"];
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Dao(Error),
+    Csv(CsvError),
+    Json(JsonError),
+    Gazetteer(GazetteerError),
+    Io(io::Error), // writing the output
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}\nUse «wohnen help» para ver los comandos"),
+            Self::Dao(error) => write!(f, "{error}"),
+            Self::Csv(error) => write!(f, "{error}"),
+            Self::Json(error) => write!(f, "{error}"),
+            Self::Gazetteer(error) => write!(f, "{error}"),
+            Self::Io(error) => write!(f, "No se pudo escribir la salida: {error}"),
+        }
+    }
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> Self {
+        CliError::Dao(error)
+    }
+}
+
+impl From<CsvError> for CliError {
+    fn from(error: CsvError) -> Self {
+        CliError::Csv(error)
+    }
+}
+
+impl From<JsonError> for CliError {
+    fn from(error: JsonError) -> Self {
+        CliError::Json(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Io(error)
+    }
+}
+
+impl From<GazetteerError> for CliError {
+    fn from(error: GazetteerError) -> Self {
+        CliError::Gazetteer(error)
+    }
+}
+
+fn usage<T>(message: String) -> Result<T, CliError> {
+    Err(CliError::Usage(message))
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    json: bool,
+}
+
+impl Args {
+    fn parse(args: &[String], allowed: &[&[&str]]) -> Result<Self, CliError> {
+        let mut parsed = Args::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some("json") => parsed.json = true,
+                Some(name) if allowed.iter().any(|names| names.contains(&name)) => {
+                    match iter.next() {
+                        Some(value) => {
+                            parsed.options.insert(name.to_owned(), value.to_owned());
+                        }
+                        None => return usage(format!("Falta el valor de --{name}")),
+                    }
+                }
+                Some(name) => return usage(format!("Opción desconocida --{name}")),
+                None => parsed.positional.push(arg.to_owned()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.options.get(name) {
+            Some(value) => match value.trim().parse::<T>() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => usage(format!("Valor inválido para --{name}: «{value}»")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Offsets and limits, unsigned so a negative one is rejected before the DAO casts it to usize
+    fn count(&self, name: &str) -> Result<Option<i64>, CliError> {
+        Ok(self.get::<u32>(name)?.map(i64::from))
+    }
+
+    fn id(&self) -> Result<i32, CliError> {
+        match self.positional.as_slice() {
+            [id] => id
+                .parse()
+                .or_else(|_| usage(format!("Número de registro inválido «{id}»"))),
+            _ => usage("Se esperaba un único número de registro".to_owned()),
+        }
+    }
+
+    fn no_positional(&self) -> Result<(), CliError> {
+        match self.positional.first() {
+            Some(arg) => usage(format!("Argumento inesperado «{arg}»")),
+            None => Ok(()),
+        }
+    }
+
+    fn page(&self) -> Result<Page, CliError> {
+        Ok(Page {
+            offset: self.count("offset")?.unwrap_or(0),
+            limit: self.count("limit")?,
+        })
+    }
+
+    fn text(&self, name: &str, text: Text) -> Text {
+        match self.options.get(name) {
+            Some(value) => Text {
+                text: value.to_owned(),
+            },
+            None => text,
+        }
+    }
+
+    fn names(&self, name: &str, names: Vec<String>) -> Vec<String> {
+        match self.options.get(name) {
+            Some(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => names,
+        }
+    }
+
+    // An empty value is no point
+    fn point(&self, name: &str) -> Result<Option<Option<(f64, f64)>>, CliError> {
+        let Some(value) = self.options.get(name) else {
+            return Ok(None);
+        };
+        if value.trim().is_empty() {
+            return Ok(Some(None));
+        }
+        let invalid = || {
+            CliError::Usage(format!(
+                "Coordenadas inválidas para --{name}: «{value}», se esperaba LAT,LON"
+            ))
+        };
+        let (latitude, longitude) = value.split_once(',').ok_or_else(invalid)?;
+        let latitude = latitude.trim().parse().map_err(|_| invalid())?;
+        let longitude = longitude.trim().parse().map_err(|_| invalid())?;
+        Ok(Some(Some((latitude, longitude))))
+    }
+
+    fn near(&self, mut near: Option<Near>) -> Result<Option<Near>, CliError> {
+        if let Some(point) = self.point("near")? {
+            near = point.map(|(latitude, longitude)| Near {
+                latitude,
+                longitude,
+                radius: f64::MAX,
+            });
+        }
+        if let Some(radius) = self.get("radius")? {
+            match near.as_mut() {
+                Some(near) => near.radius = radius,
+                None => return usage("La opción --radius requiere --near".to_owned()),
+            }
+        }
+        Ok(near)
+    }
+
+    fn range<T>(&self, name: &str, mut range: Range<T>) -> Result<Range<T>, CliError>
+    where
+        T: PartialOrd + FromStr,
+        Range<T>: Default,
+    {
+        if let Some(value) = self.options.get(name) {
+            range = Range::<T>::default();
+            let bounds = value
+                .split_once(':')
+                .map(|(min, max)| (min.trim(), max.trim()));
+            let invalid = || {
+                CliError::Usage(format!(
+                    "Rango inválido para --{name}: «{value}», se esperaba MIN:MAX"
+                ))
+            };
+            let (min, max) = bounds.ok_or_else(invalid)?;
+            if !min.is_empty() {
+                range.min = min.parse().map_err(|_| invalid())?;
+            }
+            if !max.is_empty() {
+                range.max = max.parse().map_err(|_| invalid())?;
+            }
+        }
+        Ok(range)
+    }
+
+    fn filter(&self) -> Result<Filter, CliError> {
+        let base = match self.options.get("filter") {
+            Some(path) => json::load_filter(Path::new(path))?,
+            None => Filter::default(),
+        };
+        let filter = Filter {
+            kind: self.text("kind", base.kind),
+            street: self.text("street", base.street),
+            owner: self.text("owner", base.owner),
+            number: self.range("number", base.number)?,
+            floor: self.range("floor", base.floor)?,
+            postcode: self.range("postcode", base.postcode)?,
+            rooms: self.range("rooms", base.rooms)?,
+            baths: self.range("baths", base.baths)?,
+            area: self.range("area", base.area)?,
+            price: self.range("price", base.price)?,
+            all_tags: self.names("tags", base.all_tags),
+            any_tags: self.names("any-tags", base.any_tags),
+            near: self.near(base.near)?,
+            search: self.text("search", base.search),
+        };
+        if let Some(path) = self.options.get("save-filter") {
+            json::save_filter(Path::new(path), &filter)?;
+        }
+        Ok(filter)
+    }
+
+    fn update_house(&self, house: &mut House) -> Result<(), CliError> {
+        if let Some(kind) = self.options.get("kind") {
+            house.kind = kind.to_owned();
+        }
+        if let Some(street) = self.options.get("street") {
+            house.street = street.to_owned();
+        }
+        house.number = self.get("number")?.unwrap_or(house.number);
+        house.floor = self.get("floor")?.unwrap_or(house.floor);
+        house.postcode = self.get("postcode")?.unwrap_or(house.postcode);
+        house.rooms = self.get("rooms")?.unwrap_or(house.rooms);
+        house.baths = self.get("baths")?.unwrap_or(house.baths);
+        house.area = self.get("area")?.unwrap_or(house.area);
+        house.operation = self.get("operation")?.unwrap_or(house.operation);
+        house.price = self.get("price")?.unwrap_or(house.price);
+        if let Some(currency) = self.options.get("currency") {
+            house.currency = currency.trim().to_uppercase();
+        }
+        house.expenses = self.get("expenses")?.unwrap_or(house.expenses);
+        house.tags = self.names("tags", std::mem::take(&mut house.tags));
+        let places = [
+            ("city", &mut house.city),
+            ("province", &mut house.province),
+            ("country", &mut house.country),
+        ];
+        for (name, field) in places {
+            if let Some(value) = self.options.get(name) {
+                *field = value.trim().to_owned();
+            }
+        }
+        if let Some(point) = self.point("coords")? {
+            house.latitude = point.map(|(latitude, _)| latitude);
+            house.longitude = point.map(|(_, longitude)| longitude);
+        }
+        Ok(())
+    }
+}
+
+fn print_json<T: Serialize>(value: &T, out: &mut dyn Write) -> Result<(), CliError> {
+    Ok(json::write(value, out)?)
+}
+
+fn house_row(house: &House) -> Vec<String> {
+    vec![
+        house.id.to_string(),
+        house.kind.clone(),
+        house.street.clone(),
+        house.number.to_string(),
+        house.floor.to_string(),
+        house.postcode.to_string(),
+        house.rooms.to_string(),
+        house.baths.to_string(),
+        house.area.to_string(),
+        house.price_text(),
+    ]
+}
+
+const HEADER: &[&str] = &[
+    "ID", "Tipo", "Calle", "Número", "Piso", "C.P.", "Hab.", "Baños", "m²", "Precio",
+];
+
+fn print_table(page: &HousePage, near: Option<&Near>, out: &mut dyn Write) -> Result<(), CliError> {
+    let distance = |house: &House| match near.and_then(|near| near.distance(house)) {
+        Some(distance) => format!("{distance:.1}"),
+        None => String::new(),
+    };
+    let mut header: Vec<String> = HEADER.iter().map(|h| h.to_string()).collect();
+    let mut rows: Vec<Vec<String>> = page.houses.iter().map(house_row).collect();
+    if near.is_some() {
+        header.push("km".to_owned());
+        for (row, house) in rows.iter_mut().zip(&page.houses) {
+            row.push(distance(house));
+        }
+    }
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, &width))| match column {
+                1 | 2 => format!("{cell:<width$}"), // text is aligned to the left
+                _ => format!("{cell:>width$}"),
+            })
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    writeln!(out, "({} de {} viviendas)", page.houses.len(), page.total)?;
+    Ok(())
+}
+
+fn print_house(house: &House, json: bool, out: &mut dyn Write) -> Result<(), CliError> {
+    if json {
+        print_json(house, out)?;
+    } else {
+        for (name, value) in HEADER.iter().zip(house_row(house)) {
+            writeln!(out, "{name:>9}: {value}")?;
+        }
+        writeln!(out, "{:>9}: {}", "Operación", house.operation)?;
+        if house.expenses > 0.0 {
+            writeln!(
+                out,
+                "{:>9}: {} {}/mes",
+                "Expensas", house.currency, house.expenses
+            )?;
+        }
+        for (index, ownership) in house.owners.iter().enumerate() {
+            let name = if index == 0 { "Dueños" } else { "" };
+            writeln!(out, "{name:>9}: {} ({} %)", ownership.name, ownership.share)?;
+        }
+        if !house.tags.is_empty() {
+            writeln!(out, "{:>9}: {}", "Caract.", house.tags.join(", "))?;
+        }
+        let place: Vec<&str> = [&house.city, &house.province, &house.country]
+            .into_iter()
+            .map(String::as_str)
+            .filter(|part| !part.is_empty())
+            .collect();
+        if !place.is_empty() {
+            writeln!(out, "{:>9}: {}", "Lugar", place.join(", "))?;
+        }
+        if let Some((latitude, longitude)) = house.coordinates() {
+            writeln!(out, "{:>9}: {latitude}, {longitude}", "Coords.")?;
+        }
+    }
+    Ok(())
+}
+
+fn search(
+    dao: &mut dyn DAO,
+    args: &[String],
+    fields: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let args = Args::parse(args, &[fields, PAGE_FIELDS])?;
+    args.no_positional()?;
+    let filter = args.filter()?;
+    let page = dao.get_houses(&filter, args.page()?)?;
+    if args.json {
+        print_json(&page, out)?;
+    } else {
+        print_table(&page, filter.near.as_ref(), out)?;
+    }
+    Ok(())
+}
+
+fn show(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let house = dao.get_house(args.id()?)?;
+    print_house(&house, args.json, out)
+}
+
+fn check_street(gazetteer: Option<&Path>, house: &mut House) -> Result<(), CliError> {
+    let Some(path) = gazetteer else {
+        return Ok(());
+    };
+    match Gazetteer::load(path)?.resolve(&house.street, house.postcode) {
+        Resolution::Unknown => {}
+        Resolution::Exact(street) => street.apply(house),
+        Resolution::Ambiguous(candidates) => {
+            eprintln!(
+                "La calle «{}» no figura en el callejero, las más parecidas son:",
+                house.street
+            );
+            for candidate in &candidates {
+                eprintln!("{candidate}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add(
+    dao: &mut dyn DAO,
+    gazetteer: Option<&Path>,
+    args: &[String],
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let args = Args::parse(
+        args,
+        &[HOUSE_FIELDS, PRICE_FIELDS, TAG_FIELDS, LOCATION_FIELDS],
+    )?;
+    args.no_positional()?;
+    if let Some(name) = HOUSE_FIELDS
+        .iter()
+        .find(|&&name| !args.options.contains_key(name))
+    {
+        return usage(format!("Falta la opción --{name}"));
+    }
+    let mut house = House {
+        currency: CURRENCY.to_owned(),
+        ..House::default()
+    };
+    args.update_house(&mut house)?;
+    check_street(gazetteer, &mut house)?;
+    let house = dao.create_house(&house)?;
+    print_house(&house, args.json, out)
+}
+
+fn update(
+    dao: &mut dyn DAO,
+    gazetteer: Option<&Path>,
+    args: &[String],
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let args = Args::parse(
+        args,
+        &[HOUSE_FIELDS, PRICE_FIELDS, TAG_FIELDS, LOCATION_FIELDS],
+    )?;
+    let mut house = dao.get_house(args.id()?)?;
+    args.update_house(&mut house)?;
+    // The addresses that are not changed are not checked again
+    if args.options.contains_key("street") || args.options.contains_key("postcode") {
+        check_street(gazetteer, &mut house)?;
+    }
+    house.version = dao.update_house(&house)?;
+    print_house(&house, args.json, out)
+}
+
+fn delete(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let house = dao.get_house(args.id()?)?;
+    dao.delete_house(&house)?;
+    writeln!(out, "Vivienda {} enviada a la papelera", house.id)?;
+    Ok(())
+}
+
+fn trash(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let deleted = dao.get_deleted_houses()?;
+    if args.json {
+        return print_json(&deleted, out);
+    }
+    for deleted in &deleted {
+        let house = &deleted.house;
+        writeln!(
+            out,
+            "{}  {:>4}  {} al {}  {}",
+            deleted.deleted_at.format("%Y-%m-%d %H:%M"),
+            house.id,
+            house.street,
+            house.number,
+            house.kind
+        )?;
+    }
+    Ok(())
+}
+
+fn restore(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let id = args.id()?;
+    dao.restore_house(id)?;
+    writeln!(out, "Vivienda {id} restaurada")?;
+    Ok(())
+}
+
+fn purge(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[PURGE_FIELDS])?;
+    match args.get::<u64>("days")? {
+        Some(days) => {
+            args.no_positional()?;
+            let Some(before) = Local::now().naive_local().checked_sub_days(Days::new(days)) else {
+                return usage(format!("Demasiados días para --days: {days}"));
+            };
+            let count = dao.purge_deleted(before)?;
+            writeln!(out, "Se eliminaron {count} viviendas de la papelera")?;
+        }
+        None => {
+            let id = args.id()?;
+            dao.purge_house(id)?;
+            writeln!(out, "Vivienda {id} eliminada definitivamente")?;
+        }
+    }
+    Ok(())
+}
+
+fn prices(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let prices = dao.get_price_history(args.id()?)?;
+    if args.json {
+        return print_json(&prices, out);
+    }
+    for change in &prices {
+        writeln!(
+            out,
+            "{}  {} {}",
+            change.changed_at.format("%Y-%m-%d %H:%M"),
+            change.currency,
+            change.price
+        )?;
+    }
+    Ok(())
+}
+
+fn log(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[AUDIT_FIELDS])?;
+    let house_id = if args.positional.is_empty() {
+        None
+    } else {
+        Some(args.id()?)
+    };
+    let query = AuditQuery {
+        house_id,
+        user: args.options.get("user").cloned(),
+        since: args.get("since")?,
+        limit: args.count("limit")?,
+    };
+    let entries = dao.get_audit_log(&query)?;
+    if args.json {
+        return print_json(&entries, out);
+    }
+    for entry in &entries {
+        writeln!(
+            out,
+            "{}  {:>4}  {}  {}",
+            entry.changed_at.format("%Y-%m-%d %H:%M"),
+            entry.house_id,
+            entry.action,
+            entry.user
+        )?;
+        for change in &entry.changes {
+            writeln!(out, "      {change}")?;
+        }
+    }
+    Ok(())
+}
+
+fn export(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[HOUSE_FIELDS, FILTER_FIELDS])?;
+    let filter = args.filter()?;
+    match args.positional.as_slice() {
+        [] if args.json => {
+            json::export(dao, &filter, out)?;
+        }
+        [] => {
+            csv::export(dao, &filter, out)?;
+        }
+        [path] => {
+            let mut writer = BufWriter::new(File::create(path).map_err(CsvError::from)?);
+            let count = if json::is_json(Path::new(path)) {
+                json::export(dao, &filter, &mut writer)?
+            } else {
+                csv::export(dao, &filter, &mut writer)?
+            };
+            writer.flush().map_err(CsvError::from)?;
+            writeln!(out, "Se exportaron {count} viviendas a {path}")?;
+        }
+        _ => return usage("Se esperaba un único archivo".to_owned()),
+    }
+    Ok(())
+}
+
+fn import(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    match args.positional.as_slice() {
+        [path] => {
+            let mut file = File::open(path).map_err(CsvError::from)?;
+            let report: Report = if json::is_json(Path::new(path)) {
+                json::import(dao, &mut file)?
+            } else {
+                csv::import(dao, &mut file)?
+            };
+            writeln!(out, "{report}")?;
+            Ok(())
+        }
+        _ => usage("Se esperaba un único archivo".to_owned()),
+    }
+}
+
+fn streets(gazetteer: Option<&Path>, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[&["postcode"]])?;
+    let [street] = args.positional.as_slice() else {
+        return usage("Se esperaba una única calle".to_owned());
+    };
+    let Some(path) = gazetteer else {
+        return usage(
+            "No hay un callejero, indíquelo en «gazetteer» del archivo de configuración".to_owned(),
+        );
+    };
+    let candidates = Gazetteer::load(path)?.candidates(street, args.get("postcode")?.unwrap_or(0));
+    if args.json {
+        return print_json(&candidates, out);
+    }
+    for candidate in &candidates {
+        writeln!(out, "{candidate}")?;
+    }
+    Ok(())
+}
+
+fn kinds(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let types = dao.get_house_types()?;
+    if args.json {
+        return print_json(&types, out);
+    }
+    for house_type in &types {
+        let retired = if house_type.retired {
+            "  (retirado)"
+        } else {
+            ""
+        };
+        writeln!(out, "{:>4}  {}{retired}", house_type.id, house_type.name)?;
+    }
+    Ok(())
+}
+
+fn tags(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let tags = dao.get_tags()?;
+    if args.json {
+        return print_json(&tags, out);
+    }
+    for tag in &tags {
+        writeln!(out, "{:>4}  {}", tag.id, tag.name)?;
+    }
+    Ok(())
+}
+
+fn owners(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let owners = dao.get_owners()?;
+    if args.json {
+        return print_json(&owners, out);
+    }
+    for owner in &owners {
+        let contact: Vec<&str> = [&owner.phone, &owner.email, &owner.tax_id]
+            .into_iter()
+            .map(String::as_str)
+            .filter(|field| !field.is_empty())
+            .collect();
+        writeln!(
+            out,
+            "{:>4}  {}  {}",
+            owner.id,
+            owner.name,
+            contact.join("  ")
+        )?;
+    }
+    Ok(())
+}
+
+fn tenants(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let tenants = dao.get_tenants()?;
+    if args.json {
+        return print_json(&tenants, out);
+    }
+    for tenant in &tenants {
+        let contact: Vec<&str> = [&tenant.phone, &tenant.email, &tenant.tax_id]
+            .into_iter()
+            .map(String::as_str)
+            .filter(|field| !field.is_empty())
+            .collect();
+        writeln!(
+            out,
+            "{:>4}  {}  {}",
+            tenant.id,
+            tenant.name,
+            contact.join("  ")
+        )?;
+    }
+    Ok(())
+}
+
+fn print_lease(lease: &Lease, house: &str, out: &mut dyn Write) -> Result<(), CliError> {
+    writeln!(
+        out,
+        "{:>4}  {}  {} a {}  {}  {} {}  {house}",
+        lease.id, lease.status, lease.start, lease.end, lease.tenant, lease.currency, lease.rent
+    )?;
+    Ok(())
+}
+
+fn leases(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let leases = dao.get_leases(args.id()?)?;
+    if args.json {
+        return print_json(&leases, out);
+    }
+    for lease in &leases {
+        print_lease(lease, "", out)?;
+    }
+    Ok(())
+}
+
+fn expiring(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[EXPIRING_FIELDS])?;
+    args.no_positional()?;
+    let days = args.get::<u64>("days")?.unwrap_or(30);
+    let Some(until) = Local::now().date_naive().checked_add_days(Days::new(days)) else {
+        return usage(format!("Demasiados días para --days: {days}"));
+    };
+    let leases = dao.get_expiring_leases(until)?;
+    if args.json {
+        return print_json(&leases, out);
+    }
+    for lease in &leases {
+        let house = dao.get_house(lease.house_id)?;
+        print_lease(lease, &format!("{} al {}", house.street, house.number), out)?;
+    }
+    Ok(())
+}
+
+fn account(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let entries = ledger::account(dao, args.id()?, Local::now().date_naive())?;
+    if args.json {
+        return print_json(&entries, out);
+    }
+    for entry in &entries {
+        let amount = if entry.payment_id.is_some() {
+            -entry.payment
+        } else {
+            entry.charge
+        };
+        writeln!(
+            out,
+            "{}  {:>12.2}  {:>12.2} {}  {}",
+            entry.date, amount, entry.balance, entry.currency, entry.concept
+        )?;
+    }
+    Ok(())
+}
+
+fn pay(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[PAYMENT_FIELDS])?;
+    let Some(amount) = args.get("amount")? else {
+        return usage("Falta el importe del pago, --amount".to_owned());
+    };
+    let payment = Payment {
+        id: -1,
+        lease_id: args.id()?,
+        date: args
+            .get("date")?
+            .unwrap_or_else(|| Local::now().date_naive()),
+        amount,
+        method: args.get::<PaymentMethod>("method")?.unwrap_or_default(),
+        notes: args.options.get("notes").cloned().unwrap_or_default(),
+    };
+    let payment = dao.create_payment(&payment)?;
+    if args.json {
+        return print_json(&payment, out);
+    }
+    writeln!(
+        out,
+        "{:>4}  {}  {:.2}  {}",
+        payment.id, payment.date, payment.amount, payment.method
+    )?;
+    Ok(())
+}
+
+fn overdue(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    args.no_positional()?;
+    let today = Local::now().date_naive();
+    let report = ledger::overdue(dao, today)?;
+    if args.json {
+        return print_json(&report, out);
+    }
+    for overdue in &report {
+        writeln!(
+            out,
+            "{}  {:>4} días  {} {:.2}  {}  {}",
+            overdue.since,
+            overdue.days(today),
+            overdue.lease.currency,
+            overdue.owed,
+            overdue.lease.tenant,
+            overdue.house
+        )?;
+    }
+    Ok(())
+}
+
+fn attachments(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let attachments = dao.get_attachments(args.id()?)?;
+    if args.json {
+        return print_json(&attachments, out);
+    }
+    for attachment in &attachments {
+        let cover = if attachment.cover { "*" } else { " " };
+        writeln!(
+            out,
+            "{:>4} {cover} {:>10}  {}  {}",
+            attachment.id,
+            attachment.size,
+            attachment.name,
+            dao.attachment_path(attachment).display()
+        )?;
+    }
+    Ok(())
+}
+
+fn attach(dao: &mut dyn DAO, args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let (id, path) = match args.positional.as_slice() {
+        [id, path] => match id.parse() {
+            Ok(id) => (id, Path::new(path)),
+            Err(_) => return usage(format!("Número de registro inválido «{id}»")),
+        },
+        _ => return usage("Se esperaba el número de registro y el archivo".to_owned()),
+    };
+    let attachment = dao.add_attachment(id, path)?;
+    if args.json {
+        return print_json(&attachment, out);
+    }
+    writeln!(
+        out,
+        "{:>4}  {}  {}",
+        attachment.id, attachment.name, attachment.hash
+    )?;
+    Ok(())
+}
+
+pub fn run(
+    dao: &mut dyn DAO,
+    gazetteer: Option<&Path>,
+    args: &[String],
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let Some((command, args)) = args.split_first() else {
+        return usage("Falta el comando".to_owned());
+    };
+    match command.as_str() {
+        "list" => search(dao, args, &[], out),
+        "search" => search(dao, args, &[HOUSE_FIELDS, FILTER_FIELDS].concat(), out),
+        "show" => show(dao, args, out),
+        "add" => add(dao, gazetteer, args, out),
+        "update" => update(dao, gazetteer, args, out),
+        "delete" => delete(dao, args, out),
+        "trash" => trash(dao, args, out),
+        "restore" => restore(dao, args, out),
+        "purge" => purge(dao, args, out),
+        "prices" => prices(dao, args, out),
+        "log" => log(dao, args, out),
+        "export" => export(dao, args, out),
+        "import" => import(dao, args, out),
+        "kinds" => kinds(dao, args, out),
+        "tags" => tags(dao, args, out),
+        "owners" => owners(dao, args, out),
+        "tenants" => tenants(dao, args, out),
+        "leases" => leases(dao, args, out),
+        "expiring" => expiring(dao, args, out),
+        "account" => account(dao, args, out),
+        "pay" => pay(dao, args, out),
+        "overdue" => overdue(dao, args, out),
+        "attachments" => attachments(dao, args, out),
+        "attach" => attach(dao, args, out),
+        "streets" => streets(gazetteer, args, out),
+        _ => usage(format!("Comando desconocido «{command}»")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Memory;
+
+    // The output of the command
+    fn run_args(dao: &mut dyn DAO, args: &[&str]) -> Result<String, CliError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = Vec::new();
+        run(dao, None, &args, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn negative_offset_and_limit_are_rejected() {
+        let mut dao = Memory::demo("test");
+        for option in ["--offset", "--limit"] {
+            let result = run_args(&mut dao, &["list", option, "-1"]);
+            assert!(matches!(result, Err(CliError::Usage(_))), "{option}");
+        }
+        let output = run_args(
+            &mut dao,
+            &["list", "--offset", "1", "--limit", "2", "--json"],
+        );
+        let page: serde_json::Value = serde_json::from_str(&output.unwrap()).unwrap();
+        let ids: Vec<i64> = page["data"]["houses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|house| house["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids, [2, 3]);
+        assert!(matches!(
+            run_args(&mut dao, &["log", "--limit", "-5"]),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn too_many_days_are_rejected() {
+        let mut dao = Memory::demo("test");
+        let days = u64::MAX.to_string();
+        assert!(matches!(
+            run_args(&mut dao, &["expiring", "--days", &days]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            run_args(&mut dao, &["purge", "--days", &days]),
+            Err(CliError::Usage(_))
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    env,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Days, Local, NaiveDateTime};
+use dotenvy::dotenv;
+use serde::{Deserialize, Serialize};
+
+const DIRECTORY: &str = "wohnen";
+const FILE_NAME: &str = "config.toml";
+const DEFAULT_DATABASE: &str = "wohnen.sqlite";
+const RECENT_SIZE: usize = 8;
+const UNKNOWN_USER: &str = "desconocido";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Format(PathBuf, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(
+                f,
+                "No se pudo acceder a la configuración «{}»: {error}",
+                path.display()
+            ),
+            Self::Format(path, cause) => write!(
+                f,
+                "La configuración «{}» no es válida: {cause}",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // When it is not set the most recent one is opened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<PathBuf>,
+    pub recent: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gazetteer: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    // Days in the trash, checked when a database is opened; when it is not set they are purged by hand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<TableLayout>,
+}
+
+// `field` is "id" or a name of `House::fields()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub field: String,
+    pub width: i32,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableLayout {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>, // field
+    pub descending: bool,
+    pub columns: Vec<Column>,
+}
+
+fn file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(DIRECTORY).join(FILE_NAME))
+}
+
+impl Config {
+    // A missing file is an empty configuration
+    pub fn load() -> Result<Self, ConfigError> {
+        let Some(path) = file() else {
+            return Ok(Config::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|error| ConfigError::Format(path, error.to_string()))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(error) => Err(ConfigError::Io(path, error)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let Some(path) = file() else {
+            return Ok(()); // without a home directory there is nowhere to keep it
+        };
+        let text = toml::to_string(self)
+            .map_err(|error| ConfigError::Format(path.clone(), error.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| ConfigError::Io(path.clone(), error))?;
+        }
+        fs::write(&path, text).map_err(|error| ConfigError::Io(path, error))
+    }
+
+    pub fn add_recent(&mut self, path: &Path) -> PathBuf {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        self.recent.retain(|recent| *recent != path);
+        self.recent.insert(0, path.clone());
+        self.recent.truncate(RECENT_SIZE);
+        path
+    }
+
+    // The recent databases that were moved or deleted, so that the next one is opened instead
+    pub fn forget_missing(&mut self) -> Vec<PathBuf> {
+        let (recent, missing) = self.recent.drain(..).partition(|path| path.is_file());
+        self.recent = recent;
+        missing
+    }
+
+    pub fn user(&self) -> String {
+        self.user
+            .clone()
+            .or_else(|| env::var("USER").ok())
+            .or_else(|| env::var("USERNAME").ok()) // Windows
+            .filter(|user| !user.trim().is_empty())
+            .unwrap_or_else(|| UNKNOWN_USER.to_owned())
+    }
+
+    pub fn purge_before(&self) -> Option<NaiveDateTime> {
+        self.retention
+            .and_then(|days| Local::now().naive_local().checked_sub_days(Days::new(days)))
+    }
+
+    // Database to open without --db, and whether it may be created. The configuration file comes
+    // before DATABASE_URL, so a stray .env does not change the database the user chose
+    pub fn database(&self, url: Option<&str>) -> (PathBuf, bool) {
+        if let Some(path) = self.database.as_ref().or(self.recent.first()) {
+            return (path.clone(), false);
+        }
+        if let Some(url) = url {
+            let path = url.strip_prefix("sqlite://").unwrap_or(url);
+            return (PathBuf::from(path), true);
+        }
+        let dir = dirs::data_dir().map_or_else(PathBuf::new, |dir| dir.join(DIRECTORY));
+        (dir.join(DEFAULT_DATABASE), true)
+    }
+}
+
+// DATABASE_URL from the environment or a .env file
+pub fn database_url() -> Option<String> {
+    dotenv().ok();
+    env::var("DATABASE_URL").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+    #[test]
+    fn configured_database_comes_before_database_url() {
+        let url = Some("sqlite://otra.sqlite");
+        let config = Config {
+            database: Some(PathBuf::from("perez.sqlite")),
+            ..Default::default()
+        };
+        assert_eq!(config.database(url), (PathBuf::from("perez.sqlite"), false));
+
+        let config = Config {
+            recent: vec![PathBuf::from("gomez.sqlite")],
+            ..Default::default()
+        };
+        assert_eq!(config.database(url), (PathBuf::from("gomez.sqlite"), false));
+
+        assert_eq!(
+            Config::default().database(url),
+            (PathBuf::from("otra.sqlite"), true)
+        );
+        let (path, create) = Config::default().database(None);
+        assert!(path.ends_with(DEFAULT_DATABASE) && create);
+    }
+
+    #[test]
+    fn missing_recent_databases_are_forgotten() {
+        let path = env::temp_dir().join(format!("wohnen-recent-{}.sqlite", process::id()));
+        fs::write(&path, "").unwrap();
+        let mut config = Config {
+            recent: vec![PathBuf::from("movida.sqlite"), path.clone()],
+            ..Default::default()
+        };
+        let missing = config.forget_missing();
+        let _ = fs::remove_file(&path);
+        assert_eq!(missing, [PathBuf::from("movida.sqlite")]);
+        assert_eq!(config.database(None), (path, false));
+    }
+}
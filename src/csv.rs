@@ -0,0 +1,454 @@
+use crate::base::{Change, Error, Filter, House, HouseType, Operation, Page, Tag, CURRENCY, DAO};
+use crate::utils::test_positive_and_zero_number;
+
+use std::{
+    fmt::{self, Display},
+    io::{self, Read, Write},
+    mem::take,
+};
+
+pub const COLUMNS: &[&str] = &[
+    "id",
+    "kind",
+    "street",
+    "number",
+    "floor",
+    "postcode",
+    "rooms",
+    "baths",
+    "area",
+    "operation",
+    "price",
+    "currency",
+    "expenses",
+    "tags",
+    "city",
+    "province",
+    "country",
+    "latitude",
+    "longitude",
+];
+
+// Columns added after the first version, the files without them are still imported
+const OPTIONAL: &[&str] = &[
+    "operation",
+    "price",
+    "currency",
+    "expenses",
+    "tags",
+    "city",
+    "province",
+    "country",
+    "latitude",
+    "longitude",
+];
+
+// Separator of the names in the tags column, the commas would need quotes
+const TAG_SEPARATOR: char = ';';
+
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    Dao(Error),
+    Header(String),
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "No se pudo acceder al archivo: {error}"),
+            Self::Dao(error) => write!(f, "{error}"),
+            Self::Header(cause) => write!(f, "La cabecera del archivo no es válida: {cause}"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(error: io::Error) -> Self {
+        CsvError::Io(error)
+    }
+}
+
+impl From<Error> for CsvError {
+    fn from(error: Error) -> Self {
+        CsvError::Dao(error)
+    }
+}
+
+#[derive(Debug)]
+pub struct RowError {
+    pub position: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub created: usize,
+    pub errors: Vec<RowError>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Viviendas importadas: {}", self.created)?;
+        if !self.errors.is_empty() {
+            write!(f, "\nFilas con errores: {}", self.errors.len())?;
+            for error in &self.errors {
+                write!(f, "\n  {}: {}", error.position, error.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+pub fn export<W: Write + ?Sized>(
+    dao: &mut dyn DAO,
+    filter: &Filter,
+    writer: &mut W,
+) -> Result<usize, CsvError> {
+    let page = dao.get_houses(filter, Page::all())?;
+    writeln!(writer, "{}", COLUMNS.join(","))?;
+    for house in &page.houses {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            house.id,
+            escape(&house.kind),
+            escape(&house.street),
+            house.number,
+            house.floor,
+            house.postcode,
+            house.rooms,
+            house.baths,
+            house.area,
+            house.operation.code(),
+            house.price,
+            escape(&house.currency),
+            house.expenses,
+            escape(&house.tags.join(&TAG_SEPARATOR.to_string())),
+            escape(&house.city),
+            escape(&house.province),
+            escape(&house.country),
+            house
+                .latitude
+                .map(|latitude| latitude.to_string())
+                .unwrap_or_default(),
+            house
+                .longitude
+                .map(|longitude| longitude.to_string())
+                .unwrap_or_default(),
+        )?;
+    }
+    writer.flush()?;
+    Ok(page.houses.len())
+}
+
+// With the line where each record starts
+pub fn parse(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(take(&mut field)),
+            '\n' | '\r' if !quoted => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                record.push(take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push((start, take(&mut record)));
+                }
+                record.clear();
+                line += 1;
+                start = line;
+            }
+            _ => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push((start, record));
+    }
+    records
+}
+
+fn read_house(columns: &[Option<usize>], record: &[String]) -> Result<House, String> {
+    // columns[i] is the position in the record of COLUMNS[i + 1], the id is not imported
+    let field = |i: usize| {
+        columns[i]
+            .and_then(|c| record.get(c))
+            .map(|f| f.trim())
+            .unwrap_or("")
+    };
+    let integer = |i: usize| {
+        let value = field(i);
+        if test_positive_and_zero_number::<i32>(value) {
+            Ok(value.parse::<i32>().unwrap())
+        } else {
+            Err(format!(
+                "«{value}» no es un entero positivo o cero en «{}»",
+                COLUMNS[i + 1]
+            ))
+        }
+    };
+
+    // The kind is checked by create_batch(), against the house types of the DAO
+    let kind = field(0);
+    let street = field(1);
+    if street.is_empty() {
+        return Err("la calle no puede estar vacía".to_owned());
+    }
+    let area = field(7);
+    if !test_positive_and_zero_number::<f32>(area) {
+        return Err(format!(
+            "«{area}» no es un número positivo o cero en «area»"
+        ));
+    }
+    // Empty amounts are zero, an empty operation or currency takes the default
+    let amount = |i: usize| {
+        let value = field(i);
+        if value.is_empty() {
+            Ok(0.0)
+        } else if test_positive_and_zero_number::<f64>(value) {
+            Ok(value.parse::<f64>().unwrap())
+        } else {
+            Err(format!(
+                "«{value}» no es un número positivo o cero en «{}»",
+                COLUMNS[i + 1]
+            ))
+        }
+    };
+    let operation = match field(8) {
+        "" => Operation::default(),
+        value => value
+            .parse::<Operation>()
+            .map_err(|_| format!("«{value}» no es una operación en «operation»"))?,
+    };
+    // Empty coordinates are unknown, the DAO checks their ranges and that both are given
+    let coordinate = |i: usize| match field(i) {
+        "" => Ok(None),
+        value => match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Some(number)),
+            _ => Err(format!("«{value}» no es un número en «{}»", COLUMNS[i + 1])),
+        },
+    };
+    let currency = match field(10) {
+        "" => CURRENCY.to_owned(),
+        value => value.to_uppercase(),
+    };
+
+    Ok(House {
+        id: -1,
+        kind: kind.to_owned(),
+        street: street.to_owned(),
+        number: integer(2)?,
+        floor: integer(3)?,
+        postcode: integer(4)?,
+        rooms: integer(5)?,
+        baths: integer(6)?,
+        area: area.parse().unwrap(),
+        owners: Vec::new(),
+        operation,
+        price: amount(9)?,
+        currency,
+        expenses: amount(11)?,
+        // The names are checked by create_batch(), against the tags of the DAO
+        tags: field(12)
+            .split(TAG_SEPARATOR)
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        city: field(13).to_owned(),
+        province: field(14).to_owned(),
+        country: field(15).to_owned(),
+        latitude: coordinate(16)?,
+        longitude: coordinate(17)?,
+        version: 0,
+    })
+}
+
+// The rows with errors are left out and returned with their line or element, the others are
+// created in a single batch, so if the database rejects one none of them is imported
+pub fn create_batch(
+    dao: &mut dyn DAO,
+    rows: Vec<(usize, House)>,
+    report: &mut Report,
+) -> Result<Vec<(usize, String)>, Error> {
+    let kinds = dao.get_house_types()?;
+    let tags = dao.get_tags()?;
+    let check = |house: &House| {
+        house.validate()?;
+        // Retired kinds are still valid, as when a house is saved
+        if !kinds.iter().any(|kind| kind.name == house.kind) {
+            return Err(HouseType::unknown(&house.kind));
+        }
+        match house
+            .tags
+            .iter()
+            .find(|name| !tags.iter().any(|tag| Tag::same(&tag.name, name)))
+        {
+            Some(name) => Err(Tag::unknown(name)),
+            None => Ok(()),
+        }
+    };
+
+    let mut rejected = Vec::new();
+    let mut positions = Vec::new();
+    let mut changes = Vec::new();
+    for (row, house) in rows {
+        match check(&house) {
+            Ok(()) => {
+                positions.push(row);
+                changes.push(Change::Create(house));
+            }
+            Err(error) => rejected.push((row, error.to_string())),
+        }
+    }
+    match dao.apply_batch(&changes) {
+        Ok(ids) => report.created += ids.len(),
+        Err(Error::Batch(index, error)) => {
+            rejected.push((
+                positions[index],
+                format!("{error}, no se importó ninguna vivienda"),
+            ));
+        }
+        Err(error) => return Err(error),
+    }
+    Ok(rejected)
+}
+
+pub fn import<R: Read>(dao: &mut dyn DAO, reader: &mut R) -> Result<Report, CsvError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let mut records = parse(&text).into_iter();
+    let header = match records.next() {
+        Some((_, header)) => header,
+        None => return Err(CsvError::Header("el archivo está vacío".to_owned())),
+    };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    let mut columns = Vec::new();
+    for name in &COLUMNS[1..] {
+        match header.iter().position(|h| h == name) {
+            Some(position) => columns.push(Some(position)),
+            None if OPTIONAL.contains(name) => columns.push(None),
+            None => return Err(CsvError::Header(format!("falta la columna «{name}»"))),
+        }
+    }
+
+    let mut report = Report::default();
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (line, record) in records {
+        match read_house(&columns, &record) {
+            Ok(house) => rows.push((line, house)),
+            Err(message) => errors.push((line, message)),
+        }
+    }
+    errors.extend(create_batch(dao, rows, &mut report)?);
+    errors.sort_by_key(|&(line, _)| line);
+    report.errors = errors
+        .into_iter()
+        .map(|(line, message)| RowError {
+            position: format!("línea {line}"),
+            message,
+        })
+        .collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Memory;
+
+    #[test]
+    fn houses_round_trip() {
+        let mut dao = Memory::demo("test");
+        let before = dao.get_houses(&Filter::default(), Page::all()).unwrap();
+        let mut text = Vec::new();
+        assert_eq!(
+            export(&mut dao, &Filter::default(), &mut text).unwrap(),
+            before.houses.len()
+        );
+
+        let report = import(&mut dao, &mut text.as_slice()).unwrap();
+        assert_eq!(report.created, before.houses.len());
+        assert!(report.errors.is_empty(), "{report}");
+
+        let after = dao.get_houses(&Filter::default(), Page::all()).unwrap();
+        let imported = &after.houses[before.houses.len()..];
+        for (original, copy) in before.houses.iter().zip(imported) {
+            assert_ne!(original.id, copy.id);
+            assert_eq!(copy.owners.len(), 0); // the owners are not exported
+            let copy = House {
+                id: original.id,
+                owners: original.owners.clone(),
+                version: original.version,
+                ..copy.clone()
+            };
+            assert_eq!(
+                serde_json::to_value(&copy).unwrap(),
+                serde_json::to_value(original).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn rows_with_errors_are_left_out() {
+        let mut dao = Memory::demo("test");
+        let total = dao
+            .get_houses(&Filter::default(), Page::all())
+            .unwrap()
+            .total;
+        let text = "kind,street,number,floor,postcode,rooms,baths,area,tags\n\
+                    Casa,Calle Uno,1,0,1000,3,1,90.5,Cochera\n\
+                    Castillo,Calle Dos,2,0,1000,3,1,90.5,\n\
+                    Casa,Calle Tres,3,0,1000,3,1,inf,\n\
+                    Loft,Calle Cuatro,4,0,1000,3,1,50,Helipuerto\n";
+        let report = import(&mut dao, &mut text.as_bytes()).unwrap();
+        assert_eq!(report.created, 1);
+        let positions: Vec<&str> = report
+            .errors
+            .iter()
+            .map(|error| error.position.as_str())
+            .collect();
+        assert_eq!(positions, ["línea 3", "línea 4", "línea 5"]);
+        assert_eq!(
+            dao.get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .total,
+            total + 1
+        );
+    }
+}
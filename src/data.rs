@@ -1,6 +1,258 @@
+pub mod memory;
 pub mod models;
 pub mod repository;
 pub mod schema;
 pub mod service;
+pub mod store;
+pub mod streets;
 
+pub use memory::Memory;
 pub use service::Service;
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::base::{
+        AuditQuery, Change, Error, Filter, House, Lease, LeaseStatus, Page, Tenant, CURRENCY, DAO,
+    };
+
+    use chrono::{Days, Local, NaiveDate};
+
+    use std::{env, fs, path::PathBuf, process};
+
+    // A new database with the seeded houses, its files are removed when the test ends
+    pub struct Database {
+        path: PathBuf,
+        pub service: Service,
+    }
+
+    impl Database {
+        pub fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("wohnen-{name}-{}.sqlite", process::id()));
+            let _ = fs::remove_file(&path);
+            let service = Service::open(&path, true, "test").unwrap();
+            Database { path, service }
+        }
+    }
+
+    impl Drop for Database {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_dir_all(self.path.with_extension("files"));
+        }
+    }
+
+    // Both DAOs follow the same rules, the checks run on each of them
+    pub fn each_dao(name: &str, check: impl Fn(&mut dyn DAO)) {
+        check(&mut Memory::demo("test"));
+        check(&mut Database::new(name).service);
+    }
+
+    #[test]
+    fn failed_batch_leaves_nothing_behind() {
+        each_dao("rollback", |dao| {
+            let houses = dao.get_houses(&Filter::default(), Page::all()).unwrap();
+            let audit = dao.get_audit_log(&AuditQuery::default()).unwrap().len();
+            let first = houses.houses[0].clone();
+            let prices = dao.get_price_history(first.id).unwrap().len();
+            let next_id = houses.houses.iter().map(|house| house.id).max().unwrap() + 1;
+
+            let created = House {
+                street: "Calle Nueva".to_owned(),
+                ..first.clone()
+            };
+            let cheaper = House {
+                price: first.price / 2.0,
+                ..first.clone()
+            };
+            // Read before the previous change, its version is no longer the stored one
+            let stale = House {
+                rooms: first.rooms + 1,
+                ..first.clone()
+            };
+            let changes = [
+                Change::Create(created),
+                Change::Update(cheaper),
+                Change::Update(stale),
+            ];
+            match dao.apply_batch(&changes) {
+                Err(Error::Batch(2, error)) => {
+                    assert!(matches!(*error, Error::Conflict), "{error}")
+                }
+                result => panic!("{result:?}"),
+            }
+
+            let after = dao.get_houses(&Filter::default(), Page::all()).unwrap();
+            assert_eq!(after.total, houses.total);
+            assert!(after
+                .houses
+                .iter()
+                .all(|house| house.street != "Calle Nueva"));
+            let stored = dao.get_house(first.id).unwrap();
+            assert_eq!(
+                (stored.price, stored.rooms, stored.version),
+                (first.price, first.rooms, first.version)
+            );
+            assert_eq!(dao.get_price_history(first.id).unwrap().len(), prices);
+            assert!(dao
+                .get_price_history(next_id)
+                .map_or(true, |history| history.is_empty()));
+            assert_eq!(
+                dao.get_audit_log(&AuditQuery::default()).unwrap().len(),
+                audit
+            );
+        });
+    }
+
+    #[test]
+    fn successful_batch_applies_every_change() {
+        each_dao("batch", |dao| {
+            let total = dao
+                .get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .total;
+            let first = dao
+                .get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .houses
+                .remove(0);
+            let created = House {
+                street: "Calle Nueva".to_owned(),
+                ..first.clone()
+            };
+            let ids = dao
+                .apply_batch(&[Change::Create(created), Change::Delete(first.clone())])
+                .unwrap();
+            assert_eq!(ids[1], first.id);
+            assert_eq!(dao.get_house(ids[0]).unwrap().street, "Calle Nueva");
+            assert!(matches!(dao.get_house(first.id), Err(Error::NotFound)));
+            assert_eq!(
+                dao.get_houses(&Filter::default(), Page::all())
+                    .unwrap()
+                    .total,
+                total
+            );
+        });
+    }
+
+    #[test]
+    fn batches_restore_from_the_trash() {
+        each_dao("restore", |dao| {
+            let first = dao
+                .get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .houses
+                .remove(0);
+            dao.apply_batch(&[Change::Delete(first.clone())]).unwrap();
+            // The second one finds the house out of the trash, so neither is applied
+            let twice = [
+                Change::Restore(first.clone()),
+                Change::Restore(first.clone()),
+            ];
+            match dao.apply_batch(&twice) {
+                Err(Error::Batch(1, error)) => {
+                    assert!(matches!(*error, Error::NotFound), "{error}")
+                }
+                result => panic!("{result:?}"),
+            }
+            assert!(matches!(dao.get_house(first.id), Err(Error::NotFound)));
+            let ids = dao.apply_batch(&[Change::Restore(first.clone())]).unwrap();
+            assert_eq!(ids, [first.id]);
+            let stored = dao.get_house(first.id).unwrap();
+            assert_eq!(
+                (stored.street, stored.version),
+                (first.street, first.version)
+            );
+        });
+    }
+
+    #[test]
+    fn stale_versions_conflict() {
+        each_dao("conflict", |dao| {
+            let first = dao
+                .get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .houses
+                .remove(0);
+            let house = House {
+                street: "Calle Nueva".to_owned(),
+                ..first
+            };
+            let house = dao.create_house(&house).unwrap();
+            let mine = House {
+                rooms: house.rooms + 1,
+                ..house.clone()
+            };
+            let theirs = House {
+                baths: house.baths + 1,
+                ..house.clone()
+            };
+            assert_eq!(dao.update_house(&theirs).unwrap(), house.version + 1);
+            assert!(matches!(dao.update_house(&mine), Err(Error::Conflict)));
+            assert!(matches!(dao.delete_house(&mine), Err(Error::Conflict)));
+            assert_eq!(dao.get_house(house.id).unwrap().rooms, house.rooms);
+
+            // Someone else sent it to the trash
+            let stored = dao.get_house(house.id).unwrap();
+            assert!(dao.delete_house(&stored).unwrap());
+            assert!(matches!(dao.update_house(&stored), Err(Error::Conflict)));
+            assert!(matches!(dao.delete_house(&stored), Err(Error::Conflict)));
+        });
+    }
+
+    #[test]
+    fn leases_stay_with_the_house() {
+        each_dao("leases", |dao| {
+            let first = dao
+                .get_houses(&Filter::default(), Page::all())
+                .unwrap()
+                .houses
+                .remove(0);
+            let house = House {
+                street: "Calle Nueva".to_owned(),
+                ..first
+            };
+            let house = dao.create_house(&house).unwrap();
+            let tenant = Tenant {
+                name: "Ana Gómez".to_owned(),
+                ..Default::default()
+            };
+            let tenant = dao.create_tenant(&tenant).unwrap();
+            let lease = Lease {
+                id: -1,
+                house_id: house.id,
+                tenant_id: tenant.id,
+                tenant: String::new(),
+                start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                end: NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
+                rent: 100.0,
+                currency: CURRENCY.to_owned(),
+                deposit: 0.0,
+                index_months: 0,
+                index_rate: 0.0,
+                status: LeaseStatus::Active,
+            };
+            let lease = dao.create_lease(&lease).unwrap();
+            assert!(matches!(
+                dao.delete_house(&house),
+                Err(Error::Constraint(_))
+            ));
+
+            // An ended lease does not keep it out of the trash, but it is not purged
+            let ended = Lease {
+                status: LeaseStatus::Ended,
+                ..lease
+            };
+            assert!(dao.update_lease(&ended).unwrap());
+            assert!(dao.delete_house(&house).unwrap());
+            assert!(matches!(
+                dao.purge_house(house.id),
+                Err(Error::Constraint(_))
+            ));
+            let tomorrow = Local::now().naive_local() + Days::new(1);
+            dao.purge_deleted(tomorrow).unwrap();
+            assert_eq!(dao.get_deleted_houses().unwrap()[0].house.id, house.id);
+            assert_eq!(dao.get_leases(house.id).unwrap(), [ended]);
+        });
+    }
+}
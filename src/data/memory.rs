@@ -0,0 +1,1191 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Days, Local, Months, NaiveDate, NaiveDateTime};
+
+use super::store::Store;
+
+use crate::base::{
+    Attachment, AuditAction, AuditEntry, AuditQuery, Change, Deleted, Error, Filter, House,
+    HousePage, HouseType, Lease, LeaseStatus, Operation, Owner, Ownership, Page, Payment,
+    PaymentMethod, PriceChange, Tag, Tenant, DAO,
+};
+
+// Same kinds that the house_types migration inserts
+const KINDS: &[&str] = &["Casa", "Loft", "Chalet", "Dúplex", "Apartamento"];
+
+// Same tags that the tags migration inserts
+const TAGS: &[&str] = &[
+    "Ascensor",
+    "Aire acondicionado",
+    "Balcón",
+    "Calefacción central",
+    "Cochera",
+    "Jardín",
+    "Pileta",
+    "Terraza",
+    "Amoblado",
+    "Acepta mascotas",
+];
+
+type Sample = (&'static str, &'static str, i32, i32, i32, i32, i32, f32);
+
+// Same listings that the first migration inserts
+const SAMPLE: &[Sample] = &[
+    ("Casa", "Calle el Ceibo", 123, 1, 4321, 3, 1, 150.5),
+    (
+        "Apartamento",
+        "Avenida Bustamante",
+        1456,
+        4,
+        4321,
+        2,
+        1,
+        80.2,
+    ),
+    ("Dúplex", "Calle San Martin", 456, 3, 4321, 3, 1, 120.6),
+    ("Casa", "Calle Cabo de Hornos", 2859, 2, 1758, 4, 2, 200.8),
+    ("Loft", "Av. Bernardo Lehman", 789, 5, 8765, 2, 1, 75.2),
+    ("Chalet", "Calle los Pinos", 334, 1, 5678, 6, 2, 280.0),
+    ("Apartamento", "Calle Müller", 756, 6, 3456, 2, 1, 60.3),
+    ("Loft", "Avenida Chacabuco", 567, 7, 3452, 2, 1, 85.8),
+    ("Casa", "Calle los Lapachos", 1890, 3, 6587, 4, 2, 180.0),
+    ("Apartamento", "Av. Corrientes", 1324, 8, 2345, 2, 1, 70.5),
+    ("Dúplex", "Calle Jorge Newbery", 234, 2, 3452, 4, 2, 180.3),
+    ("Casa", "Calle 2 Arroyos", 5679, 2, 6543, 5, 2, 220.7),
+    ("Loft", "Avenida Blas Parera", 434, 6, 3452, 2, 1, 95.5),
+    (
+        "Chalet",
+        "Calle Lomar del Talar",
+        3012,
+        1,
+        5796,
+        8,
+        3,
+        350.1,
+    ),
+    ("Dúplex", "Calle el Hornero", 890, 4, 1758, 3, 2, 150.0),
+    ("Apartamento", "Avenida Weiß", 3455, 5, 7654, 2, 1, 55.8),
+];
+
+// Owners of the demo: name, phone, email and tax id
+const OWNERS: &[(&str, &str, &str, &str)] = &[
+    (
+        "María González",
+        "+54 11 4555-1234",
+        "maria.gonzalez@example.com",
+        "27-12345678-4",
+    ),
+    (
+        "Juan Pérez",
+        "+54 11 4555-9876",
+        "juan.perez@example.com",
+        "20-23456789-1",
+    ),
+    (
+        "Inmobiliaria Los Álamos",
+        "+54 341 421-0000",
+        "contacto@losalamos.example.com",
+        "30-71234567-8",
+    ),
+];
+
+// House (position in SAMPLE), owner (position in OWNERS) and percentage
+const OWNERSHIPS: &[(usize, usize, f32)] = &[
+    (0, 0, 100.0),
+    (1, 1, 50.0),
+    (1, 2, 50.0),
+    (3, 0, 100.0),
+    (5, 2, 100.0),
+    (8, 1, 60.0),
+    (8, 0, 40.0),
+];
+
+type Location = (&'static str, &'static str, Option<(f64, f64)>);
+
+// City, province and coordinates of each house in SAMPLE, all of them in Argentina
+const LOCATIONS: &[Location] = &[
+    ("Rosario", "Santa Fe", Some((-32.9468, -60.6393))),
+    (
+        "Buenos Aires",
+        "Ciudad Autónoma de Buenos Aires",
+        Some((-34.5875, -58.4107)),
+    ),
+    ("Córdoba", "Córdoba", Some((-31.4135, -64.1811))),
+    ("La Matanza", "Buenos Aires", Some((-34.7703, -58.6254))),
+    ("Rafaela", "Santa Fe", Some((-31.2526, -61.4916))),
+    ("Villa Carlos Paz", "Córdoba", Some((-31.4241, -64.4978))),
+    ("Mendoza", "Mendoza", Some((-32.8895, -68.8458))),
+    ("Córdoba", "Córdoba", Some((-31.4201, -64.1860))),
+    ("Salta", "Salta", Some((-24.7821, -65.4232))),
+    (
+        "Buenos Aires",
+        "Ciudad Autónoma de Buenos Aires",
+        Some((-34.6037, -58.3816)),
+    ),
+    ("Córdoba", "Córdoba", Some((-31.3953, -64.2100))),
+    ("Mar del Plata", "Buenos Aires", Some((-38.0055, -57.5426))),
+    ("Santa Fe", "Santa Fe", Some((-31.6107, -60.6973))),
+    ("Tigre", "Buenos Aires", Some((-34.4264, -58.5797))),
+    ("La Matanza", "Buenos Aires", None),
+    (
+        "San Carlos de Bariloche",
+        "Río Negro",
+        Some((-41.1335, -71.3103)),
+    ),
+];
+
+// Operation, price, currency and expenses of each house in SAMPLE
+const PRICES: &[(Operation, f64, &str, f64)] = &[
+    (Operation::Sale, 185000.0, "USD", 0.0),
+    (Operation::Rent, 350000.0, "ARS", 45000.0),
+    (Operation::Sale, 140000.0, "USD", 0.0),
+    (Operation::Both, 230000.0, "USD", 0.0),
+    (Operation::Rent, 280000.0, "ARS", 38000.0),
+    (Operation::Sale, 320000.0, "USD", 0.0),
+    (Operation::Rent, 220000.0, "ARS", 30000.0),
+    (Operation::Sale, 95000.0, "USD", 25000.0),
+    (Operation::Sale, 210000.0, "USD", 0.0),
+    (Operation::Rent, 300000.0, "ARS", 52000.0),
+    (Operation::Both, 175000.0, "USD", 0.0),
+    (Operation::Sale, 260000.0, "USD", 0.0),
+    (Operation::Rent, 310000.0, "ARS", 41000.0),
+    (Operation::Sale, 450000.0, "USD", 0.0),
+    (Operation::Sale, 0.0, "ARS", 0.0),
+    (Operation::Rent, 190000.0, "ARS", 27000.0),
+];
+
+// Tags of each house in SAMPLE, positions in TAGS
+const HOUSE_TAGS: &[&[usize]] = &[
+    &[4, 5, 9],
+    &[0, 1, 2],
+    &[3, 5],
+    &[4, 5, 6, 9],
+    &[0, 7, 8],
+    &[1, 3, 4, 5, 6, 7],
+    &[0, 2],
+    &[0, 1, 7],
+    &[4, 5, 6],
+    &[0, 3],
+    &[4, 7],
+    &[4, 5, 6, 9],
+    &[1, 2, 8],
+    &[1, 3, 4, 5, 6, 7, 9],
+    &[2, 4],
+    &[0, 8, 9],
+];
+
+// Tenants of the demo: name, phone, email and tax id
+const TENANTS: &[(&str, &str, &str, &str)] = &[
+    (
+        "Lucía Fernández",
+        "+54 11 4777-2211",
+        "lucia.fernandez@example.com",
+        "27-34567890-2",
+    ),
+    (
+        "Carlos Rodríguez",
+        "+54 351 468-3344",
+        "carlos.rodriguez@example.com",
+        "20-45678901-5",
+    ),
+    (
+        "Sofía Martínez",
+        "+54 261 429-5566",
+        "sofia.martinez@example.com",
+        "27-56789012-9",
+    ),
+];
+
+// House (position in SAMPLE), tenant (position in TENANTS), days since the start, months,
+// rent, deposit, months between updates, percentage of each update and status.
+// The dates depend on the day the demo starts, so some leases are always about to end.
+type SampleLease = (usize, usize, u64, u32, f64, f64, i32, f64, LeaseStatus);
+
+const LEASES: &[SampleLease] = &[
+    (
+        1,
+        0,
+        330,
+        12,
+        300000.0,
+        300000.0,
+        3,
+        12.0,
+        LeaseStatus::Active,
+    ),
+    (
+        4,
+        1,
+        700,
+        24,
+        250000.0,
+        250000.0,
+        4,
+        20.0,
+        LeaseStatus::Active,
+    ),
+    (
+        6,
+        2,
+        200,
+        24,
+        200000.0,
+        200000.0,
+        6,
+        25.0,
+        LeaseStatus::Active,
+    ),
+    (
+        9,
+        1,
+        800,
+        24,
+        180000.0,
+        180000.0,
+        6,
+        30.0,
+        LeaseStatus::Ended,
+    ),
+    (
+        12,
+        2,
+        355,
+        12,
+        290000.0,
+        290000.0,
+        3,
+        10.0,
+        LeaseStatus::Active,
+    ),
+];
+
+// Last charges of each lease in LEASES that the demo leaves unpaid, the others are paid
+// a few days after they are due
+const UNPAID: &[usize] = &[0, 2, 0, 0, 1];
+
+// Ids and errors follow the same rules as `Repository`
+#[derive(Debug, Default)]
+pub struct Memory {
+    houses: BTreeMap<i32, House>,
+    trash: BTreeMap<i32, Deleted>, // the deleted houses keep their rows until they are purged
+    types: BTreeMap<i32, HouseType>,
+    tags: BTreeMap<i32, Tag>,
+    owners: BTreeMap<i32, Owner>,
+    prices: Vec<(i32, PriceChange)>, // house id, in the order they were recorded
+    tenants: BTreeMap<i32, Tenant>,
+    leases: BTreeMap<i32, Lease>,
+    payments: BTreeMap<i32, Payment>,
+    attachments: BTreeMap<i32, Attachment>,
+    audit: Vec<AuditEntry>, // in the order they were recorded
+    user: String,           // name written in the audit log
+    store: Store,           // in the temporary directory, deleted with the DAO
+}
+
+impl Memory {
+    pub fn demo(user: &str) -> Self {
+        let mut memory = Memory {
+            user: user.to_owned(),
+            ..Memory::default()
+        };
+        for kind in KINDS {
+            memory.create_house_type(kind).unwrap();
+        }
+        for tag in TAGS {
+            memory.create_tag(tag).unwrap();
+        }
+        let mut owners = Vec::new();
+        for &(name, phone, email, tax_id) in OWNERS {
+            let owner = Owner {
+                id: -1,
+                name: name.to_owned(),
+                phone: phone.to_owned(),
+                email: email.to_owned(),
+                tax_id: tax_id.to_owned(),
+                notes: String::new(),
+            };
+            owners.push(memory.create_owner(&owner).unwrap().id);
+        }
+        for (index, &(kind, street, number, floor, postcode, rooms, baths, area)) in
+            SAMPLE.iter().enumerate()
+        {
+            let owners = OWNERSHIPS
+                .iter()
+                .filter(|&&(house, _, _)| house == index)
+                .map(|&(_, owner, share)| Ownership {
+                    owner_id: owners[owner],
+                    name: String::new(),
+                    share,
+                })
+                .collect();
+            let (operation, price, currency, expenses) = PRICES[index];
+            let (city, province, coordinates) = LOCATIONS[index];
+            let house = House {
+                id: -1,
+                kind: kind.to_owned(),
+                street: street.to_owned(),
+                number,
+                floor,
+                postcode,
+                rooms,
+                baths,
+                area,
+                owners,
+                operation,
+                price,
+                currency: currency.to_owned(),
+                expenses,
+                tags: HOUSE_TAGS[index]
+                    .iter()
+                    .map(|&tag| TAGS[tag].to_owned())
+                    .collect(),
+                city: city.to_owned(),
+                province: province.to_owned(),
+                country: "Argentina".to_owned(),
+                latitude: coordinates.map(|(latitude, _)| latitude),
+                longitude: coordinates.map(|(_, longitude)| longitude),
+                version: 0,
+            };
+            memory.create_house(&house).unwrap();
+        }
+        let mut tenants = Vec::new();
+        for &(name, phone, email, tax_id) in TENANTS {
+            let tenant = Tenant {
+                id: -1,
+                name: name.to_owned(),
+                phone: phone.to_owned(),
+                email: email.to_owned(),
+                tax_id: tax_id.to_owned(),
+                notes: String::new(),
+            };
+            tenants.push(memory.create_tenant(&tenant).unwrap().id);
+        }
+        let today = Local::now().date_naive();
+        let leases = LEASES.iter().zip(UNPAID);
+        for (
+            &(house, tenant, days, months, rent, deposit, index_months, index_rate, status),
+            &unpaid,
+        ) in leases
+        {
+            let start = today - Days::new(days);
+            let lease = Lease {
+                id: -1,
+                house_id: house as i32 + 1, // the houses of the demo are numbered from 1
+                tenant_id: tenants[tenant],
+                tenant: String::new(),
+                start,
+                end: start + Months::new(months) - Days::new(1),
+                rent,
+                currency: "ARS".to_owned(),
+                deposit,
+                index_months,
+                index_rate,
+                status,
+            };
+            let lease = memory.create_lease(&lease).unwrap();
+            let charges = lease.charges(today);
+            for &(due, amount) in &charges[..charges.len().saturating_sub(unpaid)] {
+                let payment = Payment {
+                    id: -1,
+                    lease_id: lease.id,
+                    date: (due + Days::new(5)).min(today),
+                    amount,
+                    method: PaymentMethod::Transfer,
+                    notes: String::new(),
+                };
+                memory.create_payment(&payment).unwrap();
+            }
+        }
+        memory
+    }
+
+    fn check_kind(&self, kind: &str) -> Result<(), Error> {
+        // Retired kinds are still valid, the houses that use them can be edited
+        if self.types.values().any(|t| t.name == kind) {
+            Ok(())
+        } else {
+            Err(HouseType::unknown(kind))
+        }
+    }
+
+    fn check_owners(&self, house: &House) -> Result<(), Error> {
+        // Same message that the FOREIGN KEY constraint gives in SQLite
+        if house
+            .owners
+            .iter()
+            .all(|o| self.owners.contains_key(&o.owner_id))
+        {
+            Ok(())
+        } else {
+            Err(Error::Constraint(
+                "FOREIGN KEY constraint failed".to_owned(),
+            ))
+        }
+    }
+
+    fn check_tags(&self, house: &House) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for name in &house.tags {
+            let tag = self
+                .tags
+                .values()
+                .find(|tag| Tag::same(&tag.name, name))
+                .ok_or_else(|| Tag::unknown(name))?;
+            if !names.contains(&tag.name) {
+                names.push(tag.name.clone());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    // Like the version check of the database
+    fn check_version(&self, house: &House) -> Result<(), Error> {
+        match self.houses.get(&house.id) {
+            Some(stored) if stored.version == house.version => Ok(()),
+            Some(_) => Err(Error::Conflict),
+            None if self.trash.contains_key(&house.id) => Err(Error::Conflict),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    // The stored houses keep the owner ids, the names are taken when they are read
+    fn named(&self, house: &House) -> House {
+        let mut house = house.clone();
+        for ownership in &mut house.owners {
+            ownership.name = self
+                .owners
+                .get(&ownership.owner_id)
+                .map(|o| o.name.clone())
+                .unwrap_or_default();
+        }
+        house.owners.sort_by(|a, b| b.share.total_cmp(&a.share));
+        house
+    }
+
+    fn house_leases(&self, id: i32) -> usize {
+        self.leases.values().filter(|l| l.house_id == id).count()
+    }
+
+    fn check_lease(&self, lease: &Lease) -> Result<(), Error> {
+        lease.validate()?;
+        // Same message that the FOREIGN KEY constraint gives in SQLite
+        if !self.houses.contains_key(&lease.house_id)
+            || !self.tenants.contains_key(&lease.tenant_id)
+        {
+            return Err(Error::Constraint(
+                "FOREIGN KEY constraint failed".to_owned(),
+            ));
+        }
+        let other = |l: &Lease| {
+            l.status == LeaseStatus::Active && l.house_id == lease.house_id && l.id != lease.id
+        };
+        if lease.status == LeaseStatus::Active && self.leases.values().any(other) {
+            return Err(Lease::already_active());
+        }
+        Ok(())
+    }
+
+    fn named_lease(&self, lease: &Lease) -> Lease {
+        let mut lease = lease.clone();
+        lease.tenant = self
+            .tenants
+            .get(&lease.tenant_id)
+            .map(|t| t.name.clone())
+            .unwrap_or_default();
+        lease
+    }
+
+    fn record_price(&mut self, house: &House) {
+        let change = PriceChange {
+            price: house.price,
+            currency: house.currency.clone(),
+            changed_at: Local::now().naive_local(),
+        };
+        self.prices.push((house.id, change));
+    }
+
+    // Unless an update changed nothing, like the database
+    fn record_audit(
+        &mut self,
+        house_id: i32,
+        action: AuditAction,
+        before: Option<&House>,
+        after: Option<&House>,
+    ) {
+        let changes = AuditEntry::diff(before, after);
+        if action == AuditAction::Update && changes.is_empty() {
+            return;
+        }
+        let entry = AuditEntry {
+            id: self.audit.len() as i32 + 1,
+            house_id,
+            action,
+            changes,
+            user: self.user.clone(),
+            changed_at: Local::now().naive_local(),
+        };
+        self.audit.push(entry);
+    }
+
+    fn release(&self, hash: &str) -> Result<(), Error> {
+        if self.attachments.values().any(|a| a.hash == hash) {
+            return Ok(());
+        }
+        self.store.remove(hash)
+    }
+
+    fn check_tag_name(&self, id: i32, name: &str) -> Result<(), Error> {
+        Tag::validate_name(name)?;
+        // Same message that the UNIQUE constraint gives in SQLite
+        if self
+            .tags
+            .values()
+            .any(|t| t.id != id && t.name == name.trim())
+        {
+            Err(Error::Constraint(
+                "UNIQUE constraint failed: tags.name".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_type_name(&self, id: i32, name: &str) -> Result<(), Error> {
+        HouseType::validate_name(name)?;
+        // Same message that the UNIQUE constraint gives in SQLite
+        if self
+            .types
+            .values()
+            .any(|t| t.id != id && t.name == name.trim())
+        {
+            Err(Error::Constraint(
+                "UNIQUE constraint failed: house_types.name".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Like the SQLite rowid: one more than the largest id in use
+fn next_id<T>(map: &BTreeMap<i32, T>) -> i32 {
+    map.keys().next_back().map_or(1, |id| id + 1)
+}
+
+impl DAO for Memory {
+    fn get_houses(&mut self, filter: &Filter, page: Page) -> Result<HousePage, Error> {
+        if let Some(near) = &filter.near {
+            near.validate()?;
+        }
+        let mut houses: Vec<House> = self
+            .houses
+            .values()
+            .map(|h| self.named(h))
+            .filter(|h| filter.valid(h))
+            .collect();
+        filter.sort(&mut houses);
+        let total = houses.len() as i64;
+        let houses = page.cut(houses);
+        Ok(HousePage { houses, total })
+    }
+
+    fn get_house(&mut self, id: i32) -> Result<House, Error> {
+        self.houses
+            .get(&id)
+            .map(|h| self.named(h))
+            .ok_or(Error::NotFound)
+    }
+
+    fn create_house(&mut self, house: &House) -> Result<House, Error> {
+        house.validate()?;
+        self.check_kind(&house.kind)?;
+        self.check_owners(house)?;
+        let tags = self.check_tags(house)?;
+        let mut house = house.clone();
+        house.tags = tags;
+        // The houses in the trash keep their ids, like the rows of the database
+        house.id = next_id(&self.houses).max(next_id(&self.trash));
+        house.version = 1;
+        self.houses.insert(house.id, house.clone());
+        self.record_price(&house);
+        let named = self.named(&house);
+        self.record_audit(house.id, AuditAction::Create, None, Some(&named));
+        Ok(named)
+    }
+
+    fn update_house(&mut self, house: &House) -> Result<i32, Error> {
+        house.validate()?;
+        self.check_kind(&house.kind)?;
+        self.check_owners(house)?;
+        let tags = self.check_tags(house)?;
+        self.check_version(house)?;
+        let before = self.get_house(house.id)?;
+        let stored = self.houses.get_mut(&house.id).ok_or(Error::NotFound)?;
+        let changed = stored.price != house.price || stored.currency != house.currency;
+        *stored = House {
+            tags,
+            version: house.version + 1,
+            ..house.clone()
+        };
+        if changed {
+            self.record_price(house);
+        }
+        let after = self.get_house(house.id)?;
+        self.record_audit(house.id, AuditAction::Update, Some(&before), Some(&after));
+        Ok(after.version)
+    }
+
+    fn delete_house(&mut self, house: &House) -> Result<bool, Error> {
+        let id = house.id;
+        if self
+            .leases
+            .values()
+            .any(|l| l.house_id == id && l.status == LeaseStatus::Active)
+        {
+            return Err(Lease::still_active());
+        }
+        self.check_version(house)?;
+        let before = self.get_house(id)?;
+        let house = self.houses.remove(&id).ok_or(Error::NotFound)?;
+        let deleted_at = Local::now().naive_local();
+        self.trash.insert(id, Deleted { house, deleted_at });
+        self.record_audit(id, AuditAction::Delete, Some(&before), None);
+        Ok(true)
+    }
+
+    fn apply_batch(&mut self, changes: &[Change]) -> Result<Vec<i32>, Error> {
+        // Like a transaction: the rows that the changes write are put back when one fails
+        let snapshot = (
+            self.houses.clone(),
+            self.trash.clone(),
+            self.prices.len(),
+            self.audit.len(),
+        );
+        let mut ids = Vec::with_capacity(changes.len());
+        for (index, change) in changes.iter().enumerate() {
+            let applied = match change {
+                Change::Create(house) => self.create_house(house).map(|house| house.id),
+                Change::Update(house) => self.update_house(house).map(|_| house.id),
+                Change::Delete(house) => self.delete_house(house).map(|_| house.id),
+                Change::Restore(house) => self.restore_house(house.id).map(|_| house.id),
+            };
+            match applied {
+                Ok(id) => ids.push(id),
+                Err(error) => {
+                    let (houses, trash, prices, audit) = snapshot;
+                    self.houses = houses;
+                    self.trash = trash;
+                    self.prices.truncate(prices);
+                    self.audit.truncate(audit);
+                    return Err(Error::Batch(index, Box::new(error)));
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn get_deleted_houses(&mut self) -> Result<Vec<Deleted>, Error> {
+        let mut deleted: Vec<Deleted> = self
+            .trash
+            .values()
+            .map(|d| Deleted {
+                house: self.named(&d.house),
+                deleted_at: d.deleted_at,
+            })
+            .collect();
+        deleted.sort_by(|a, b| {
+            b.deleted_at
+                .cmp(&a.deleted_at)
+                .then(b.house.id.cmp(&a.house.id))
+        });
+        Ok(deleted)
+    }
+
+    fn restore_house(&mut self, id: i32) -> Result<bool, Error> {
+        let deleted = self.trash.remove(&id).ok_or(Error::NotFound)?;
+        self.houses.insert(id, deleted.house);
+        let after = self.get_house(id)?;
+        self.record_audit(id, AuditAction::Restore, None, Some(&after));
+        Ok(true)
+    }
+
+    fn purge_house(&mut self, id: i32) -> Result<bool, Error> {
+        if !self.trash.contains_key(&id) {
+            return Err(Error::NotFound);
+        }
+        let count = self.house_leases(id);
+        if count > 0 {
+            return Err(Lease::in_house(count as i64));
+        }
+        let deleted = self.trash.remove(&id).ok_or(Error::NotFound)?;
+        let before = self.named(&deleted.house);
+        self.record_audit(id, AuditAction::Purge, Some(&before), None);
+        // Like ON DELETE CASCADE, the audit log has no foreign key and keeps the entries
+        self.prices.retain(|(house_id, _)| *house_id != id);
+        let attachments: Vec<i32> = self
+            .attachments
+            .values()
+            .filter(|a| a.house_id == id)
+            .map(|a| a.id)
+            .collect();
+        for attachment in attachments {
+            self.remove_attachment(attachment)?;
+        }
+        Ok(true)
+    }
+
+    fn purge_deleted(&mut self, before: NaiveDateTime) -> Result<usize, Error> {
+        // The houses with leases stay in the trash, purge_house() says why
+        let ids: Vec<i32> = self
+            .trash
+            .values()
+            .filter(|d| d.deleted_at < before && self.house_leases(d.house.id) == 0)
+            .map(|d| d.house.id)
+            .collect();
+        for &id in &ids {
+            self.purge_house(id)?;
+        }
+        Ok(ids.len())
+    }
+
+    fn get_price_history(&mut self, id: i32) -> Result<Vec<PriceChange>, Error> {
+        Ok(self
+            .prices
+            .iter()
+            .rev()
+            .filter(|(house_id, _)| *house_id == id)
+            .map(|(_, change)| change.clone())
+            .collect())
+    }
+
+    fn get_audit_log(&mut self, query: &AuditQuery) -> Result<Vec<AuditEntry>, Error> {
+        let entries = self
+            .audit
+            .iter()
+            .rev()
+            .filter(|entry| query.valid(entry))
+            .cloned();
+        Ok(match query.limit {
+            Some(limit) => entries.take(limit.max(0) as usize).collect(),
+            None => entries.collect(),
+        })
+    }
+
+    fn get_leases(&mut self, house_id: i32) -> Result<Vec<Lease>, Error> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.house_id == house_id)
+            .map(|l| self.named_lease(l))
+            .collect();
+        leases.sort_by(|a, b| b.start.cmp(&a.start).then(b.id.cmp(&a.id)));
+        Ok(leases)
+    }
+
+    fn get_expiring_leases(&mut self, until: NaiveDate) -> Result<Vec<Lease>, Error> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.status == LeaseStatus::Active && l.end <= until)
+            .map(|l| self.named_lease(l))
+            .collect();
+        leases.sort_by(|a, b| a.end.cmp(&b.end).then(a.id.cmp(&b.id)));
+        Ok(leases)
+    }
+
+    fn create_lease(&mut self, lease: &Lease) -> Result<Lease, Error> {
+        let mut lease = lease.clone();
+        lease.id = next_id(&self.leases);
+        self.check_lease(&lease)?;
+        self.leases.insert(lease.id, lease.clone());
+        Ok(self.named_lease(&lease))
+    }
+
+    fn update_lease(&mut self, lease: &Lease) -> Result<bool, Error> {
+        if !self.leases.contains_key(&lease.id) {
+            return Err(Error::NotFound);
+        }
+        self.check_lease(lease)?;
+        self.leases.insert(lease.id, lease.clone());
+        Ok(true)
+    }
+
+    fn delete_lease(&mut self, id: i32) -> Result<bool, Error> {
+        let count = self.payments.values().filter(|p| p.lease_id == id).count();
+        if count > 0 {
+            return Err(Lease::with_payments(count as i64));
+        }
+        self.leases.remove(&id).map(|_| true).ok_or(Error::NotFound)
+    }
+
+    fn get_active_leases(&mut self) -> Result<Vec<Lease>, Error> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.status == LeaseStatus::Active)
+            .map(|l| self.named_lease(l))
+            .collect();
+        leases.sort_by(|a, b| a.start.cmp(&b.start).then(a.id.cmp(&b.id)));
+        Ok(leases)
+    }
+
+    fn get_tenant_leases(&mut self, tenant_id: i32) -> Result<Vec<Lease>, Error> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.tenant_id == tenant_id)
+            .map(|l| self.named_lease(l))
+            .collect();
+        leases.sort_by(|a, b| a.start.cmp(&b.start).then(a.id.cmp(&b.id)));
+        Ok(leases)
+    }
+
+    fn get_payments(&mut self, lease_id: i32) -> Result<Vec<Payment>, Error> {
+        let mut payments: Vec<Payment> = self
+            .payments
+            .values()
+            .filter(|p| p.lease_id == lease_id)
+            .cloned()
+            .collect();
+        payments.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+        Ok(payments)
+    }
+
+    fn create_payment(&mut self, payment: &Payment) -> Result<Payment, Error> {
+        payment.validate()?;
+        // Same message that the FOREIGN KEY constraint gives in SQLite
+        if !self.leases.contains_key(&payment.lease_id) {
+            return Err(Error::Constraint(
+                "FOREIGN KEY constraint failed".to_owned(),
+            ));
+        }
+        let mut payment = payment.clone();
+        payment.id = next_id(&self.payments);
+        self.payments.insert(payment.id, payment.clone());
+        Ok(payment)
+    }
+
+    fn delete_payment(&mut self, id: i32) -> Result<bool, Error> {
+        self.payments
+            .remove(&id)
+            .map(|_| true)
+            .ok_or(Error::NotFound)
+    }
+
+    fn get_attachments(&mut self, house_id: i32) -> Result<Vec<Attachment>, Error> {
+        let mut attachments: Vec<Attachment> = self
+            .attachments
+            .values()
+            .filter(|a| a.house_id == house_id)
+            .cloned()
+            .collect();
+        attachments.sort_by(|a, b| b.cover.cmp(&a.cover).then(a.id.cmp(&b.id)));
+        Ok(attachments)
+    }
+
+    fn add_attachment(&mut self, house_id: i32, path: &Path) -> Result<Attachment, Error> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let name = name
+            .ok_or_else(|| Error::Validation(format!("«{}» no es un archivo", path.display())))?;
+        // Same message that the FOREIGN KEY constraint gives in SQLite
+        if !self.houses.contains_key(&house_id) {
+            return Err(Error::Constraint(
+                "FOREIGN KEY constraint failed".to_owned(),
+            ));
+        }
+        let (hash, size) = self.store.put(path)?;
+        if self
+            .attachments
+            .values()
+            .any(|a| a.house_id == house_id && a.hash == hash)
+        {
+            return Err(Attachment::duplicate(&name));
+        }
+        let mut attachment = Attachment {
+            id: next_id(&self.attachments),
+            house_id,
+            hash,
+            name,
+            size,
+            cover: false,
+            added_at: Local::now().naive_local(),
+        };
+        let has_cover = self
+            .attachments
+            .values()
+            .any(|a| a.house_id == house_id && a.cover);
+        attachment.cover = attachment.is_image() && !has_cover;
+        self.attachments.insert(attachment.id, attachment.clone());
+        Ok(attachment)
+    }
+
+    fn remove_attachment(&mut self, id: i32) -> Result<bool, Error> {
+        let attachment = self.attachments.remove(&id).ok_or(Error::NotFound)?;
+        self.release(&attachment.hash)?;
+        Ok(true)
+    }
+
+    fn set_cover(&mut self, id: i32) -> Result<bool, Error> {
+        let house_id = self.attachments.get(&id).ok_or(Error::NotFound)?.house_id;
+        for attachment in self
+            .attachments
+            .values_mut()
+            .filter(|a| a.house_id == house_id)
+        {
+            attachment.cover = attachment.id == id;
+        }
+        Ok(true)
+    }
+
+    fn attachment_path(&self, attachment: &Attachment) -> PathBuf {
+        self.store.path(&attachment.hash)
+    }
+
+    fn get_house_types(&mut self) -> Result<Vec<HouseType>, Error> {
+        Ok(self.types.values().cloned().collect())
+    }
+
+    fn create_house_type(&mut self, name: &str) -> Result<HouseType, Error> {
+        self.check_type_name(-1, name)?;
+        let house_type = HouseType {
+            id: next_id(&self.types),
+            name: name.trim().to_owned(),
+            retired: false,
+        };
+        self.types.insert(house_type.id, house_type.clone());
+        Ok(house_type)
+    }
+
+    fn rename_house_type(&mut self, id: i32, name: &str) -> Result<bool, Error> {
+        self.check_type_name(id, name)?;
+        let house_type = self.types.get_mut(&id).ok_or(Error::NotFound)?;
+        let old = std::mem::replace(&mut house_type.name, name.trim().to_owned());
+        // The houses keep the name, in the database they refer to the id
+        for house in self.houses.values_mut().filter(|h| h.kind == old) {
+            house.kind = house_type.name.clone();
+        }
+        Ok(true)
+    }
+
+    fn retire_house_type(&mut self, id: i32, retired: bool) -> Result<bool, Error> {
+        let house_type = self.types.get_mut(&id).ok_or(Error::NotFound)?;
+        house_type.retired = retired;
+        Ok(true)
+    }
+
+    fn get_tags(&mut self) -> Result<Vec<Tag>, Error> {
+        let mut tags: Vec<Tag> = self.tags.values().cloned().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tags)
+    }
+
+    fn create_tag(&mut self, name: &str) -> Result<Tag, Error> {
+        self.check_tag_name(-1, name)?;
+        let tag = Tag {
+            id: next_id(&self.tags),
+            name: name.trim().to_owned(),
+        };
+        self.tags.insert(tag.id, tag.clone());
+        Ok(tag)
+    }
+
+    fn rename_tag(&mut self, id: i32, name: &str) -> Result<bool, Error> {
+        self.check_tag_name(id, name)?;
+        let tag = self.tags.get_mut(&id).ok_or(Error::NotFound)?;
+        let old = std::mem::replace(&mut tag.name, name.trim().to_owned());
+        // The houses keep the names, in the database they refer to the id
+        for house in self.houses.values_mut().filter(|h| h.tags.contains(&old)) {
+            house.tags.retain(|t| *t != old);
+            house.tags.push(tag.name.clone());
+            house.tags.sort();
+        }
+        Ok(true)
+    }
+
+    fn delete_tag(&mut self, id: i32) -> Result<bool, Error> {
+        let name = &self.tags.get(&id).ok_or(Error::NotFound)?.name;
+        let count = self
+            .houses
+            .values()
+            .filter(|h| h.tags.contains(name))
+            .count();
+        if count > 0 {
+            return Err(Tag::in_use(count as i64));
+        }
+        self.tags.remove(&id);
+        Ok(true)
+    }
+
+    fn get_owners(&mut self) -> Result<Vec<Owner>, Error> {
+        let mut owners: Vec<Owner> = self.owners.values().cloned().collect();
+        owners.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(owners)
+    }
+
+    fn create_owner(&mut self, owner: &Owner) -> Result<Owner, Error> {
+        owner.validate()?;
+        let mut owner = owner.clone();
+        owner.id = next_id(&self.owners);
+        self.owners.insert(owner.id, owner.clone());
+        Ok(owner)
+    }
+
+    fn update_owner(&mut self, owner: &Owner) -> Result<bool, Error> {
+        owner.validate()?;
+        match self.owners.get_mut(&owner.id) {
+            Some(stored) => {
+                *stored = owner.clone();
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    fn delete_owner(&mut self, id: i32) -> Result<bool, Error> {
+        let count = self
+            .houses
+            .values()
+            .filter(|h| h.owners.iter().any(|o| o.owner_id == id))
+            .count();
+        if count > 0 {
+            return Err(Owner::in_use(count as i64));
+        }
+        self.owners.remove(&id).map(|_| true).ok_or(Error::NotFound)
+    }
+
+    fn get_tenants(&mut self) -> Result<Vec<Tenant>, Error> {
+        let mut tenants: Vec<Tenant> = self.tenants.values().cloned().collect();
+        tenants.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tenants)
+    }
+
+    fn create_tenant(&mut self, tenant: &Tenant) -> Result<Tenant, Error> {
+        tenant.validate()?;
+        let mut tenant = tenant.clone();
+        tenant.id = next_id(&self.tenants);
+        self.tenants.insert(tenant.id, tenant.clone());
+        Ok(tenant)
+    }
+
+    fn update_tenant(&mut self, tenant: &Tenant) -> Result<bool, Error> {
+        tenant.validate()?;
+        match self.tenants.get_mut(&tenant.id) {
+            Some(stored) => {
+                *stored = tenant.clone();
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    fn delete_tenant(&mut self, id: i32) -> Result<bool, Error> {
+        let count = self.leases.values().filter(|l| l.tenant_id == id).count();
+        if count > 0 {
+            return Err(Tenant::in_use(count as i64));
+        }
+        self.tenants
+            .remove(&id)
+            .map(|_| true)
+            .ok_or(Error::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn houses_are_created_updated_and_deleted() {
+        let mut dao = Memory::demo("test");
+        let page = dao.get_houses(&Filter::default(), Page::all()).unwrap();
+        let house = House {
+            id: -1,
+            street: "Calle Nueva".to_owned(),
+            tags: vec![
+                "terraza".to_owned(),
+                "Cochera".to_owned(),
+                "cochera".to_owned(),
+            ],
+            ..page.houses[0].clone()
+        };
+        let created = dao.create_house(&house).unwrap();
+        assert_eq!((created.id, created.version), (page.total as i32 + 1, 1));
+        assert_eq!(created.tags, ["Cochera", "Terraza"]); // as they are in the catalogue
+
+        let bigger = House {
+            rooms: created.rooms + 1,
+            ..created.clone()
+        };
+        assert_eq!(dao.update_house(&bigger).unwrap(), 2);
+        assert_eq!(dao.get_house(created.id).unwrap().rooms, bigger.rooms);
+        // Only a new price is added to the history
+        assert_eq!(dao.get_price_history(created.id).unwrap().len(), 1);
+        let dearer = House {
+            price: bigger.price + 1000.0,
+            version: 2,
+            ..bigger.clone()
+        };
+        assert_eq!(dao.update_house(&dearer).unwrap(), 3);
+        assert_eq!(dao.get_price_history(created.id).unwrap().len(), 2);
+        // Saving it again without changes writes no audit entry
+        let stored = dao.get_house(created.id).unwrap();
+        assert_eq!(dao.update_house(&stored).unwrap(), 4);
+        let actions: Vec<AuditAction> = dao
+            .get_audit_log(&AuditQuery::house(created.id))
+            .unwrap()
+            .iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                AuditAction::Update,
+                AuditAction::Update,
+                AuditAction::Create
+            ]
+        );
+
+        let stored = dao.get_house(created.id).unwrap();
+        assert!(dao.delete_house(&stored).unwrap());
+        assert!(matches!(dao.get_house(created.id), Err(Error::NotFound)));
+        assert_eq!(dao.get_deleted_houses().unwrap()[0].house.id, created.id);
+        // The id stays taken while the house is in the trash
+        assert_eq!(dao.create_house(&house).unwrap().id, created.id + 1);
+        assert!(dao.restore_house(created.id).unwrap());
+        assert_eq!(dao.get_house(created.id).unwrap().street, "Calle Nueva");
+    }
+
+    #[test]
+    fn invalid_houses_are_rejected() {
+        let mut dao = Memory::demo("test");
+        let house = dao.get_house(1).unwrap();
+        let invalid = [
+            House {
+                kind: "Castillo".to_owned(),
+                ..house.clone()
+            },
+            House {
+                tags: vec!["Helipuerto".to_owned()],
+                ..house.clone()
+            },
+            House {
+                street: " ".to_owned(),
+                ..house.clone()
+            },
+            House {
+                area: -1.0,
+                ..house.clone()
+            },
+        ];
+        for house in &invalid {
+            assert!(dao.create_house(house).is_err(), "{house:?}");
+            assert!(dao.update_house(house).is_err(), "{house:?}");
+        }
+        assert_eq!(dao.get_house(1).unwrap().version, house.version);
+    }
+}
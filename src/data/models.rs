@@ -1,12 +1,16 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{AsChangeset, Insertable, Queryable};
 
-use super::schema::houses;
+use super::schema::{
+    attachments, audit_log, house_owners, house_tags, house_types, houses, leases, owners,
+    payments, price_history, tags, tenants,
+};
 
 #[derive(Debug, Queryable, AsChangeset)]
-#[diesel(table_name = houses)]
+#[diesel(table_name = houses, treat_none_as_null = true)]
 pub struct House {
     pub id: i32,
-    pub kind: String, // kind should be a FOREIGN KEY
+    pub kind_id: i32,
     pub street: String,
     pub number: i32,
     pub floor: i32,
@@ -14,12 +18,23 @@ pub struct House {
     pub rooms: i32,
     pub baths: i32,
     pub area: f32,
+    pub operation: String,
+    pub price: f64,
+    pub currency: String,
+    pub expenses: f64,
+    pub city: String,
+    pub province: String,
+    pub country: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub deleted_at: Option<NaiveDateTime>, // in the trash since then
+    pub version: i32,
 }
 
 #[derive(Debug, Insertable)]
 #[diesel(table_name = houses)]
 pub struct NewHouse {
-    pub kind: String, // kind should be a FOREIGN KEY
+    pub kind_id: i32,
     pub street: String,
     pub number: i32,
     pub floor: i32,
@@ -27,4 +42,227 @@ pub struct NewHouse {
     pub rooms: i32,
     pub baths: i32,
     pub area: f32,
+    pub operation: String,
+    pub price: f64,
+    pub currency: String,
+    pub expenses: f64,
+    pub city: String,
+    pub province: String,
+    pub country: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Queryable, AsChangeset)]
+#[diesel(table_name = house_types)]
+pub struct HouseType {
+    pub id: i32,
+    pub name: String,
+    pub retired: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = house_types)]
+pub struct NewHouseType {
+    pub name: String,
+}
+
+#[derive(Debug, Queryable, AsChangeset)]
+#[diesel(table_name = owners)]
+pub struct Owner {
+    pub id: i32,
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub tax_id: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = owners)]
+pub struct NewOwner {
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub tax_id: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Queryable, Insertable)]
+#[diesel(table_name = house_owners)]
+pub struct HouseOwner {
+    pub house_id: i32,
+    pub owner_id: i32,
+    pub share: f32,
+}
+
+#[derive(Debug, Queryable, AsChangeset)]
+#[diesel(table_name = tags)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tags)]
+pub struct NewTag {
+    pub name: String,
+}
+
+#[derive(Debug, Queryable, Insertable)]
+#[diesel(table_name = house_tags)]
+pub struct HouseTag {
+    pub house_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Debug, Queryable, AsChangeset)]
+#[diesel(table_name = tenants)]
+pub struct Tenant {
+    pub id: i32,
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub tax_id: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tenants)]
+pub struct NewTenant {
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub tax_id: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Queryable, AsChangeset)]
+#[diesel(table_name = leases)]
+pub struct Lease {
+    pub id: i32,
+    pub house_id: i32,
+    pub tenant_id: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub rent: f64,
+    pub currency: String,
+    pub deposit: f64,
+    pub index_months: i32,
+    pub index_rate: f64,
+    pub status: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = leases)]
+pub struct NewLease {
+    pub house_id: i32,
+    pub tenant_id: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub rent: f64,
+    pub currency: String,
+    pub deposit: f64,
+    pub index_months: i32,
+    pub index_rate: f64,
+    pub status: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Payment {
+    pub id: i32,
+    pub lease_id: i32,
+    pub paid_on: NaiveDate,
+    pub amount: f64,
+    pub method: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = payments)]
+pub struct NewPayment {
+    pub lease_id: i32,
+    pub paid_on: NaiveDate,
+    pub amount: f64,
+    pub method: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct PriceChange {
+    pub price: f64,
+    pub currency: String,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = price_history)]
+pub struct NewPriceChange {
+    pub house_id: i32,
+    pub price: f64,
+    pub currency: String,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub house_id: i32,
+    pub action: String,
+    pub changes: String,
+    pub user_name: String,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry {
+    pub house_id: i32,
+    pub action: String,
+    pub changes: String,
+    pub user_name: String,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Attachment {
+    pub id: i32,
+    pub house_id: i32,
+    pub hash: String,
+    pub name: String,
+    pub size: i64,
+    pub cover: bool,
+    pub added_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment {
+    pub house_id: i32,
+    pub hash: String,
+    pub name: String,
+    pub size: i64,
+    pub cover: bool,
+    pub added_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct Bounds<T> {
+    pub min: T,
+    pub max: T,
+}
+
+#[derive(Debug)]
+pub struct HouseBounds {
+    pub number: Bounds<i32>,
+    pub floor: Bounds<i32>,
+    pub postcode: Bounds<i32>,
+    pub rooms: Bounds<i32>,
+    pub baths: Bounds<i32>,
+    pub area: Bounds<f32>,
+    pub price: Bounds<f64>,
+    pub all_tags: Vec<i32>,            // the house has every one
+    pub any_tags: Vec<i32>,            // the house has at least one, when not empty
+    pub latitude: Option<Bounds<f64>>, // box around the circle of the filter
+    pub longitude: Option<Bounds<f64>>,
 }
@@ -1,25 +1,257 @@
-use diesel::{prelude::*, result::Error, SqliteConnection as Conn};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{
+    connection::SimpleConnection, prelude::*, result::Error, sqlite::Sqlite, ConnectionError,
+    SqliteConnection as Conn,
+};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
-use dotenvy::dotenv;
-use std::env;
+use super::models::{
+    Attachment, AuditEntry, House, HouseBounds, HouseOwner, HouseTag, HouseType, Lease,
+    NewAttachment, NewAuditEntry, NewHouse, NewHouseType, NewLease, NewOwner, NewPayment,
+    NewPriceChange, NewTag, NewTenant, Owner, Payment, PriceChange, Tag, Tenant,
+};
+use super::schema::houses::{self, dsl::*};
+use super::schema::{
+    attachments, audit_log, house_owners, house_tags, house_types, leases, owners, payments,
+    price_history, tags, tenants,
+};
 
-use super::models::{House, NewHouse};
-use super::schema::houses::dsl::*;
-
-pub struct RepositoryError;
+#[derive(Debug)]
+pub enum RepositoryError {
+    NotFound,
+    Query(Error),
+    Connection(ConnectionError),
+    Migration(String),
+    Conflict,                           // the row changed since it was read
+    Batch(usize, Box<RepositoryError>), // index of the change that failed
+}
 
-impl RepositoryError {
-    fn get(_: Error) -> Self {
-        RepositoryError
+impl From<Error> for RepositoryError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotFound => RepositoryError::NotFound,
+            error => RepositoryError::Query(error),
+        }
     }
 }
 
-fn run_migrations(conn: &mut Conn) {
+fn run_migrations(conn: &mut Conn) -> Result<(), RepositoryError> {
     conn.run_pending_migrations(MIGRATIONS)
-        .expect("Failed to run migrations");
+        .map(|_| ())
+        .map_err(|error| RepositoryError::Migration(error.to_string()))
+}
+
+fn bounded(bounds: &HouseBounds) -> houses::BoxedQuery<'static, Sqlite> {
+    // The houses in the trash are only listed by find_deleted()
+    let mut query = houses.filter(deleted_at.is_null()).into_boxed();
+    // One subquery for each tag that the house must have
+    for &tag in &bounds.all_tags {
+        let tagged = house_tags::table
+            .filter(house_tags::tag_id.eq(tag))
+            .select(house_tags::house_id);
+        query = query.filter(id.eq_any(tagged));
+    }
+    if !bounds.any_tags.is_empty() {
+        let tagged = house_tags::table
+            .filter(house_tags::tag_id.eq_any(bounds.any_tags.clone()))
+            .select(house_tags::house_id);
+        query = query.filter(id.eq_any(tagged));
+    }
+    // The box around the circle, the exact distance is checked by the service
+    if let Some(bounds) = &bounds.latitude {
+        query = query.filter(latitude.between(bounds.min, bounds.max));
+    }
+    if let Some(bounds) = &bounds.longitude {
+        query = query.filter(longitude.between(bounds.min, bounds.max));
+    }
+    query
+        .filter(number.between(bounds.number.min, bounds.number.max))
+        .filter(floor.between(bounds.floor.min, bounds.floor.max))
+        .filter(postcode.between(bounds.postcode.min, bounds.postcode.max))
+        .filter(rooms.between(bounds.rooms.min, bounds.rooms.max))
+        .filter(baths.between(bounds.baths.min, bounds.baths.max))
+        .filter(area.between(bounds.area.min, bounds.area.max))
+        .filter(price.between(bounds.price.min, bounds.price.max))
+}
+
+// A write that found no row: the house exists, in use or in the trash, so someone else changed it
+fn missing(conn: &mut Conn, houseid: i32) -> RepositoryError {
+    match houses.find(houseid).count().get_result::<i64>(conn) {
+        Ok(0) => RepositoryError::NotFound,
+        Ok(_) => RepositoryError::Conflict,
+        Err(error) => error.into(),
+    }
+}
+
+fn tagged(houseid: i32, tagids: &[i32]) -> Vec<HouseTag> {
+    tagids
+        .iter()
+        .map(|&tag_id| HouseTag {
+            house_id: houseid,
+            tag_id,
+        })
+        .collect()
+}
+
+// The house_id of the links and of the entry is replaced
+fn insert_house(
+    conn: &mut Conn,
+    new_house: &NewHouse,
+    mut links: Vec<HouseOwner>,
+    tagids: &[i32],
+    mut entry: NewAuditEntry,
+    now: NaiveDateTime,
+) -> Result<House, RepositoryError> {
+    // RETURNING gives the row with its id, whatever other connections insert
+    let house: House = diesel::insert_into(houses)
+        .values(new_house)
+        .get_result(conn)?;
+    for link in links.iter_mut() {
+        link.house_id = house.id;
+    }
+    diesel::insert_into(house_owners::table)
+        .values(&links)
+        .execute(conn)?;
+    diesel::insert_into(house_tags::table)
+        .values(&tagged(house.id, tagids))
+        .execute(conn)?;
+    let change = NewPriceChange {
+        house_id: house.id,
+        price: house.price,
+        currency: house.currency.clone(),
+        changed_at: now,
+    };
+    diesel::insert_into(price_history::table)
+        .values(&change)
+        .execute(conn)?;
+    entry.house_id = house.id;
+    diesel::insert_into(audit_log::table)
+        .values(&entry)
+        .execute(conn)?;
+    Ok(house)
+}
+
+// Only over the version that was read, which is increased; an update that changes nothing has no audit entry
+fn update_house(
+    conn: &mut Conn,
+    house: &mut House,
+    links: &[HouseOwner],
+    tagids: &[i32],
+    entry: Option<&NewAuditEntry>,
+    now: NaiveDateTime,
+) -> Result<bool, RepositoryError> {
+    let (old_price, old_currency) = houses
+        .find(house.id)
+        .select((price, currency))
+        .first::<(f64, String)>(conn)?;
+    let loaded = house.version;
+    house.version += 1;
+    let count = diesel::update(
+        houses
+            .find(house.id)
+            .filter(deleted_at.is_null())
+            .filter(version.eq(loaded)),
+    )
+    .set(&*house)
+    .execute(conn)?;
+    if count != 1 {
+        return Err(missing(conn, house.id));
+    }
+    diesel::delete(house_owners::table.filter(house_owners::house_id.eq(house.id)))
+        .execute(conn)?;
+    diesel::insert_into(house_owners::table)
+        .values(links)
+        .execute(conn)?;
+    diesel::delete(house_tags::table.filter(house_tags::house_id.eq(house.id))).execute(conn)?;
+    diesel::insert_into(house_tags::table)
+        .values(&tagged(house.id, tagids))
+        .execute(conn)?;
+    if old_price != house.price || old_currency != house.currency {
+        let change = NewPriceChange {
+            house_id: house.id,
+            price: house.price,
+            currency: house.currency.clone(),
+            changed_at: now,
+        };
+        diesel::insert_into(price_history::table)
+            .values(&change)
+            .execute(conn)?;
+    }
+    if let Some(entry) = entry {
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .execute(conn)?;
+    }
+    Ok(true)
+}
+
+// The house must still have the version that was read
+fn trash_house(
+    conn: &mut Conn,
+    houseid: i32,
+    loaded: i32,
+    entry: &NewAuditEntry,
+    now: NaiveDateTime,
+) -> Result<bool, RepositoryError> {
+    let count = diesel::update(
+        houses
+            .find(houseid)
+            .filter(deleted_at.is_null())
+            .filter(version.eq(loaded)),
+    )
+    .set(deleted_at.eq(now))
+    .execute(conn)?;
+    if count != 1 {
+        return Err(missing(conn, houseid));
+    }
+    diesel::insert_into(audit_log::table)
+        .values(entry)
+        .execute(conn)?;
+    Ok(true)
+}
+
+// The house must be in the trash
+fn untrash_house(
+    conn: &mut Conn,
+    houseid: i32,
+    entry: &NewAuditEntry,
+) -> Result<bool, RepositoryError> {
+    let count = diesel::update(houses.find(houseid).filter(deleted_at.is_not_null()))
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .execute(conn)?;
+    if count != 1 {
+        return Err(RepositoryError::NotFound);
+    }
+    diesel::insert_into(audit_log::table)
+        .values(entry)
+        .execute(conn)?;
+    Ok(true)
+}
+
+pub enum Change {
+    Create {
+        house: NewHouse,
+        links: Vec<HouseOwner>,
+        tagids: Vec<i32>,
+        entry: NewAuditEntry,
+    },
+    Update {
+        house: House,
+        links: Vec<HouseOwner>,
+        tagids: Vec<i32>,
+        entry: Option<NewAuditEntry>,
+    },
+    Delete {
+        houseid: i32,
+        loaded: i32, // version that was read
+        entry: NewAuditEntry,
+    },
+    Restore {
+        houseid: i32,
+        entry: NewAuditEntry,
+    },
 }
 
 pub struct Repository {
@@ -27,56 +259,553 @@ pub struct Repository {
 }
 
 impl Repository {
-    pub fn new() -> Self {
-        dotenv().ok();
-        let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let mut conn = Conn::establish(&url).expect(&format!("Error connecting to {}", url));
-        run_migrations(&mut conn);
-        Repository { conn }
+    pub fn new(url: &str) -> Result<Self, RepositoryError> {
+        let mut conn = Conn::establish(url).map_err(RepositoryError::Connection)?;
+        run_migrations(&mut conn)?;
+        // SQLite only checks the foreign keys when asked, once per connection
+        conn.batch_execute("PRAGMA foreign_keys = ON")?;
+        Ok(Repository { conn })
     }
 
-    pub fn find_all(&mut self) -> Result<Vec<House>, RepositoryError> {
-        // TODO Pagination should be implemented
-        houses
-            .order(id.asc())
-            .load::<House>(&mut self.conn)
-            // TODO Errors should be better reported
-            .map_err(RepositoryError::get)
-    }
-
-    pub fn create(&mut self, new_house: &NewHouse) -> Result<House, RepositoryError> {
-        let result = diesel::insert_into(houses)
-            .values(new_house)
-            .execute(&mut self.conn);
-        if result == Ok(1) {
-            return houses
-                .order(id.desc())
-                .first(&mut self.conn)
-                // TODO Errors should be better reported
-                .map_err(RepositoryError::get);
-        }
-        Err(RepositoryError)
-    }
-
-    pub fn update(&mut self, house: &House) -> Result<bool, RepositoryError> {
-        let result = diesel::update(houses.find(house.id))
-            .set(house)
-            .execute(&mut self.conn);
-        if result == Ok(1) {
+    pub fn find(
+        &mut self,
+        bounds: &HouseBounds,
+        offset: i64,
+        limit: Option<i64>, // None for every house after the offset
+    ) -> Result<Vec<House>, RepositoryError> {
+        let mut query = bounded(bounds).order(id.asc()).offset(offset);
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(query.load::<House>(&mut self.conn)?)
+    }
+
+    pub fn find_by_id(&mut self, houseid: i32) -> Result<House, RepositoryError> {
+        Ok(houses
+            .find(houseid)
+            .filter(deleted_at.is_null())
+            .first(&mut self.conn)?)
+    }
+
+    pub fn count(&mut self, bounds: &HouseBounds) -> Result<i64, RepositoryError> {
+        Ok(bounded(bounds).count().get_result(&mut self.conn)?)
+    }
+
+    // The first change that fails undoes the previous ones
+    pub fn apply(
+        &mut self,
+        changes: Vec<Change>,
+        now: NaiveDateTime,
+    ) -> Result<Vec<i32>, RepositoryError> {
+        self.conn.transaction(|conn| {
+            let mut ids = Vec::with_capacity(changes.len());
+            for (index, change) in changes.into_iter().enumerate() {
+                let applied = match change {
+                    Change::Create {
+                        house,
+                        links,
+                        tagids,
+                        entry,
+                    } => {
+                        insert_house(conn, &house, links, &tagids, entry, now).map(|house| house.id)
+                    }
+                    Change::Update {
+                        mut house,
+                        links,
+                        tagids,
+                        entry,
+                    } => update_house(conn, &mut house, &links, &tagids, entry.as_ref(), now)
+                        .map(|_| house.id),
+                    Change::Delete {
+                        houseid,
+                        loaded,
+                        entry,
+                    } => trash_house(conn, houseid, loaded, &entry, now).map(|_| houseid),
+                    Change::Restore { houseid, entry } => {
+                        untrash_house(conn, houseid, &entry).map(|_| houseid)
+                    }
+                };
+                ids.push(applied.map_err(|error| RepositoryError::Batch(index, Box::new(error)))?);
+            }
+            Ok(ids)
+        })
+    }
+
+    pub fn find_prices(&mut self, houseid: i32) -> Result<Vec<PriceChange>, RepositoryError> {
+        Ok(price_history::table
+            .filter(price_history::house_id.eq(houseid))
+            .order((price_history::changed_at.desc(), price_history::id.desc()))
+            .select((
+                price_history::price,
+                price_history::currency,
+                price_history::changed_at,
+            ))
+            .load::<PriceChange>(&mut self.conn)?)
+    }
+
+    pub fn find_deleted(&mut self) -> Result<Vec<House>, RepositoryError> {
+        Ok(houses
+            .filter(deleted_at.is_not_null())
+            .order((deleted_at.desc(), id.desc()))
+            .load::<House>(&mut self.conn)?)
+    }
+
+    pub fn find_deleted_by_id(&mut self, houseid: i32) -> Result<House, RepositoryError> {
+        Ok(houses
+            .find(houseid)
+            .filter(deleted_at.is_not_null())
+            .first(&mut self.conn)?)
+    }
+
+    pub fn find_expired(&mut self, before: NaiveDateTime) -> Result<Vec<i32>, RepositoryError> {
+        Ok(houses
+            .filter(deleted_at.lt(before))
+            .select(id)
+            .load::<i32>(&mut self.conn)?)
+    }
+
+    pub fn restore(
+        &mut self,
+        houseid: i32,
+        entry: &NewAuditEntry,
+    ) -> Result<bool, RepositoryError> {
+        self.conn
+            .transaction(|conn| untrash_house(conn, houseid, entry))
+    }
+
+    // The audit entries of the house are kept
+    pub fn purge(&mut self, houseid: i32, entry: &NewAuditEntry) -> Result<bool, RepositoryError> {
+        self.conn.transaction(|conn| {
+            let count = diesel::delete(houses.find(houseid).filter(deleted_at.is_not_null()))
+                .execute(conn)?;
+            if count != 1 {
+                return Err(RepositoryError::NotFound);
+            }
+            diesel::insert_into(audit_log::table)
+                .values(entry)
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+
+    pub fn find_audit(
+        &mut self,
+        houseid: Option<i32>,
+        user: Option<&str>,
+        since: Option<NaiveDateTime>,
+        limit: Option<i64>,
+    ) -> Result<Vec<AuditEntry>, RepositoryError> {
+        let mut query = audit_log::table.into_boxed();
+        if let Some(houseid) = houseid {
+            query = query.filter(audit_log::house_id.eq(houseid));
+        }
+        if let Some(user) = user {
+            query = query.filter(audit_log::user_name.eq(user.to_owned()));
+        }
+        if let Some(since) = since {
+            query = query.filter(audit_log::changed_at.ge(since));
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(query
+            .order((audit_log::changed_at.desc(), audit_log::id.desc()))
+            .load::<AuditEntry>(&mut self.conn)?)
+    }
+
+    pub fn find_attachments(&mut self, houseid: i32) -> Result<Vec<Attachment>, RepositoryError> {
+        Ok(attachments::table
+            .filter(attachments::house_id.eq(houseid))
+            .order((attachments::cover.desc(), attachments::id.asc()))
+            .load::<Attachment>(&mut self.conn)?)
+    }
+
+    pub fn find_attachment_by_id(
+        &mut self,
+        attachmentid: i32,
+    ) -> Result<Attachment, RepositoryError> {
+        Ok(attachments::table
+            .find(attachmentid)
+            .first(&mut self.conn)?)
+    }
+
+    pub fn count_hash(
+        &mut self,
+        houseid: Option<i32>,
+        content: &str,
+    ) -> Result<i64, RepositoryError> {
+        let mut query = attachments::table
+            .filter(attachments::hash.eq(content))
+            .into_boxed();
+        if let Some(houseid) = houseid {
+            query = query.filter(attachments::house_id.eq(houseid));
+        }
+        Ok(query.count().get_result(&mut self.conn)?)
+    }
+
+    pub fn has_cover(&mut self, houseid: i32) -> Result<bool, RepositoryError> {
+        let count: i64 = attachments::table
+            .filter(attachments::house_id.eq(houseid))
+            .filter(attachments::cover.eq(true))
+            .count()
+            .get_result(&mut self.conn)?;
+        Ok(count > 0)
+    }
+
+    pub fn create_attachment(
+        &mut self,
+        new_attachment: &NewAttachment,
+    ) -> Result<Attachment, RepositoryError> {
+        Ok(diesel::insert_into(attachments::table)
+            .values(new_attachment)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn delete_attachment(&mut self, attachmentid: i32) -> Result<bool, RepositoryError> {
+        let count =
+            diesel::delete(attachments::table.find(attachmentid)).execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn set_cover(&mut self, attachment: &Attachment) -> Result<bool, RepositoryError> {
+        self.conn.transaction(|conn| {
+            // Cleared first, the unique index allows one cover at any time
+            diesel::update(
+                attachments::table.filter(attachments::house_id.eq(attachment.house_id)),
+            )
+            .set(attachments::cover.eq(false))
+            .execute(conn)?;
+            let count = diesel::update(attachments::table.find(attachment.id))
+                .set(attachments::cover.eq(true))
+                .execute(conn)?;
+            if count == 1 {
+                Ok(true)
+            } else {
+                Err(RepositoryError::NotFound)
+            }
+        })
+    }
+
+    pub fn find_house_hashes(&mut self, houseid: i32) -> Result<Vec<String>, RepositoryError> {
+        Ok(attachments::table
+            .filter(attachments::house_id.eq(houseid))
+            .select(attachments::hash)
+            .load::<String>(&mut self.conn)?)
+    }
+
+    pub fn find_types(&mut self) -> Result<Vec<HouseType>, RepositoryError> {
+        Ok(house_types::table
+            .order(house_types::id.asc())
+            .load::<HouseType>(&mut self.conn)?)
+    }
+
+    pub fn find_type_by_id(&mut self, typeid: i32) -> Result<HouseType, RepositoryError> {
+        Ok(house_types::table.find(typeid).first(&mut self.conn)?)
+    }
+
+    pub fn create_type(&mut self, new_type: &NewHouseType) -> Result<HouseType, RepositoryError> {
+        Ok(diesel::insert_into(house_types::table)
+            .values(new_type)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn update_type(&mut self, house_type: &HouseType) -> Result<bool, RepositoryError> {
+        let count = diesel::update(house_types::table.find(house_type.id))
+            .set(house_type)
+            .execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn find_house_owners(
+        &mut self,
+        houseids: &[i32],
+    ) -> Result<Vec<(HouseOwner, String)>, RepositoryError> {
+        let mut links = Vec::new();
+        // SQLite limits the number of parameters of a statement
+        for chunk in houseids.chunks(500) {
+            links.extend(
+                house_owners::table
+                    .inner_join(owners::table)
+                    .filter(house_owners::house_id.eq_any(chunk))
+                    .order((house_owners::house_id, house_owners::share.desc()))
+                    .select((house_owners::all_columns, owners::name))
+                    .load::<(HouseOwner, String)>(&mut self.conn)?,
+            );
+        }
+        Ok(links)
+    }
+
+    pub fn find_house_tags(
+        &mut self,
+        houseids: &[i32],
+    ) -> Result<Vec<(i32, String)>, RepositoryError> {
+        let mut links = Vec::new();
+        // SQLite limits the number of parameters of a statement
+        for chunk in houseids.chunks(500) {
+            links.extend(
+                house_tags::table
+                    .inner_join(tags::table)
+                    .filter(house_tags::house_id.eq_any(chunk))
+                    .order((house_tags::house_id, tags::name))
+                    .select((house_tags::house_id, tags::name))
+                    .load::<(i32, String)>(&mut self.conn)?,
+            );
+        }
+        Ok(links)
+    }
+
+    pub fn find_tags(&mut self) -> Result<Vec<Tag>, RepositoryError> {
+        Ok(tags::table
+            .order(tags::name.asc())
+            .load::<Tag>(&mut self.conn)?)
+    }
+
+    pub fn find_tag_by_id(&mut self, tagid: i32) -> Result<Tag, RepositoryError> {
+        Ok(tags::table.find(tagid).first(&mut self.conn)?)
+    }
+
+    pub fn count_tag_houses(&mut self, tagid: i32) -> Result<i64, RepositoryError> {
+        Ok(house_tags::table
+            .filter(house_tags::tag_id.eq(tagid))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn create_tag(&mut self, new_tag: &NewTag) -> Result<Tag, RepositoryError> {
+        Ok(diesel::insert_into(tags::table)
+            .values(new_tag)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn update_tag(&mut self, tag: &Tag) -> Result<bool, RepositoryError> {
+        let count = diesel::update(tags::table.find(tag.id))
+            .set(tag)
+            .execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn delete_tag(&mut self, tagid: i32) -> Result<bool, RepositoryError> {
+        let count = diesel::delete(tags::table.find(tagid)).execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn find_owners(&mut self) -> Result<Vec<Owner>, RepositoryError> {
+        Ok(owners::table
+            .order(owners::name.asc())
+            .load::<Owner>(&mut self.conn)?)
+    }
+
+    pub fn count_owner_houses(&mut self, ownerid: i32) -> Result<i64, RepositoryError> {
+        Ok(house_owners::table
+            .filter(house_owners::owner_id.eq(ownerid))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn create_owner(&mut self, new_owner: &NewOwner) -> Result<Owner, RepositoryError> {
+        Ok(diesel::insert_into(owners::table)
+            .values(new_owner)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn update_owner(&mut self, owner: &Owner) -> Result<bool, RepositoryError> {
+        let count = diesel::update(owners::table.find(owner.id))
+            .set(owner)
+            .execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn delete_owner(&mut self, ownerid: i32) -> Result<bool, RepositoryError> {
+        let count = diesel::delete(owners::table.find(ownerid)).execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn find_leases(&mut self, houseid: i32) -> Result<Vec<(Lease, String)>, RepositoryError> {
+        Ok(leases::table
+            .inner_join(tenants::table)
+            .filter(leases::house_id.eq(houseid))
+            .order((leases::start_date.desc(), leases::id.desc()))
+            .select((leases::all_columns, tenants::name))
+            .load::<(Lease, String)>(&mut self.conn)?)
+    }
+
+    pub fn find_expiring(
+        &mut self,
+        until: NaiveDate,
+    ) -> Result<Vec<(Lease, String)>, RepositoryError> {
+        Ok(leases::table
+            .inner_join(tenants::table)
+            .filter(leases::status.eq("active"))
+            .filter(leases::end_date.le(until))
+            .order((leases::end_date.asc(), leases::id.asc()))
+            .select((leases::all_columns, tenants::name))
+            .load::<(Lease, String)>(&mut self.conn)?)
+    }
+
+    pub fn find_active(&mut self) -> Result<Vec<(Lease, String)>, RepositoryError> {
+        Ok(leases::table
+            .inner_join(tenants::table)
+            .filter(leases::status.eq("active"))
+            .order((leases::start_date.asc(), leases::id.asc()))
+            .select((leases::all_columns, tenants::name))
+            .load::<(Lease, String)>(&mut self.conn)?)
+    }
+
+    pub fn find_tenant_leases(
+        &mut self,
+        tenantid: i32,
+    ) -> Result<Vec<(Lease, String)>, RepositoryError> {
+        Ok(leases::table
+            .inner_join(tenants::table)
+            .filter(leases::tenant_id.eq(tenantid))
+            .order((leases::start_date.asc(), leases::id.asc()))
+            .select((leases::all_columns, tenants::name))
+            .load::<(Lease, String)>(&mut self.conn)?)
+    }
+
+    pub fn find_lease_by_id(&mut self, leaseid: i32) -> Result<(Lease, String), RepositoryError> {
+        Ok(leases::table
+            .inner_join(tenants::table)
+            .filter(leases::id.eq(leaseid))
+            .select((leases::all_columns, tenants::name))
+            .first::<(Lease, String)>(&mut self.conn)?)
+    }
+
+    pub fn count_active_leases(
+        &mut self,
+        houseid: i32,
+        except: i32,
+    ) -> Result<i64, RepositoryError> {
+        Ok(leases::table
+            .filter(leases::house_id.eq(houseid))
+            .filter(leases::status.eq("active"))
+            .filter(leases::id.ne(except))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn count_house_leases(&mut self, houseid: i32) -> Result<i64, RepositoryError> {
+        Ok(leases::table
+            .filter(leases::house_id.eq(houseid))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn create_lease(&mut self, new_lease: &NewLease) -> Result<Lease, RepositoryError> {
+        Ok(diesel::insert_into(leases::table)
+            .values(new_lease)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn update_lease(&mut self, lease: &Lease) -> Result<bool, RepositoryError> {
+        let count = diesel::update(leases::table.find(lease.id))
+            .set(lease)
+            .execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn delete_lease(&mut self, leaseid: i32) -> Result<bool, RepositoryError> {
+        let count = diesel::delete(leases::table.find(leaseid)).execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn find_payments(&mut self, leaseid: i32) -> Result<Vec<Payment>, RepositoryError> {
+        Ok(payments::table
+            .filter(payments::lease_id.eq(leaseid))
+            .order((payments::paid_on.asc(), payments::id.asc()))
+            .load::<Payment>(&mut self.conn)?)
+    }
+
+    pub fn count_lease_payments(&mut self, leaseid: i32) -> Result<i64, RepositoryError> {
+        Ok(payments::table
+            .filter(payments::lease_id.eq(leaseid))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn create_payment(&mut self, new_payment: &NewPayment) -> Result<Payment, RepositoryError> {
+        Ok(diesel::insert_into(payments::table)
+            .values(new_payment)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn delete_payment(&mut self, paymentid: i32) -> Result<bool, RepositoryError> {
+        let count = diesel::delete(payments::table.find(paymentid)).execute(&mut self.conn)?;
+        if count == 1 {
+            Ok(true)
+        } else {
+            Err(RepositoryError::NotFound)
+        }
+    }
+
+    pub fn find_tenants(&mut self) -> Result<Vec<Tenant>, RepositoryError> {
+        Ok(tenants::table
+            .order(tenants::name.asc())
+            .load::<Tenant>(&mut self.conn)?)
+    }
+
+    pub fn count_tenant_leases(&mut self, tenantid: i32) -> Result<i64, RepositoryError> {
+        Ok(leases::table
+            .filter(leases::tenant_id.eq(tenantid))
+            .count()
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn create_tenant(&mut self, new_tenant: &NewTenant) -> Result<Tenant, RepositoryError> {
+        Ok(diesel::insert_into(tenants::table)
+            .values(new_tenant)
+            .get_result(&mut self.conn)?)
+    }
+
+    pub fn update_tenant(&mut self, tenant: &Tenant) -> Result<bool, RepositoryError> {
+        let count = diesel::update(tenants::table.find(tenant.id))
+            .set(tenant)
+            .execute(&mut self.conn)?;
+        if count == 1 {
             Ok(true)
         } else {
-            // TODO Errors should be better reported
-            Err(RepositoryError)
+            Err(RepositoryError::NotFound)
         }
     }
 
-    pub fn delete(&mut self, houseid: i32) -> Result<bool, RepositoryError> {
-        let result = diesel::delete(houses.find(houseid)).execute(&mut self.conn);
-        if result == Ok(1) {
+    pub fn delete_tenant(&mut self, tenantid: i32) -> Result<bool, RepositoryError> {
+        let count = diesel::delete(tenants::table.find(tenantid)).execute(&mut self.conn)?;
+        if count == 1 {
             Ok(true)
         } else {
-            // TODO Errors should be better reported
-            Err(RepositoryError)
+            Err(RepositoryError::NotFound)
         }
     }
 }
@@ -1,9 +1,55 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachments (id) {
+        id -> Integer,
+        house_id -> Integer,
+        hash -> Text,
+        name -> Text,
+        size -> BigInt,
+        cover -> Bool,
+        added_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        house_id -> Integer,
+        action -> Text,
+        changes -> Text,
+        user_name -> Text,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    house_owners (house_id, owner_id) {
+        house_id -> Integer,
+        owner_id -> Integer,
+        share -> Float,
+    }
+}
+
+diesel::table! {
+    house_tags (house_id, tag_id) {
+        house_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    house_types (id) {
+        id -> Integer,
+        name -> Text,
+        retired -> Bool,
+    }
+}
+
 diesel::table! {
     houses (id) {
         id -> Integer,
-        kind -> Text,
+        kind_id -> Integer,
         street -> Text,
         number -> Integer,
         floor -> Integer,
@@ -11,5 +57,108 @@ diesel::table! {
         rooms -> Integer,
         baths -> Integer,
         area -> Float,
+        operation -> Text,
+        price -> Double,
+        currency -> Text,
+        expenses -> Double,
+        city -> Text,
+        province -> Text,
+        country -> Text,
+        latitude -> Nullable<Double>,
+        longitude -> Nullable<Double>,
+        deleted_at -> Nullable<Timestamp>,
+        version -> Integer,
+    }
+}
+
+diesel::table! {
+    leases (id) {
+        id -> Integer,
+        house_id -> Integer,
+        tenant_id -> Integer,
+        start_date -> Date,
+        end_date -> Date,
+        rent -> Double,
+        currency -> Text,
+        deposit -> Double,
+        index_months -> Integer,
+        index_rate -> Double,
+        status -> Text,
     }
 }
+
+diesel::table! {
+    owners (id) {
+        id -> Integer,
+        name -> Text,
+        phone -> Text,
+        email -> Text,
+        tax_id -> Text,
+        notes -> Text,
+    }
+}
+
+diesel::table! {
+    payments (id) {
+        id -> Integer,
+        lease_id -> Integer,
+        paid_on -> Date,
+        amount -> Double,
+        method -> Text,
+        notes -> Text,
+    }
+}
+
+diesel::table! {
+    price_history (id) {
+        id -> Integer,
+        house_id -> Integer,
+        price -> Double,
+        currency -> Text,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    tenants (id) {
+        id -> Integer,
+        name -> Text,
+        phone -> Text,
+        email -> Text,
+        tax_id -> Text,
+        notes -> Text,
+    }
+}
+
+diesel::joinable!(attachments -> houses (house_id));
+diesel::joinable!(house_owners -> houses (house_id));
+diesel::joinable!(house_owners -> owners (owner_id));
+diesel::joinable!(house_tags -> houses (house_id));
+diesel::joinable!(house_tags -> tags (tag_id));
+diesel::joinable!(houses -> house_types (kind_id));
+diesel::joinable!(leases -> houses (house_id));
+diesel::joinable!(leases -> tenants (tenant_id));
+diesel::joinable!(payments -> leases (lease_id));
+diesel::joinable!(price_history -> houses (house_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    audit_log,
+    house_owners,
+    house_tags,
+    house_types,
+    houses,
+    leases,
+    owners,
+    payments,
+    price_history,
+    tags,
+    tenants,
+);
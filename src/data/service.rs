@@ -1,63 +1,731 @@
-use super::models::{House as DbHouse, NewHouse as DbNewHouse};
-use super::repository::Repository;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::base::{Error, House, DAO};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+use super::models::{
+    Attachment as DbAttachment, AuditEntry as DbAuditEntry, Bounds, House as DbHouse, HouseBounds,
+    HouseOwner, HouseType as DbHouseType, Lease as DbLease, NewAttachment as DbNewAttachment,
+    NewAuditEntry as DbNewAuditEntry, NewHouse as DbNewHouse, NewHouseType as DbNewHouseType,
+    NewLease as DbNewLease, NewOwner as DbNewOwner, NewPayment as DbNewPayment, NewTag as DbNewTag,
+    NewTenant as DbNewTenant, Owner as DbOwner, Payment as DbPayment, PriceChange as DbPriceChange,
+    Tag as DbTag, Tenant as DbTenant,
+};
+use super::repository::{Change as DbChange, Repository, RepositoryError};
+use super::store::Store;
+
+use crate::base::{
+    Attachment, AuditAction, AuditEntry, AuditQuery, Change, Deleted, Error, Filter, House,
+    HousePage, HouseType, Lease, LeaseStatus, Owner, Ownership, Page, Payment, PriceChange, Range,
+    Tag, Tenant, DAO,
+};
+
+impl From<RepositoryError> for Error {
+    fn from(error: RepositoryError) -> Self {
+        match error {
+            RepositoryError::NotFound => Error::NotFound,
+            RepositoryError::Connection(error) => Error::Open(error.to_string()),
+            RepositoryError::Migration(cause) => Error::Migration(cause),
+            RepositoryError::Conflict => Error::Conflict,
+            RepositoryError::Batch(index, error) => Error::Batch(index, Box::new((*error).into())),
+            RepositoryError::Query(error) => match error {
+                DieselError::DatabaseError(kind, info) => {
+                    let cause = info.message().to_owned();
+                    match kind {
+                        DatabaseErrorKind::UniqueViolation
+                        | DatabaseErrorKind::ForeignKeyViolation
+                        | DatabaseErrorKind::NotNullViolation
+                        | DatabaseErrorKind::CheckViolation => Error::Constraint(cause),
+                        DatabaseErrorKind::ClosedConnection => Error::Connection(cause),
+                        // SQLite reports SQLITE_BUSY and SQLITE_LOCKED without a specific kind
+                        _ if cause.contains("locked") => Error::Locked(cause),
+                        _ => Error::Database(cause),
+                    }
+                }
+                DieselError::BrokenTransactionManager => Error::Connection(error.to_string()),
+                error => Error::Database(error.to_string()),
+            },
+        }
+    }
+}
+
+// The kinds, owners and tags that the changes of a batch refer to, read once for all of them
+struct Catalogue {
+    kinds: Vec<DbHouseType>,
+    owners: Vec<DbOwner>,
+    tags: Vec<DbTag>,
+}
+
+impl Catalogue {
+    fn kind_id(&self, kind: &str) -> Result<i32, Error> {
+        self.kinds
+            .iter()
+            .find(|house_type| house_type.name == kind)
+            .map(|house_type| house_type.id)
+            .ok_or_else(|| HouseType::unknown(kind))
+    }
+
+    fn tag_ids(&self, names: &[String]) -> Result<Vec<i32>, Error> {
+        let mut ids = Vec::new();
+        for name in names {
+            let tag = self
+                .tags
+                .iter()
+                .find(|tag| Tag::same(&tag.name, name))
+                .ok_or_else(|| Tag::unknown(name))?;
+            if !ids.contains(&tag.id) {
+                ids.push(tag.id);
+            }
+        }
+        Ok(ids)
+    }
+
+    // The house as it is read back, for the audit log
+    fn stored(&self, house: &House) -> House {
+        let mut stored = house.clone();
+        for ownership in stored.owners.iter_mut() {
+            if let Some(owner) = self
+                .owners
+                .iter()
+                .find(|owner| owner.id == ownership.owner_id)
+            {
+                ownership.name.clone_from(&owner.name);
+            }
+        }
+        for name in stored.tags.iter_mut() {
+            if let Some(tag) = self.tags.iter().find(|tag| Tag::same(&tag.name, name)) {
+                name.clone_from(&tag.name);
+            }
+        }
+        stored.owners.sort_by(|a, b| b.share.total_cmp(&a.share));
+        stored.tags.sort();
+        stored.tags.dedup();
+        stored
+    }
+}
 
 pub struct Service {
     repository: Repository,
+    store: Store,
+    user: String, // name written in the audit log
 }
 
 impl Service {
-    pub fn new() -> Self {
-        Service {
-            repository: Repository::new(),
+    // `create` allows a new empty database when it does not exist
+    pub fn open(path: &Path, create: bool, user: &str) -> Result<Self, Error> {
+        if !path.exists() {
+            if !create {
+                return Err(Error::Open(format!(
+                    "el archivo «{}» no existe",
+                    path.display()
+                )));
+            }
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir).map_err(|error| Error::Open(error.to_string()))?;
+            }
+        } else if !path.is_file() {
+            return Err(Error::Open(format!(
+                "«{}» no es un archivo",
+                path.display()
+            )));
+        }
+        Ok(Service {
+            repository: Repository::new(&path.to_string_lossy())?,
+            store: Store::beside(path),
+            user: user.to_owned(),
+        })
+    }
+
+    fn catalogue(&mut self) -> Result<Catalogue, Error> {
+        Ok(Catalogue {
+            kinds: self.repository.find_types()?,
+            owners: self.repository.find_owners()?,
+            tags: self.repository.find_tags()?,
+        })
+    }
+
+    fn prepare(
+        &mut self,
+        change: &Change,
+        catalogue: &Catalogue,
+        now: NaiveDateTime,
+    ) -> Result<DbChange, Error> {
+        match change {
+            Change::Create(house) => {
+                house.validate()?;
+                let kind_id = catalogue.kind_id(&house.kind)?;
+                let stored = catalogue.stored(house);
+                Ok(DbChange::Create {
+                    house: (house, kind_id).into(),
+                    links: links(house),
+                    tagids: catalogue.tag_ids(&house.tags)?,
+                    entry: self.audit(-1, AuditAction::Create, None, Some(&stored), now),
+                })
+            }
+            Change::Update(house) => {
+                house.validate()?;
+                let kind_id = catalogue.kind_id(&house.kind)?;
+                let before = self.current(house)?;
+                let after = catalogue.stored(house);
+                let entry = self.audit(
+                    house.id,
+                    AuditAction::Update,
+                    Some(&before),
+                    Some(&after),
+                    now,
+                );
+                Ok(DbChange::Update {
+                    house: (house, kind_id).into(),
+                    links: links(house),
+                    tagids: catalogue.tag_ids(&house.tags)?,
+                    // Saving a house without changes leaves no trace in the log
+                    entry: (entry.changes != "[]").then_some(entry),
+                })
+            }
+            Change::Delete(house) => {
+                // The ended leases go with the house, an active one must end first
+                if self.repository.count_active_leases(house.id, -1)? > 0 {
+                    return Err(Lease::still_active());
+                }
+                // The house goes to the trash with its rows and files, purge_house() deletes them
+                let before = self.current(house)?;
+                Ok(DbChange::Delete {
+                    houseid: house.id,
+                    loaded: house.version,
+                    entry: self.audit(house.id, AuditAction::Delete, Some(&before), None, now),
+                })
+            }
+            Change::Restore(house) => Ok(DbChange::Restore {
+                houseid: house.id,
+                entry: self.restore_entry(house.id, now)?,
+            }),
         }
     }
+
+    // The repository checks the version again on writing
+    fn current(&mut self, house: &House) -> Result<House, Error> {
+        match self.get_house(house.id) {
+            Ok(current) if current.version == house.version => Ok(current),
+            Ok(_) => Err(Error::Conflict),
+            Err(Error::NotFound) if self.repository.find_deleted_by_id(house.id).is_ok() => {
+                Err(Error::Conflict)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    // The house comes back as it was when it was sent to the trash
+    fn restore_entry(&mut self, id: i32, now: NaiveDateTime) -> Result<DbNewAuditEntry, Error> {
+        let house = self.repository.find_deleted_by_id(id)?;
+        let house = self.complete(vec![house])?.remove(0);
+        Ok(self.audit(id, AuditAction::Restore, None, Some(&house), now))
+    }
+
+    // A batch of one change, whose error is given as it is
+    fn apply_one(&mut self, change: Change) -> Result<i32, Error> {
+        match self.apply_batch(&[change]) {
+            Ok(ids) => Ok(ids[0]),
+            Err(Error::Batch(_, error)) => Err(*error),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn audit(
+        &self,
+        house_id: i32,
+        action: AuditAction,
+        before: Option<&House>,
+        after: Option<&House>,
+        now: NaiveDateTime,
+    ) -> DbNewAuditEntry {
+        let changes = AuditEntry::diff(before, after);
+        DbNewAuditEntry {
+            house_id,
+            action: action.code().to_owned(),
+            // A list of strings is always serializable
+            changes: serde_json::to_string(&changes).unwrap_or_default(),
+            user_name: self.user.clone(),
+            changed_at: now,
+        }
+    }
+
+    fn kind_names(&mut self) -> Result<HashMap<i32, String>, Error> {
+        let types = self.repository.find_types()?;
+        Ok(types.into_iter().map(|t| (t.id, t.name)).collect())
+    }
+
+    // An unknown name gets an id that no house has
+    fn filter_tag_ids(&mut self, names: &[String]) -> Result<Vec<i32>, Error> {
+        let tags = self.repository.find_tags()?;
+        Ok(names
+            .iter()
+            .map(|name| {
+                tags.iter()
+                    .find(|tag| Tag::same(&tag.name, name))
+                    .map_or(-1, |tag| tag.id)
+            })
+            .collect())
+    }
+
+    fn check_active_lease(&mut self, lease: &Lease) -> Result<(), Error> {
+        // Checked before the unique index, which does not say what went wrong
+        if lease.status == LeaseStatus::Active
+            && self
+                .repository
+                .count_active_leases(lease.house_id, lease.id)?
+                > 0
+        {
+            return Err(Lease::already_active());
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, hashes: &[String]) -> Result<(), Error> {
+        for hash in hashes {
+            if self.repository.count_hash(None, hash)? == 0 {
+                self.store.remove(hash)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, houses: Vec<DbHouse>) -> Result<Vec<House>, Error> {
+        let kinds = self.kind_names()?;
+        let ids: Vec<i32> = houses.iter().map(|house| house.id).collect();
+        let mut owners: HashMap<i32, Vec<Ownership>> = HashMap::new();
+        for (link, name) in self.repository.find_house_owners(&ids)? {
+            owners.entry(link.house_id).or_default().push(Ownership {
+                owner_id: link.owner_id,
+                name,
+                share: link.share,
+            });
+        }
+        let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+        for (house_id, name) in self.repository.find_house_tags(&ids)? {
+            tags.entry(house_id).or_default().push(name);
+        }
+        Ok(houses
+            .into_iter()
+            .map(|house| {
+                // The foreign key guarantees that the kind exists
+                let kind = kinds.get(&house.kind_id).cloned().unwrap_or_default();
+                let owners = owners.remove(&house.id).unwrap_or_default();
+                let tags = tags.remove(&house.id).unwrap_or_default();
+                House {
+                    owners,
+                    tags,
+                    ..(house, kind).into()
+                }
+            })
+            .collect())
+    }
 }
 
 impl DAO for Service {
-    fn get_houses(&mut self) -> Result<Vec<House>, Error> {
-        let houses = self.repository.find_all();
-        match houses {
-            Ok(houses) => {
-                let houses: Vec<House> = convert_vector(houses);
-                Ok(houses)
-            }
-            // TODO Errors should be better reported
-            Err(_) => Err(Error),
+    fn get_houses(&mut self, filter: &Filter, page: Page) -> Result<HousePage, Error> {
+        let mut bounds: HouseBounds = filter.into();
+        bounds.all_tags = self.filter_tag_ids(&filter.all_tags)?;
+        bounds.any_tags = self.filter_tag_ids(&filter.any_tags)?;
+        if let Some(near) = &filter.near {
+            near.validate()?;
+        }
+        if filter.in_rust() {
+            // The fuzzy text match and the distance have no SQL equivalent, so the page is cut after them
+            let houses = self.repository.find(&bounds, 0, None)?;
+            let houses = self.complete(houses)?;
+            let mut houses: Vec<House> = houses.into_iter().filter(|h| filter.valid(h)).collect();
+            filter.sort(&mut houses);
+            let total = houses.len() as i64;
+            let houses = page.cut(houses);
+            Ok(HousePage { houses, total })
+        } else {
+            let total = self.repository.count(&bounds)?;
+            let houses = self.repository.find(&bounds, page.offset, page.limit)?;
+            Ok(HousePage {
+                houses: self.complete(houses)?,
+                total,
+            })
         }
     }
 
+    fn get_house(&mut self, id: i32) -> Result<House, Error> {
+        let house = self.repository.find_by_id(id)?;
+        Ok(self.complete(vec![house])?.remove(0))
+    }
+
     fn create_house(&mut self, house: &House) -> Result<House, Error> {
-        let house: DbNewHouse = house.into();
-        let house = self.repository.create(&house);
-        match house {
-            Ok(house) => {
-                let house: House = house.into();
-                Ok(house)
+        let id = self.apply_one(Change::Create(house.clone()))?;
+        self.get_house(id)
+    }
+
+    fn update_house(&mut self, house: &House) -> Result<i32, Error> {
+        self.apply_one(Change::Update(house.clone()))?;
+        Ok(house.version + 1)
+    }
+
+    fn delete_house(&mut self, house: &House) -> Result<bool, Error> {
+        self.apply_one(Change::Delete(house.clone()))?;
+        Ok(true)
+    }
+
+    fn apply_batch(&mut self, changes: &[Change]) -> Result<Vec<i32>, Error> {
+        let now = Local::now().naive_local();
+        let catalogue = self.catalogue()?;
+        let mut prepared = Vec::with_capacity(changes.len());
+        for (index, change) in changes.iter().enumerate() {
+            let change = self
+                .prepare(change, &catalogue, now)
+                .map_err(|error| Error::Batch(index, Box::new(error)))?;
+            prepared.push(change);
+        }
+        Ok(self.repository.apply(prepared, now)?)
+    }
+
+    fn get_deleted_houses(&mut self) -> Result<Vec<Deleted>, Error> {
+        let houses = self.repository.find_deleted()?;
+        // Every row of the trash has the time, complete() keeps the order
+        let times: Vec<NaiveDateTime> = houses
+            .iter()
+            .map(|house| house.deleted_at.unwrap_or_default())
+            .collect();
+        let houses = self.complete(houses)?;
+        Ok(houses
+            .into_iter()
+            .zip(times)
+            .map(|(house, deleted_at)| Deleted { house, deleted_at })
+            .collect())
+    }
+
+    fn restore_house(&mut self, id: i32) -> Result<bool, Error> {
+        let entry = self.restore_entry(id, Local::now().naive_local())?;
+        Ok(self.repository.restore(id, &entry)?)
+    }
+
+    fn purge_house(&mut self, id: i32) -> Result<bool, Error> {
+        let house = self.repository.find_deleted_by_id(id)?;
+        // leases.house_id has no ON DELETE CASCADE, the payments would be lost with them
+        let count = self.repository.count_house_leases(id)?;
+        if count > 0 {
+            return Err(Lease::in_house(count));
+        }
+        let house = self.complete(vec![house])?.remove(0);
+        // The rows go with the house, ON DELETE CASCADE, but the files are outside the database
+        let hashes = self.repository.find_house_hashes(id)?;
+        let entry = self.audit(
+            id,
+            AuditAction::Purge,
+            Some(&house),
+            None,
+            Local::now().naive_local(),
+        );
+        self.repository.purge(id, &entry)?;
+        self.release(&hashes)?;
+        Ok(true)
+    }
+
+    fn purge_deleted(&mut self, before: NaiveDateTime) -> Result<usize, Error> {
+        let mut count = 0;
+        for id in self.repository.find_expired(before)? {
+            // The houses with leases stay in the trash, purge_house() says why
+            if self.repository.count_house_leases(id)? == 0 {
+                self.purge_house(id)?;
+                count += 1;
             }
-            // TODO Errors should be better reported
-            Err(_) => Err(Error),
         }
+        Ok(count)
+    }
+
+    fn get_price_history(&mut self, id: i32) -> Result<Vec<PriceChange>, Error> {
+        Ok(convert_vector(self.repository.find_prices(id)?))
+    }
+
+    fn get_audit_log(&mut self, query: &AuditQuery) -> Result<Vec<AuditEntry>, Error> {
+        let since = query.since.and_then(|date| date.and_hms_opt(0, 0, 0));
+        let entries = self.repository.find_audit(
+            query.house_id,
+            query.user.as_deref(),
+            since,
+            query.limit,
+        )?;
+        Ok(convert_vector(entries))
+    }
+
+    fn get_leases(&mut self, house_id: i32) -> Result<Vec<Lease>, Error> {
+        Ok(convert_vector(self.repository.find_leases(house_id)?))
     }
 
-    fn update_house(&mut self, house: &House) -> Result<bool, Error> {
-        let house: DbHouse = house.into();
-        // TODO Errors should be better reported
-        self.repository.update(&house).map_err(|_| Error)
+    fn get_expiring_leases(&mut self, until: NaiveDate) -> Result<Vec<Lease>, Error> {
+        Ok(convert_vector(self.repository.find_expiring(until)?))
     }
 
-    fn delete_house(&mut self, id: i32) -> Result<bool, Error> {
-        // TODO Errors should be better reported
-        self.repository.delete(id).map_err(|_| Error)
+    fn create_lease(&mut self, lease: &Lease) -> Result<Lease, Error> {
+        lease.validate()?;
+        self.check_active_lease(lease)?;
+        let new_lease: DbNewLease = lease.into();
+        let created = self.repository.create_lease(&new_lease)?;
+        Ok(self.repository.find_lease_by_id(created.id)?.into())
     }
+
+    fn update_lease(&mut self, lease: &Lease) -> Result<bool, Error> {
+        lease.validate()?;
+        self.check_active_lease(lease)?;
+        let lease: DbLease = lease.into();
+        Ok(self.repository.update_lease(&lease)?)
+    }
+
+    fn delete_lease(&mut self, id: i32) -> Result<bool, Error> {
+        let count = self.repository.count_lease_payments(id)?;
+        if count > 0 {
+            return Err(Lease::with_payments(count));
+        }
+        Ok(self.repository.delete_lease(id)?)
+    }
+
+    fn get_active_leases(&mut self) -> Result<Vec<Lease>, Error> {
+        Ok(convert_vector(self.repository.find_active()?))
+    }
+
+    fn get_tenant_leases(&mut self, tenant_id: i32) -> Result<Vec<Lease>, Error> {
+        Ok(convert_vector(
+            self.repository.find_tenant_leases(tenant_id)?,
+        ))
+    }
+
+    fn get_payments(&mut self, lease_id: i32) -> Result<Vec<Payment>, Error> {
+        Ok(convert_vector(self.repository.find_payments(lease_id)?))
+    }
+
+    fn create_payment(&mut self, payment: &Payment) -> Result<Payment, Error> {
+        payment.validate()?;
+        let payment: DbNewPayment = payment.into();
+        Ok(self.repository.create_payment(&payment)?.into())
+    }
+
+    fn delete_payment(&mut self, id: i32) -> Result<bool, Error> {
+        Ok(self.repository.delete_payment(id)?)
+    }
+
+    fn get_attachments(&mut self, house_id: i32) -> Result<Vec<Attachment>, Error> {
+        Ok(convert_vector(self.repository.find_attachments(house_id)?))
+    }
+
+    fn add_attachment(&mut self, house_id: i32, path: &Path) -> Result<Attachment, Error> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let name = name
+            .ok_or_else(|| Error::Validation(format!("«{}» no es un archivo", path.display())))?;
+        let (hash, size) = self.store.put(path)?;
+        if self.repository.count_hash(Some(house_id), &hash)? > 0 {
+            return Err(Attachment::duplicate(&name));
+        }
+        let mut attachment = Attachment {
+            id: -1,
+            house_id,
+            hash,
+            name,
+            size,
+            cover: false,
+            added_at: Local::now().naive_local(),
+        };
+        // The first photo is the cover until another one is chosen
+        attachment.cover = attachment.is_image() && !self.repository.has_cover(house_id)?;
+        let new_attachment: DbNewAttachment = (&attachment).into();
+        match self.repository.create_attachment(&new_attachment) {
+            Ok(created) => Ok(created.into()),
+            Err(error) => {
+                self.release(&[attachment.hash])?;
+                Err(error.into())
+            }
+        }
+    }
+
+    fn remove_attachment(&mut self, id: i32) -> Result<bool, Error> {
+        let attachment = self.repository.find_attachment_by_id(id)?;
+        self.repository.delete_attachment(id)?;
+        self.release(&[attachment.hash])?;
+        Ok(true)
+    }
+
+    fn set_cover(&mut self, id: i32) -> Result<bool, Error> {
+        let attachment = self.repository.find_attachment_by_id(id)?;
+        Ok(self.repository.set_cover(&attachment)?)
+    }
+
+    fn attachment_path(&self, attachment: &Attachment) -> PathBuf {
+        self.store.path(&attachment.hash)
+    }
+
+    fn get_house_types(&mut self) -> Result<Vec<HouseType>, Error> {
+        Ok(convert_vector(self.repository.find_types()?))
+    }
+
+    fn create_house_type(&mut self, name: &str) -> Result<HouseType, Error> {
+        HouseType::validate_name(name)?;
+        let new_type = DbNewHouseType {
+            name: name.trim().to_owned(),
+        };
+        Ok(self.repository.create_type(&new_type)?.into())
+    }
+
+    fn rename_house_type(&mut self, id: i32, name: &str) -> Result<bool, Error> {
+        HouseType::validate_name(name)?;
+        let mut house_type = self.repository.find_type_by_id(id)?;
+        house_type.name = name.trim().to_owned();
+        Ok(self.repository.update_type(&house_type)?)
+    }
+
+    fn retire_house_type(&mut self, id: i32, retired: bool) -> Result<bool, Error> {
+        let mut house_type = self.repository.find_type_by_id(id)?;
+        house_type.retired = retired;
+        Ok(self.repository.update_type(&house_type)?)
+    }
+
+    fn get_tags(&mut self) -> Result<Vec<Tag>, Error> {
+        Ok(convert_vector(self.repository.find_tags()?))
+    }
+
+    fn create_tag(&mut self, name: &str) -> Result<Tag, Error> {
+        Tag::validate_name(name)?;
+        let new_tag = DbNewTag {
+            name: name.trim().to_owned(),
+        };
+        Ok(self.repository.create_tag(&new_tag)?.into())
+    }
+
+    fn rename_tag(&mut self, id: i32, name: &str) -> Result<bool, Error> {
+        Tag::validate_name(name)?;
+        let mut tag = self.repository.find_tag_by_id(id)?;
+        tag.name = name.trim().to_owned();
+        Ok(self.repository.update_tag(&tag)?)
+    }
+
+    fn delete_tag(&mut self, id: i32) -> Result<bool, Error> {
+        // Checked before the foreign key, which does not say what went wrong
+        let count = self.repository.count_tag_houses(id)?;
+        if count > 0 {
+            return Err(Tag::in_use(count));
+        }
+        Ok(self.repository.delete_tag(id)?)
+    }
+
+    fn get_owners(&mut self) -> Result<Vec<Owner>, Error> {
+        Ok(convert_vector(self.repository.find_owners()?))
+    }
+
+    fn create_owner(&mut self, owner: &Owner) -> Result<Owner, Error> {
+        owner.validate()?;
+        let owner: DbNewOwner = owner.into();
+        Ok(self.repository.create_owner(&owner)?.into())
+    }
+
+    fn update_owner(&mut self, owner: &Owner) -> Result<bool, Error> {
+        owner.validate()?;
+        let owner: DbOwner = owner.into();
+        Ok(self.repository.update_owner(&owner)?)
+    }
+
+    fn delete_owner(&mut self, id: i32) -> Result<bool, Error> {
+        // Checked before the foreign key, which does not say what went wrong
+        let count = self.repository.count_owner_houses(id)?;
+        if count > 0 {
+            return Err(Owner::in_use(count));
+        }
+        Ok(self.repository.delete_owner(id)?)
+    }
+
+    fn get_tenants(&mut self) -> Result<Vec<Tenant>, Error> {
+        Ok(convert_vector(self.repository.find_tenants()?))
+    }
+
+    fn create_tenant(&mut self, tenant: &Tenant) -> Result<Tenant, Error> {
+        tenant.validate()?;
+        let tenant: DbNewTenant = tenant.into();
+        Ok(self.repository.create_tenant(&tenant)?.into())
+    }
+
+    fn update_tenant(&mut self, tenant: &Tenant) -> Result<bool, Error> {
+        tenant.validate()?;
+        let tenant: DbTenant = tenant.into();
+        Ok(self.repository.update_tenant(&tenant)?)
+    }
+
+    fn delete_tenant(&mut self, id: i32) -> Result<bool, Error> {
+        let count = self.repository.count_tenant_leases(id)?;
+        if count > 0 {
+            return Err(Tenant::in_use(count));
+        }
+        Ok(self.repository.delete_tenant(id)?)
+    }
+}
+
+fn links(house: &House) -> Vec<HouseOwner> {
+    house
+        .owners
+        .iter()
+        .map(|ownership| HouseOwner {
+            house_id: house.id,
+            owner_id: ownership.owner_id,
+            share: ownership.share,
+        })
+        .collect()
 }
 
-impl From<&House> for DbHouse {
-    fn from(house: &House) -> Self {
+impl<T: PartialOrd + FromStr + Copy> From<&Range<T>> for Bounds<T> {
+    fn from(range: &Range<T>) -> Self {
+        Bounds {
+            min: range.min,
+            max: range.max,
+        }
+    }
+}
+
+// The REAL column keeps doubles, such as the 80.2 of the seeded houses, which are past the f32 that
+// the filter parsed (80.1999969), so the bounds are widened by their rounding error
+fn widened(range: &Range<f32>) -> Bounds<f32> {
+    let slack = |value: f32| value.abs() * f32::EPSILON;
+    Bounds {
+        min: range.min - slack(range.min),
+        max: (range.max + slack(range.max)).min(f32::MAX),
+    }
+}
+
+impl From<&Filter> for HouseBounds {
+    fn from(filter: &Filter) -> Self {
+        let (latitude, longitude) = match filter.near.as_ref().and_then(|near| near.bounding_box())
+        {
+            Some((latitude, longitude)) => {
+                (Some((&latitude).into()), longitude.as_ref().map(Into::into))
+            }
+            None => (None, None),
+        };
+        HouseBounds {
+            number: (&filter.number).into(),
+            floor: (&filter.floor).into(),
+            postcode: (&filter.postcode).into(),
+            rooms: (&filter.rooms).into(),
+            baths: (&filter.baths).into(),
+            area: widened(&filter.area),
+            price: (&filter.price).into(),
+            // The names are looked up by the service
+            all_tags: Vec::new(),
+            any_tags: Vec::new(),
+            latitude,
+            longitude,
+        }
+    }
+}
+
+impl From<(&House, i32)> for DbHouse {
+    fn from((house, kind_id): (&House, i32)) -> Self {
         DbHouse {
             id: house.id,
-            kind: house.kind.clone(),
+            kind_id,
             street: house.street.clone(),
             number: house.number,
             floor: house.floor,
@@ -65,14 +733,25 @@ impl From<&House> for DbHouse {
             rooms: house.rooms,
             baths: house.baths,
             area: house.area,
+            operation: house.operation.code().to_owned(),
+            price: house.price,
+            currency: house.currency.clone(),
+            expenses: house.expenses,
+            city: house.city.clone(),
+            province: house.province.clone(),
+            country: house.country.clone(),
+            latitude: house.latitude,
+            longitude: house.longitude,
+            deleted_at: None, // only the houses in use are updated
+            version: house.version,
         }
     }
 }
 
-impl From<&House> for DbNewHouse {
-    fn from(house: &House) -> Self {
+impl From<(&House, i32)> for DbNewHouse {
+    fn from((house, kind_id): (&House, i32)) -> Self {
         DbNewHouse {
-            kind: house.kind.clone(),
+            kind_id,
             street: house.street.clone(),
             number: house.number,
             floor: house.floor,
@@ -80,15 +759,24 @@ impl From<&House> for DbNewHouse {
             rooms: house.rooms,
             baths: house.baths,
             area: house.area,
+            operation: house.operation.code().to_owned(),
+            price: house.price,
+            currency: house.currency.clone(),
+            expenses: house.expenses,
+            city: house.city.clone(),
+            province: house.province.clone(),
+            country: house.country.clone(),
+            latitude: house.latitude,
+            longitude: house.longitude,
         }
     }
 }
 
-impl From<DbHouse> for House {
-    fn from(house: DbHouse) -> Self {
+impl From<(DbHouse, String)> for House {
+    fn from((house, kind): (DbHouse, String)) -> Self {
         House {
             id: house.id,
-            kind: house.kind,
+            kind,
             street: house.street,
             number: house.number,
             floor: house.floor,
@@ -96,22 +784,245 @@ impl From<DbHouse> for House {
             rooms: house.rooms,
             baths: house.baths,
             area: house.area,
+            owners: Vec::new(),
+            // The CHECK constraint only allows the known codes
+            operation: house.operation.parse().unwrap_or_default(),
+            price: house.price,
+            currency: house.currency,
+            expenses: house.expenses,
+            tags: Vec::new(),
+            city: house.city,
+            province: house.province,
+            country: house.country,
+            latitude: house.latitude,
+            longitude: house.longitude,
+            version: house.version,
         }
     }
 }
 
-impl From<DbNewHouse> for House {
-    fn from(house: DbNewHouse) -> Self {
-        House {
-            id: -1,
-            kind: house.kind,
-            street: house.street,
-            number: house.number,
-            floor: house.floor,
-            postcode: house.postcode,
-            rooms: house.rooms,
-            baths: house.baths,
-            area: house.area,
+impl From<DbHouseType> for HouseType {
+    fn from(house_type: DbHouseType) -> Self {
+        HouseType {
+            id: house_type.id,
+            name: house_type.name,
+            retired: house_type.retired,
+        }
+    }
+}
+
+impl From<DbTag> for Tag {
+    fn from(tag: DbTag) -> Self {
+        Tag {
+            id: tag.id,
+            name: tag.name,
+        }
+    }
+}
+
+impl From<DbPriceChange> for PriceChange {
+    fn from(change: DbPriceChange) -> Self {
+        PriceChange {
+            price: change.price,
+            currency: change.currency,
+            changed_at: change.changed_at,
+        }
+    }
+}
+
+impl From<DbAuditEntry> for AuditEntry {
+    fn from(entry: DbAuditEntry) -> Self {
+        AuditEntry {
+            id: entry.id,
+            house_id: entry.house_id,
+            // The check constraint guarantees a known action
+            action: entry.action.parse().unwrap_or(AuditAction::Update),
+            changes: serde_json::from_str(&entry.changes).unwrap_or_default(),
+            user: entry.user_name,
+            changed_at: entry.changed_at,
+        }
+    }
+}
+
+impl From<DbAttachment> for Attachment {
+    fn from(attachment: DbAttachment) -> Self {
+        Attachment {
+            id: attachment.id,
+            house_id: attachment.house_id,
+            hash: attachment.hash,
+            name: attachment.name,
+            size: attachment.size,
+            cover: attachment.cover,
+            added_at: attachment.added_at,
+        }
+    }
+}
+
+impl From<&Attachment> for DbNewAttachment {
+    fn from(attachment: &Attachment) -> Self {
+        DbNewAttachment {
+            house_id: attachment.house_id,
+            hash: attachment.hash.clone(),
+            name: attachment.name.clone(),
+            size: attachment.size,
+            cover: attachment.cover,
+            added_at: attachment.added_at,
+        }
+    }
+}
+
+impl From<DbOwner> for Owner {
+    fn from(owner: DbOwner) -> Self {
+        Owner {
+            id: owner.id,
+            name: owner.name,
+            phone: owner.phone,
+            email: owner.email,
+            tax_id: owner.tax_id,
+            notes: owner.notes,
+        }
+    }
+}
+
+impl From<&Owner> for DbOwner {
+    fn from(owner: &Owner) -> Self {
+        DbOwner {
+            id: owner.id,
+            name: owner.name.clone(),
+            phone: owner.phone.clone(),
+            email: owner.email.clone(),
+            tax_id: owner.tax_id.clone(),
+            notes: owner.notes.clone(),
+        }
+    }
+}
+
+impl From<&Owner> for DbNewOwner {
+    fn from(owner: &Owner) -> Self {
+        DbNewOwner {
+            name: owner.name.clone(),
+            phone: owner.phone.clone(),
+            email: owner.email.clone(),
+            tax_id: owner.tax_id.clone(),
+            notes: owner.notes.clone(),
+        }
+    }
+}
+
+impl From<DbTenant> for Tenant {
+    fn from(tenant: DbTenant) -> Self {
+        Tenant {
+            id: tenant.id,
+            name: tenant.name,
+            phone: tenant.phone,
+            email: tenant.email,
+            tax_id: tenant.tax_id,
+            notes: tenant.notes,
+        }
+    }
+}
+
+impl From<&Tenant> for DbTenant {
+    fn from(tenant: &Tenant) -> Self {
+        DbTenant {
+            id: tenant.id,
+            name: tenant.name.clone(),
+            phone: tenant.phone.clone(),
+            email: tenant.email.clone(),
+            tax_id: tenant.tax_id.clone(),
+            notes: tenant.notes.clone(),
+        }
+    }
+}
+
+impl From<&Tenant> for DbNewTenant {
+    fn from(tenant: &Tenant) -> Self {
+        DbNewTenant {
+            name: tenant.name.clone(),
+            phone: tenant.phone.clone(),
+            email: tenant.email.clone(),
+            tax_id: tenant.tax_id.clone(),
+            notes: tenant.notes.clone(),
+        }
+    }
+}
+
+impl From<(DbLease, String)> for Lease {
+    fn from((lease, tenant): (DbLease, String)) -> Self {
+        Lease {
+            id: lease.id,
+            house_id: lease.house_id,
+            tenant_id: lease.tenant_id,
+            tenant,
+            start: lease.start_date,
+            end: lease.end_date,
+            rent: lease.rent,
+            currency: lease.currency,
+            deposit: lease.deposit,
+            index_months: lease.index_months,
+            index_rate: lease.index_rate,
+            // The CHECK constraint only allows the known codes
+            status: lease.status.parse().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&Lease> for DbLease {
+    fn from(lease: &Lease) -> Self {
+        DbLease {
+            id: lease.id,
+            house_id: lease.house_id,
+            tenant_id: lease.tenant_id,
+            start_date: lease.start,
+            end_date: lease.end,
+            rent: lease.rent,
+            currency: lease.currency.clone(),
+            deposit: lease.deposit,
+            index_months: lease.index_months,
+            index_rate: lease.index_rate,
+            status: lease.status.code().to_owned(),
+        }
+    }
+}
+
+impl From<&Lease> for DbNewLease {
+    fn from(lease: &Lease) -> Self {
+        DbNewLease {
+            house_id: lease.house_id,
+            tenant_id: lease.tenant_id,
+            start_date: lease.start,
+            end_date: lease.end,
+            rent: lease.rent,
+            currency: lease.currency.clone(),
+            deposit: lease.deposit,
+            index_months: lease.index_months,
+            index_rate: lease.index_rate,
+            status: lease.status.code().to_owned(),
+        }
+    }
+}
+
+impl From<DbPayment> for Payment {
+    fn from(payment: DbPayment) -> Self {
+        Payment {
+            id: payment.id,
+            lease_id: payment.lease_id,
+            date: payment.paid_on,
+            amount: payment.amount,
+            method: payment.method.parse().unwrap_or_default(),
+            notes: payment.notes,
+        }
+    }
+}
+
+impl From<&Payment> for DbNewPayment {
+    fn from(payment: &Payment) -> Self {
+        DbNewPayment {
+            lease_id: payment.lease_id,
+            paid_on: payment.date,
+            amount: payment.amount,
+            method: payment.method.code().to_owned(),
+            notes: payment.notes.clone(),
         }
     }
 }
@@ -122,3 +1033,49 @@ where
 {
     vector.into_iter().map(U::from).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tests::Database;
+
+    #[test]
+    fn area_bounds_include_the_stored_doubles() {
+        let mut db = Database::new("area");
+        let filter = Filter {
+            area: Range {
+                min: 80.2,
+                max: 80.2,
+            },
+            ..Default::default()
+        };
+        let page = db.service.get_houses(&filter, Page::all()).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.houses[0].street, "Avenida Bustamante");
+    }
+
+    #[test]
+    fn pages_with_and_without_limit() {
+        let mut db = Database::new("pages");
+        let filter = Filter::default();
+        let all = db.service.get_houses(&filter, Page::all()).unwrap();
+        assert_eq!(all.houses.len() as i64, all.total);
+
+        let page = Page {
+            offset: 10,
+            limit: Some(4),
+        };
+        let page = db.service.get_houses(&filter, page).unwrap();
+        assert_eq!(page.total, all.total);
+        let ids: Vec<i32> = page.houses.iter().map(|house| house.id).collect();
+        let expected: Vec<i32> = all.houses[10..14].iter().map(|house| house.id).collect();
+        assert_eq!(ids, expected);
+
+        let rest = Page {
+            offset: 10,
+            limit: None,
+        };
+        let rest = db.service.get_houses(&filter, rest).unwrap();
+        assert_eq!(rest.houses.len() as i64, all.total - 10);
+    }
+}
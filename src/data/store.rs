@@ -0,0 +1,78 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::base::Error;
+
+// Files named by the hash of their content, the same file added twice is kept once.
+// They are spread in subdirectories by the first two characters of the hash.
+#[derive(Debug)]
+pub struct Store {
+    root: PathBuf,
+    temporary: bool, // removed with the store
+}
+
+impl Store {
+    // The files of `casas.sqlite` go to `casas.files`
+    pub fn beside(database: &Path) -> Self {
+        Store {
+            root: database.with_extension("files"),
+            temporary: false,
+        }
+    }
+
+    pub fn path(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(hash)
+    }
+
+    pub fn put(&self, source: &Path) -> Result<(String, i64), Error> {
+        let file_error = |error: io::Error| Error::File(format!("{}: {error}", source.display()));
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut File::open(source).map_err(file_error)?, &mut hasher)
+            .map_err(file_error)?;
+        let hash = format!("{:x}", hasher.finalize());
+        let target = self.path(&hash);
+        if !target.exists() {
+            let store_error =
+                |error: io::Error| Error::File(format!("{}: {error}", target.display()));
+            fs::create_dir_all(target.parent().unwrap()).map_err(store_error)?;
+            // Renamed when complete, an interrupted copy never has the name of a hash
+            let partial = target.with_extension("partial");
+            fs::copy(source, &partial).map_err(store_error)?;
+            fs::rename(&partial, &target).map_err(store_error)?;
+        }
+        Ok((hash, size as i64))
+    }
+
+    pub fn remove(&self, hash: &str) -> Result<(), Error> {
+        match fs::remove_file(self.path(hash)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                Err(Error::File(error.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// For the databases kept in memory
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            root: std::env::temp_dir().join(format!("wohnen-{}", process::id())),
+            temporary: true,
+        }
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+}
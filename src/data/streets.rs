@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{Double, Integer, Nullable, Text},
+    SqliteConnection,
+};
+
+use crate::base::Error;
+use crate::gazetteer::Street;
+
+// The gazetteer is a table of another database, it is not part of the schema of Wohnen
+const QUERY: &str =
+    "SELECT street, postcode, city, province, country, latitude, longitude FROM streets";
+
+#[derive(QueryableByName)]
+struct Row {
+    #[diesel(sql_type = Text)]
+    street: String,
+    #[diesel(sql_type = Nullable<Integer>)]
+    postcode: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    city: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    province: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    country: Option<String>,
+    #[diesel(sql_type = Nullable<Double>)]
+    latitude: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    longitude: Option<f64>,
+}
+
+impl From<Row> for Street {
+    fn from(row: Row) -> Self {
+        Street {
+            name: row.street,
+            postcode: row.postcode.unwrap_or(0),
+            city: row.city.unwrap_or_default(),
+            province: row.province.unwrap_or_default(),
+            country: row.country.unwrap_or_default(),
+            latitude: row.latitude,
+            longitude: row.longitude,
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<Street>, Error> {
+    if !path.is_file() {
+        return Err(Error::Open(format!(
+            "el archivo «{}» no existe",
+            path.display()
+        )));
+    }
+    // The characters that end the path of a URI are escaped
+    let path = path
+        .display()
+        .to_string()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    let url = format!("file:{path}?mode=ro");
+    let mut conn =
+        SqliteConnection::establish(&url).map_err(|error| Error::Open(error.to_string()))?;
+    let rows: Vec<Row> = sql_query(QUERY)
+        .load(&mut conn)
+        .map_err(|error| Error::Database(error.to_string()))?;
+    Ok(rows.into_iter().map(Street::from).collect())
+}
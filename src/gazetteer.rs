@@ -0,0 +1,320 @@
+use crate::base::{normalize, similarity, Error, House, SIMILAR};
+use crate::csv;
+use crate::data::streets;
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+};
+
+use serde::Serialize;
+
+// Columns of a CSV gazetteer, only the street is required
+const COLUMNS: &[&str] = &[
+    "street",
+    "postcode",
+    "city",
+    "province",
+    "country",
+    "latitude",
+    "longitude",
+];
+
+// Most candidates offered for an ambiguous street
+const CANDIDATES: usize = 10;
+
+// A street can cross several postcodes, so another postcode lowers the score without discarding it
+const OTHER_POSTCODE: f64 = 0.9;
+
+#[derive(Debug)]
+pub enum GazetteerError {
+    Io(io::Error),
+    Dao(Error),
+    Header(String),
+    Row(usize, String),
+}
+
+impl Display for GazetteerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "No se pudo leer el callejero: {error}"),
+            Self::Dao(error) => write!(f, "No se pudo leer el callejero: {error}"),
+            Self::Header(cause) => write!(f, "La cabecera del callejero no es válida: {cause}"),
+            Self::Row(line, cause) => {
+                write!(f, "El callejero tiene un error en la línea {line}: {cause}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GazetteerError {}
+
+// The postcode is 0 and the texts are empty when the gazetteer does not give them
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Street {
+    pub name: String,
+    pub postcode: i32,
+    pub city: String,
+    pub province: String,
+    pub country: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl Street {
+    pub fn apply(&self, house: &mut House) {
+        house.street = self.name.clone();
+        if house.postcode == 0 {
+            house.postcode = self.postcode;
+        }
+        for (field, value) in [
+            (&mut house.city, &self.city),
+            (&mut house.province, &self.province),
+            (&mut house.country, &self.country),
+        ] {
+            if field.is_empty() {
+                field.clone_from(value);
+            }
+        }
+        if house.coordinates().is_none() && self.latitude.is_some() && self.longitude.is_some() {
+            house.latitude = self.latitude;
+            house.longitude = self.longitude;
+        }
+    }
+
+    pub fn place(&self) -> String {
+        let postcode = if self.postcode > 0 {
+            self.postcode.to_string()
+        } else {
+            String::new()
+        };
+        [postcode.as_str(), &self.city, &self.province]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub street: Street,
+    pub score: f64, // from SIMILAR to 1
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>4.0} %  {}", 100.0 * self.score, self.street.name)?;
+        match self.street.place() {
+            place if place.is_empty() => Ok(()),
+            place => write!(f, "  ({place})"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Resolution {
+    Unknown,
+    Exact(Street),
+    Ambiguous(Vec<Candidate>),
+}
+
+#[derive(Debug, Default)]
+pub struct Gazetteer {
+    streets: Vec<(String, Street)>, // normalized name and street
+}
+
+impl Gazetteer {
+    // SQLite unless it is .csv
+    pub fn load(path: &Path) -> Result<Self, GazetteerError> {
+        let is_csv = path
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("csv"));
+        let streets = if is_csv {
+            read_csv(&fs::read_to_string(path).map_err(GazetteerError::Io)?)?
+        } else {
+            streets::read(path).map_err(GazetteerError::Dao)?
+        };
+        let streets = streets
+            .into_iter()
+            .filter(|street| !street.name.trim().is_empty())
+            .map(|street| (normalize(street.name.trim()), street))
+            .collect();
+        Ok(Gazetteer { streets })
+    }
+
+    pub fn count(&self) -> usize {
+        self.streets.len()
+    }
+
+    // The score is the same that the text filters use, a street that contains the text counts as similar
+    pub fn candidates(&self, street: &str, postcode: i32) -> Vec<Candidate> {
+        let text = normalize(street.trim());
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let mut candidates: Vec<Candidate> = self
+            .streets
+            .iter()
+            .filter_map(|(name, street)| {
+                let mut score = match name {
+                    _ if *name == text => 1.0,
+                    _ if name.contains(&text) => similarity(&text, name).max(SIMILAR),
+                    _ => similarity(&text, name),
+                };
+                if postcode > 0 && street.postcode > 0 && street.postcode != postcode {
+                    score *= OTHER_POSTCODE;
+                }
+                (score >= SIMILAR).then(|| Candidate {
+                    street: street.clone(),
+                    score,
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(CANDIDATES);
+        candidates
+    }
+
+    // A house without a postcode accepts the street of any postcode
+    pub fn resolve(&self, street: &str, postcode: i32) -> Resolution {
+        let text = normalize(street.trim());
+        let same_postcode =
+            |street: &Street| postcode == 0 || street.postcode == 0 || street.postcode == postcode;
+        let exact: Vec<&Street> = self
+            .streets
+            .iter()
+            .filter(|(name, street)| *name == text && same_postcode(street))
+            .map(|(_, street)| street)
+            .collect();
+        if let [street] = exact.as_slice() {
+            return Resolution::Exact((*street).clone());
+        }
+        let candidates = self.candidates(street, postcode);
+        if candidates.is_empty() {
+            Resolution::Unknown
+        } else {
+            Resolution::Ambiguous(candidates)
+        }
+    }
+}
+
+fn read_csv(text: &str) -> Result<Vec<Street>, GazetteerError> {
+    let mut records = csv::parse(text).into_iter();
+    let Some((_, header)) = records.next() else {
+        return Err(GazetteerError::Header("el archivo está vacío".to_owned()));
+    };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    let columns: Vec<Option<usize>> = COLUMNS
+        .iter()
+        .map(|name| header.iter().position(|h| h == name))
+        .collect();
+    if columns[0].is_none() {
+        return Err(GazetteerError::Header(
+            "falta la columna «street»".to_owned(),
+        ));
+    }
+
+    let mut streets = Vec::new();
+    for (line, record) in records {
+        let field = |i: usize| {
+            columns[i]
+                .and_then(|c| record.get(c))
+                .map(|f| f.trim())
+                .unwrap_or("")
+        };
+        let number = |i: usize| match field(i) {
+            "" => Ok(None),
+            value => value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(Some)
+                .ok_or_else(|| {
+                    GazetteerError::Row(
+                        line,
+                        format!("«{value}» no es un número en «{}»", COLUMNS[i]),
+                    )
+                }),
+        };
+        let postcode = match field(1) {
+            "" => 0,
+            value => value.parse().map_err(|_| {
+                GazetteerError::Row(line, format!("«{value}» no es un código postal"))
+            })?,
+        };
+        streets.push(Street {
+            name: field(0).to_owned(),
+            postcode,
+            city: field(2).to_owned(),
+            province: field(3).to_owned(),
+            country: field(4).to_owned(),
+            latitude: number(5)?,
+            longitude: number(6)?,
+        });
+    }
+    Ok(streets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    fn load(text: &str) -> Result<Gazetteer, GazetteerError> {
+        let path = env::temp_dir().join(format!("wohnen-gazetteer-{}.csv", process::id()));
+        fs::write(&path, text).unwrap();
+        let gazetteer = Gazetteer::load(&path);
+        let _ = fs::remove_file(&path);
+        gazetteer
+    }
+
+    #[test]
+    fn resolve_by_name_and_postcode() {
+        let gazetteer = load(
+            "street,postcode,city\n\
+             Avenida Bustamante,4321,Salta\n\
+             Calle el Ceibo,4321,Salta\n\
+             San Martín,4321,Salta\n\
+             San Martín,5000,Córdoba\n",
+        )
+        .unwrap();
+        assert_eq!(gazetteer.count(), 4);
+        match gazetteer.resolve("  AVENIDA bustamante ", 4321) {
+            Resolution::Exact(street) => assert_eq!(street.name, "Avenida Bustamante"),
+            resolution => panic!("{resolution:?}"),
+        }
+        match gazetteer.resolve("san martin", 5000) {
+            Resolution::Exact(street) => assert_eq!(street.city, "Córdoba"),
+            resolution => panic!("{resolution:?}"),
+        }
+        // Without a postcode both are the same street
+        match gazetteer.resolve("san martin", 0) {
+            Resolution::Ambiguous(candidates) => assert_eq!(candidates.len(), 2),
+            resolution => panic!("{resolution:?}"),
+        }
+        // In another postcode it is only similar, the best candidate first
+        match gazetteer.resolve("Avenida Bustamente", 1000) {
+            Resolution::Ambiguous(candidates) => {
+                assert_eq!(candidates[0].street.name, "Avenida Bustamante");
+                assert!(candidates
+                    .windows(2)
+                    .all(|pair| pair[0].score >= pair[1].score));
+            }
+            resolution => panic!("{resolution:?}"),
+        }
+        assert!(matches!(
+            gazetteer.resolve("Rivadavia", 4321),
+            Resolution::Unknown
+        ));
+        assert!(matches!(gazetteer.resolve("", 4321), Resolution::Unknown));
+    }
+
+    #[test]
+    fn gazetteer_needs_the_street_column() {
+        let result = load("calle,postcode\nMitre,1000\n");
+        assert!(matches!(result, Err(GazetteerError::Header(_))));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::base::{Error, Filter, House, Page, DAO};
+use crate::csv::{self, Report, RowError};
+
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+// Increase it when a field of `House` or `Filter` changes its meaning or type,
+// new optional fields do not require a new version
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    Dao(Error),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "No se pudo acceder al archivo: {error}"),
+            Self::Format(error) => write!(f, "El documento JSON no es válido: {error}"),
+            Self::Version(version) => write!(
+                f,
+                "El documento usa la versión {version} del esquema y solo se admite hasta la {SCHEMA_VERSION}"
+            ),
+            Self::Dao(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<io::Error> for JsonError {
+    fn from(error: io::Error) -> Self {
+        JsonError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Format(error)
+    }
+}
+
+impl From<Error> for JsonError {
+    fn from(error: Error) -> Self {
+        JsonError::Dao(error)
+    }
+}
+
+pub fn is_json(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("json"))
+}
+
+pub fn write<T: Serialize, W: Write + ?Sized>(data: &T, writer: &mut W) -> Result<(), JsonError> {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        data,
+    };
+    serde_json::to_writer_pretty(&mut *writer, &document)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read<T: DeserializeOwned, R: Read>(reader: &mut R) -> Result<T, JsonError> {
+    // The version is checked before the data, a newer layout would fail with a misleading error
+    let document: Document<Value> = serde_json::from_reader(reader)?;
+    if document.version > SCHEMA_VERSION {
+        return Err(JsonError::Version(document.version));
+    }
+    Ok(T::deserialize(document.data)?)
+}
+
+pub fn save_filter(path: &Path, filter: &Filter) -> Result<(), JsonError> {
+    write(filter, &mut BufWriter::new(File::create(path)?))
+}
+
+pub fn load_filter(path: &Path) -> Result<Filter, JsonError> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+pub fn export<W: Write + ?Sized>(
+    dao: &mut dyn DAO,
+    filter: &Filter,
+    writer: &mut W,
+) -> Result<usize, JsonError> {
+    let page = dao.get_houses(filter, Page::all())?;
+    write(&page.houses, writer)?;
+    Ok(page.houses.len())
+}
+
+// The ids of the document are not kept
+pub fn import<R: Read>(dao: &mut dyn DAO, reader: &mut R) -> Result<Report, JsonError> {
+    let houses: Vec<House> = read(reader)?;
+    let rows = houses
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut house)| {
+            // The owners are ids of the database that wrote the document
+            house.owners.clear();
+            (index + 1, house)
+        })
+        .collect();
+    let mut report = Report::default();
+    report.errors = csv::create_batch(dao, rows, &mut report)?
+        .into_iter()
+        .map(|(element, message)| RowError {
+            position: format!("elemento {element}"),
+            message,
+        })
+        .collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{Near, Range, Text};
+
+    #[test]
+    fn partial_ranges_keep_the_default_bounds() {
+        let document = r#"{"version": 1, "data": {"rooms": {"min": 2}, "area": {"max": 80.5}, "kind": "Casa"}}"#;
+        let filter: Filter = read(&mut document.as_bytes()).unwrap();
+        assert_eq!(
+            filter.rooms,
+            Range {
+                min: 2,
+                max: i32::MAX
+            }
+        );
+        assert_eq!(
+            filter.area,
+            Range {
+                min: 0.0,
+                max: 80.5
+            }
+        );
+        assert_eq!(filter.price, Range::default());
+        assert_eq!(filter.kind.text, "Casa");
+    }
+
+    #[test]
+    fn filters_round_trip() {
+        let filter = Filter {
+            street: Text {
+                text: "Corrientes".to_owned(),
+            },
+            rooms: Range { min: 2, max: 4 },
+            any_tags: vec!["cochera".to_owned()],
+            near: Some(Near {
+                latitude: -34.6,
+                longitude: -58.4,
+                radius: 5.0,
+            }),
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        write(&filter, &mut buffer).unwrap();
+        let read: Filter = read(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read, filter);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let document = format!(r#"{{"version": {}, "data": {{}}}}"#, SCHEMA_VERSION + 1);
+        let result: Result<Filter, JsonError> = read(&mut document.as_bytes());
+        assert!(matches!(result, Err(JsonError::Version(_))));
+    }
+}
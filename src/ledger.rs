@@ -0,0 +1,202 @@
+use crate::base::{Error, Lease, DAO};
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+// Differences below a cent are rounding of the updates, not debts
+const CENT: f64 = 0.005;
+
+// The charges are not stored, they follow from the terms of the lease
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub date: NaiveDate,
+    pub lease_id: i32,
+    pub payment_id: Option<i32>, // none for the charges
+    pub concept: String,
+    pub currency: String,
+    pub charge: f64,
+    pub payment: f64,
+    pub balance: f64, // owed in the currency of the entry, negative when paid in advance
+}
+
+pub fn account(dao: &mut dyn DAO, tenant_id: i32, today: NaiveDate) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    for lease in dao.get_tenant_leases(tenant_id)? {
+        for (due, amount) in lease.charges(today) {
+            entries.push(Entry {
+                date: due,
+                lease_id: lease.id,
+                payment_id: None,
+                concept: format!("Alquiler {} del contrato {}", due.format("%Y-%m"), lease.id),
+                currency: lease.currency.clone(),
+                charge: amount,
+                payment: 0.0,
+                balance: 0.0,
+            });
+        }
+        for payment in dao.get_payments(lease.id)? {
+            let method = payment.method.to_string().to_lowercase();
+            let mut concept = format!("Pago del contrato {} en {method}", lease.id);
+            if !payment.notes.is_empty() {
+                concept = format!("{concept}: {}", payment.notes);
+            }
+            entries.push(Entry {
+                date: payment.date,
+                lease_id: lease.id,
+                payment_id: Some(payment.id),
+                concept,
+                currency: lease.currency.clone(),
+                charge: 0.0,
+                payment: payment.amount,
+                balance: 0.0,
+            });
+        }
+    }
+    // The charges of a day go before its payments, stable for the rest
+    entries.sort_by_key(|e| (e.date, e.payment_id.is_some()));
+    let mut balances: HashMap<String, f64> = HashMap::new();
+    for entry in &mut entries {
+        let balance = balances.entry(entry.currency.clone()).or_default();
+        *balance += entry.charge - entry.payment;
+        entry.balance = *balance;
+    }
+    Ok(entries)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Overdue {
+    pub lease: Lease,
+    pub house: String,
+    pub owed: f64,
+    pub since: NaiveDate, // due date of the oldest charge not paid
+}
+
+impl Overdue {
+    pub fn days(&self, today: NaiveDate) -> i64 {
+        (today - self.since).num_days()
+    }
+}
+
+// The payments settle the charges in the order they were due
+pub fn overdue(dao: &mut dyn DAO, today: NaiveDate) -> Result<Vec<Overdue>, Error> {
+    let mut report = Vec::new();
+    for lease in dao.get_active_leases()? {
+        let charges = lease.charges(today);
+        let paid: f64 = dao.get_payments(lease.id)?.iter().map(|p| p.amount).sum();
+        let owed = charges.iter().map(|&(_, amount)| amount).sum::<f64>() - paid;
+        let mut left = paid;
+        let unpaid = charges.into_iter().find(|&(_, amount)| {
+            left -= amount;
+            left < -CENT
+        });
+        let Some((since, _)) = unpaid else {
+            continue;
+        };
+        // What is due today is not late yet
+        if since >= today || owed < CENT {
+            continue;
+        }
+        let house = dao.get_house(lease.house_id)?;
+        report.push(Overdue {
+            house: format!("{} al {}", house.street, house.number),
+            lease,
+            owed,
+            since,
+        });
+    }
+    report.sort_by_key(|o| o.since);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{House, LeaseStatus, Payment, PaymentMethod, Tenant, CURRENCY};
+    use crate::data::Memory;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    // A house for each (start, rent, paid), all of them rented by the same tenant
+    fn rented(leases: &[(&str, f64, f64)]) -> Memory {
+        let mut dao = Memory::default();
+        dao.create_house_type("Casa").unwrap();
+        let tenant = Tenant {
+            name: "Laura Gómez".to_owned(),
+            ..Default::default()
+        };
+        let tenant = dao.create_tenant(&tenant).unwrap();
+        for (number, &(start, rent, paid)) in leases.iter().enumerate() {
+            let house = House {
+                kind: "Casa".to_owned(),
+                street: "Mitre".to_owned(),
+                number: number as i32 + 1,
+                currency: CURRENCY.to_owned(),
+                ..Default::default()
+            };
+            let house = dao.create_house(&house).unwrap();
+            let lease = Lease {
+                id: -1,
+                house_id: house.id,
+                tenant_id: tenant.id,
+                tenant: String::new(),
+                start: date(start),
+                end: date("2030-12-31"),
+                rent,
+                currency: CURRENCY.to_owned(),
+                deposit: 0.0,
+                index_months: 0,
+                index_rate: 0.0,
+                status: LeaseStatus::Active,
+            };
+            let lease = dao.create_lease(&lease).unwrap();
+            if paid > 0.0 {
+                let payment = Payment {
+                    id: -1,
+                    lease_id: lease.id,
+                    date: date(start),
+                    amount: paid,
+                    method: PaymentMethod::Cash,
+                    notes: String::new(),
+                };
+                dao.create_payment(&payment).unwrap();
+            }
+        }
+        dao
+    }
+
+    #[test]
+    fn overdue_leases_oldest_debt_first() {
+        let mut dao = rented(&[
+            ("2024-01-10", 100.0, 250.0), // half of the third month is owed
+            ("2024-02-01", 100.0, 0.0),   // nothing paid
+            ("2024-04-01", 200.0, 400.0), // paid up
+            ("2024-05-15", 300.0, 0.0),   // the first month is due today
+        ]);
+        let report = overdue(&mut dao, date("2024-05-15")).unwrap();
+        let owed: Vec<(&str, NaiveDate, f64)> = report
+            .iter()
+            .map(|o| (o.house.as_str(), o.since, o.owed))
+            .collect();
+        assert_eq!(
+            owed,
+            [
+                ("Mitre al 2", date("2024-02-01"), 400.0),
+                ("Mitre al 1", date("2024-03-10"), 250.0)
+            ]
+        );
+        assert_eq!(report[0].days(date("2024-05-15")), 104);
+    }
+
+    #[test]
+    fn account_balance_by_date() {
+        let mut dao = rented(&[("2024-01-10", 100.0, 250.0)]);
+        let entries = account(&mut dao, 1, date("2024-03-10")).unwrap();
+        let balances: Vec<f64> = entries.iter().map(|entry| entry.balance).collect();
+        // The payment of the first day goes after its charge
+        assert_eq!(balances, [100.0, -150.0, -50.0, 50.0]);
+    }
+}
@@ -3,10 +3,111 @@
  */
 mod app;
 mod base;
+mod cli;
+mod config;
+mod csv;
 mod data;
+mod gazetteer;
+mod json;
+mod ledger;
+mod utils;
+
+use base::DAO;
+use config::Config;
+
+use std::{env, io, path::PathBuf, process::exit};
+
+fn fail(message: &str, gui: bool) -> ! {
+    eprintln!("{message}");
+    if gui {
+        app::alert(message);
+    }
+    exit(1);
+}
 
 fn main() {
-    let mut dao = data::Service::new();
-    let mut gui = app::Gui::new(&mut dao);
-    gui.run();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // The global options go before the command, after it they are arguments of the command.
+    // --demo works on a throwaway copy of the sample data
+    let (mut demo, mut create, mut db) = (false, false, None);
+    let mut globals = 0;
+    loop {
+        match args.get(globals).map(String::as_str) {
+            Some("--demo") => demo = true,
+            Some("--create") => create = true,
+            Some("--db") => match args.get(globals + 1) {
+                Some(path) => {
+                    db = Some(PathBuf::from(path));
+                    globals += 1;
+                }
+                None => fail(
+                    "Falta el valor de --db\nUse «wohnen help» para ver los comandos",
+                    false,
+                ),
+            },
+            _ => break,
+        }
+        globals += 1;
+    }
+    args.drain(..globals);
+
+    // After the global options, so «wohnen --demo help» also shows the usage
+    if matches!(
+        args.first().map(String::as_str),
+        Some("help" | "--help" | "-h")
+    ) {
+        print!("{}", cli::USAGE);
+        return;
+    }
+    let gui = args.is_empty();
+
+    let mut config = Config::load().unwrap_or_else(|error| {
+        eprintln!("{error}");
+        Config::default()
+    });
+
+    let (mut dao, database): (Box<dyn DAO>, Option<PathBuf>) = if demo {
+        (Box::new(data::Memory::demo(&config.user())), None)
+    } else {
+        let (path, create) = match db {
+            Some(path) => (path, create),
+            None => {
+                for path in config.forget_missing() {
+                    eprintln!(
+                        "La base de datos reciente «{}» ya no existe",
+                        path.display()
+                    );
+                }
+                config.database(config::database_url().as_deref())
+            }
+        };
+        match data::Service::open(&path, create, &config.user()) {
+            Ok(dao) => {
+                let path = config.add_recent(&path);
+                if let Err(error) = config.save() {
+                    eprintln!("{error}");
+                }
+                (Box::new(dao), Some(path))
+            }
+            Err(error) => fail(&format!("{error}\nBase de datos: {}", path.display()), gui),
+        }
+    };
+
+    if let Some(before) = config.purge_before() {
+        if let Err(error) = dao.purge_deleted(before) {
+            eprintln!("No se pudo vaciar la papelera: {error}");
+        }
+    }
+
+    if gui {
+        let mut gui = app::Gui::new(dao, database, config);
+        gui.run();
+    } else {
+        let gazetteer = config.gazetteer.as_deref();
+        if let Err(error) = cli::run(dao.as_mut(), gazetteer, &args, &mut io::stdout().lock()) {
+            eprintln!("{error}");
+            exit(1);
+        }
+    }
 }
@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+pub trait Number: PartialOrd + FromStr + Default {
+    fn is_finite(&self) -> bool;
+}
+
+impl Number for i32 {
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+macro_rules! float_number_impl {
+    ($t:ty) => {
+        impl Number for $t {
+            fn is_finite(&self) -> bool {
+                <$t>::is_finite(*self)
+            }
+        }
+    };
+}
+float_number_impl!(f32);
+float_number_impl!(f64);
+
+// "inf" and "NaN" parse as floats, but are not numbers the database can keep
+pub fn test_positive_and_zero_number<T: Number>(value: &str) -> bool {
+    match value.trim().parse::<T>() {
+        Ok(n) => n.is_finite() && n >= T::default(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_and_zero_numbers() {
+        assert!(test_positive_and_zero_number::<i32>(" 0 "));
+        assert!(test_positive_and_zero_number::<i32>("12"));
+        assert!(!test_positive_and_zero_number::<i32>("-1"));
+        assert!(!test_positive_and_zero_number::<i32>("1.5"));
+        assert!(test_positive_and_zero_number::<f32>("80.2"));
+        assert!(!test_positive_and_zero_number::<f32>("-0.1"));
+        assert!(!test_positive_and_zero_number::<f64>(""));
+    }
+
+    #[test]
+    fn infinite_and_nan_are_rejected() {
+        for value in ["inf", "-inf", "infinity", "NaN", "nan"] {
+            assert!(!test_positive_and_zero_number::<f32>(value), "{value}");
+            assert!(!test_positive_and_zero_number::<f64>(value), "{value}");
+        }
+        assert!(!test_positive_and_zero_number::<f64>("1e400"));
+    }
+}